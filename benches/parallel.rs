@@ -0,0 +1,115 @@
+//! Throughput of the parallel bulk functions as the thread count grows
+//!
+//! Run with `cargo bench --bench parallel`. Each function is compared against
+//! its serial counterpart on the same buffer.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use project_1_aes::constant::KeyType;
+use project_1_aes::ctr::{ctr_encrypt, ctr_encrypt_parallel};
+use project_1_aes::ecb::{ecb_encrypt, ecb_encrypt_parallel};
+use project_1_aes::key_expansion::key_expansion;
+use project_1_aes::padding::ZeroPadding;
+use project_1_aes::xts::{xts_encrypt_sectors, xts_encrypt_sectors_parallel};
+
+const BUFFER_LEN: usize = 4 << 20;
+const SECTOR_SIZE: usize = 4096;
+const THREAD_COUNTS: [usize; 5] = [1, 2, 4, 8, 16];
+
+/// The fastest of a few runs, to smooth out noise from the rest of the system
+fn time(mut f: impl FnMut()) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, serial: Duration, mut parallel: impl FnMut(usize) -> Duration) {
+    let throughput = |duration: Duration| BUFFER_LEN as f64 / duration.as_secs_f64() / 1e6;
+
+    println!("{name}");
+    println!("  serial      {:8.2} MB/s", throughput(serial));
+    for threads in THREAD_COUNTS {
+        let duration = parallel(threads);
+        println!(
+            "  {threads:2} threads  {:8.2} MB/s  ({:.2}x)",
+            throughput(duration),
+            serial.as_secs_f64() / duration.as_secs_f64()
+        );
+    }
+}
+
+fn main() {
+    let key_type = KeyType::Aes128;
+    let expanded_key = key_expansion(&[0x2b; 16], key_type);
+    let tweak_key = key_expansion(&[0x7e; 16], key_type);
+    let buffer: Vec<u8> = (0..=255).cycle().take(BUFFER_LEN).collect();
+    let counter = [0; 16];
+
+    report(
+        "CTR",
+        time(|| {
+            black_box(ctr_encrypt(&buffer, &counter, &expanded_key, key_type));
+        }),
+        |threads| {
+            time(|| {
+                black_box(ctr_encrypt_parallel(
+                    &buffer,
+                    &counter,
+                    threads,
+                    &expanded_key,
+                    key_type,
+                ));
+            })
+        },
+    );
+
+    report(
+        "ECB",
+        time(|| {
+            black_box(ecb_encrypt(&buffer, &ZeroPadding, &expanded_key, key_type));
+        }),
+        |threads| {
+            time(|| {
+                black_box(ecb_encrypt_parallel(
+                    &buffer,
+                    &ZeroPadding,
+                    threads,
+                    &expanded_key,
+                    key_type,
+                ));
+            })
+        },
+    );
+
+    report(
+        "XTS",
+        time(|| {
+            black_box(
+                xts_encrypt_sectors(&buffer, 0, SECTOR_SIZE, &expanded_key, &tweak_key, key_type)
+                    .unwrap(),
+            );
+        }),
+        |threads| {
+            time(|| {
+                black_box(
+                    xts_encrypt_sectors_parallel(
+                        &buffer,
+                        0,
+                        SECTOR_SIZE,
+                        threads,
+                        &expanded_key,
+                        &tweak_key,
+                        key_type,
+                    )
+                    .unwrap(),
+                );
+            })
+        },
+    );
+}
@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+use std::{env, fs};
+
+use project_1_aes::constant::KeyType;
+use project_1_aes::container::{DEFAULT_CHUNK_SIZE, MAX_CHUNK_SIZE};
+use project_1_aes::encoding::{decode_any, Encoding};
+use project_1_aes::openssl::{Digest, KeyDerivation, DEFAULT_ITERATIONS as OPENSSL_ITERATIONS};
+
+use crate::error::CliError;
+
+/// Options that are followed by a value
+const VALUE_OPTIONS: [&str; 19] = [
+    "--key",
+    "--key-size",
+    "--password",
+    "--iterations",
+    "--openssl",
+    "--mode",
+    "--iv",
+    "--nonce",
+    "--aad",
+    "--padding",
+    "--in",
+    "--out",
+    "--in-format",
+    "--out-format",
+    "--block",
+    "--tag-len",
+    "--chunk-size",
+    "--offset",
+    "--length",
+];
+
+/// Options that stand on their own
+const FLAGS: [&str; 1] = ["--inverse"];
+
+/// A subcommand and its options
+#[derive(Debug)]
+pub struct Args {
+    pub command: String,
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Args {
+    pub fn parse(args: &[String]) -> Result<Self, CliError> {
+        let (command, mut rest) = match args {
+            [command, rest @ ..] => (command.clone(), rest.iter()),
+            [] => return Err(CliError::Usage("no command given".to_string())),
+        };
+
+        let mut values = HashMap::new();
+        let mut flags = HashSet::new();
+
+        while let Some(arg) = rest.next() {
+            if FLAGS.contains(&arg.as_str()) {
+                flags.insert(arg.clone());
+            } else if VALUE_OPTIONS.contains(&arg.as_str()) {
+                let value = rest
+                    .next()
+                    .ok_or_else(|| CliError::Usage(format!("{arg} needs a value")))?;
+                if values.insert(arg.clone(), value.clone()).is_some() {
+                    return Err(CliError::Usage(format!("{arg} was given more than once")));
+                }
+            } else {
+                return Err(CliError::Usage(format!("unknown option {arg}")));
+            }
+        }
+
+        Ok(Self {
+            command,
+            values,
+            flags,
+        })
+    }
+
+    pub fn get(&self, option: &str) -> Option<&str> {
+        self.values.get(option).map(String::as_str)
+    }
+
+    pub fn require(&self, option: &str) -> Result<&str, CliError> {
+        self.get(option)
+            .ok_or_else(|| CliError::Usage(format!("{} needs {option}", self.command)))
+    }
+
+    pub fn flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    /// The `--key-size` in bits, defaulting to 128
+    pub fn key_size(&self) -> Result<KeyType, CliError> {
+        match self.get("--key-size").unwrap_or("128") {
+            "128" => Ok(KeyType::Aes128),
+            "192" => Ok(KeyType::Aes192),
+            "256" => Ok(KeyType::Aes256),
+            other => Err(CliError::Usage(format!(
+                "key size must be 128, 192 or 256, not {other}"
+            ))),
+        }
+    }
+
+    /// The `--key`, along with its key type
+    ///
+    /// The key type is worked out from the key's length unless `--key-size`
+    /// is given, in which case the key has to match it.
+    pub fn key(&self) -> Result<(Vec<u8>, KeyType), CliError> {
+        let key_size = match self.get("--key-size") {
+            Some(_) => Some(self.key_size()?),
+            None => None,
+        };
+        let key = self.key_for_size(self.bytes("--key", None)?, key_size)?;
+
+        let key_type = match key_size {
+            Some(key_type) => key_type,
+            None => KeyType::from_key_len(key.len()).ok_or_else(|| {
+                CliError::Usage(format!(
+                    "--key must be 16, 24 or 32 bytes, not {}",
+                    key.len()
+                ))
+            })?,
+        };
+        if key.len() != key_type.n_k() * 4 {
+            return Err(CliError::Usage(format!(
+                "--key is {} bytes, which doesn't match --key-size",
+                key.len()
+            )));
+        }
+
+        Ok((key, key_type))
+    }
+
+    /// Check a `--key` that was decoded as hex against its base64 reading
+    ///
+    /// A value without a prefix can be a key either way (32 characters is an
+    /// AES-128 key as hex but an AES-192 key as base64). Hex is tried first,
+    /// but if `--key-size` only matches the base64 reading, that's used.
+    fn key_for_size(&self, key: Vec<u8>, key_size: Option<KeyType>) -> Result<Vec<u8>, CliError> {
+        let value = self.require("--key")?;
+        match (key_size, Encoding::Base64.decode(value)) {
+            (Some(key_type), Ok(base64))
+                if !value.contains(':')
+                    && key.len() != key_type.n_k() * 4
+                    && base64.len() == key_type.n_k() * 4 =>
+            {
+                Ok(base64)
+            }
+            _ => Ok(key),
+        }
+    }
+
+    /// The `--password`, which is used as it is unless it's `env:NAME` or
+    /// `file:PATH` to read it from an environment variable or the first line
+    /// of a file
+    pub fn password(&self) -> Result<Vec<u8>, CliError> {
+        let value = self.require("--password")?;
+        let password = if let Some(name) = value.strip_prefix("env:") {
+            env::var(name)
+                .map_err(|_| CliError::Usage(format!("environment variable {name} isn't set")))?
+        } else if let Some(path) = value.strip_prefix("file:") {
+            let contents = fs::read_to_string(path)?;
+            contents.lines().next().unwrap_or("").to_string()
+        } else {
+            value.to_string()
+        };
+
+        if password.is_empty() {
+            return Err(CliError::Usage("--password is empty".to_string()));
+        }
+        Ok(password.into_bytes())
+    }
+
+    /// The `--iterations` for deriving a key from a password
+    pub fn iterations(&self, default: u32) -> Result<u32, CliError> {
+        match self.get("--iterations").map(str::parse) {
+            None => Ok(default),
+            Some(Ok(iterations @ 1..)) => Ok(iterations),
+            Some(_) => Err(CliError::Usage(
+                "--iterations must be a whole number from 1 to 4294967295".to_string(),
+            )),
+        }
+    }
+
+    /// The `--chunk-size` for gcm-stream
+    pub fn chunk_size(&self) -> Result<u32, CliError> {
+        match self.get("--chunk-size").map(str::parse) {
+            None => Ok(DEFAULT_CHUNK_SIZE),
+            Some(Ok(chunk_size @ 1..=MAX_CHUNK_SIZE)) => Ok(chunk_size),
+            Some(_) => Err(CliError::Usage(format!(
+                "--chunk-size must be from 1 to {MAX_CHUNK_SIZE}"
+            ))),
+        }
+    }
+
+    /// The plaintext range to decrypt from `--offset` (0 by default) and
+    /// `--length` (the rest by default), if either was given
+    pub fn range(&self) -> Result<Option<(u64, usize)>, CliError> {
+        if self.get("--offset").is_none() && self.get("--length").is_none() {
+            return Ok(None);
+        }
+
+        let offset = match self.get("--offset").map(str::parse) {
+            None => 0,
+            Some(Ok(offset)) => offset,
+            Some(Err(_)) => {
+                return Err(CliError::Usage(
+                    "--offset must be a whole number".to_string(),
+                ))
+            }
+        };
+        let length = match self.get("--length").map(str::parse) {
+            None => usize::MAX,
+            Some(Ok(length)) => length,
+            Some(Err(_)) => {
+                return Err(CliError::Usage(
+                    "--length must be a whole number".to_string(),
+                ))
+            }
+        };
+        Ok(Some((offset, length)))
+    }
+
+    /// How `openssl enc` derived the key, if `--openssl` was given
+    pub fn openssl(&self) -> Result<Option<KeyDerivation>, CliError> {
+        match self.get("--openssl") {
+            None => Ok(None),
+            Some("pbkdf2") => Ok(Some(KeyDerivation::Pbkdf2 {
+                iterations: self.iterations(OPENSSL_ITERATIONS)?,
+            })),
+            Some("md5") => Ok(Some(KeyDerivation::BytesToKey(Digest::Md5))),
+            Some("sha256") => Ok(Some(KeyDerivation::BytesToKey(Digest::Sha256))),
+            Some(other) => Err(CliError::Usage(format!(
+                "--openssl must be pbkdf2, md5 or sha256, not {other}"
+            ))),
+        }
+    }
+
+    /// Decode the value of `option`, which must be `len` bytes long if given
+    ///
+    /// Values are hex or base64 (see [`decode_any`]), or `file:PATH` to read
+    /// them from a file.
+    pub fn bytes(&self, option: &str, len: Option<usize>) -> Result<Vec<u8>, CliError> {
+        let value = self.require(option)?;
+        let bytes = match value.strip_prefix("file:") {
+            Some(path) => decode_file(fs::read(path)?),
+            None => {
+                decode_any(value).map_err(|error| CliError::Decoding(option.to_string(), error))?
+            }
+        };
+
+        match len {
+            Some(len) if bytes.len() != len => Err(CliError::Usage(format!(
+                "{option} must be {len} bytes, not {}",
+                bytes.len()
+            ))),
+            _ => Ok(bytes),
+        }
+    }
+
+    /// The data format chosen by `option`
+    pub fn format(&self, option: &str, default: Format) -> Result<Format, CliError> {
+        match self.get(option) {
+            None => Ok(default),
+            Some("raw") => Ok(Format::Raw),
+            Some("hex") => Ok(Format::Encoded(Encoding::Hex)),
+            Some("base64") => Ok(Format::Encoded(Encoding::Base64)),
+            Some(other) => Err(CliError::Usage(format!(
+                "{option} must be raw, hex or base64, not {other}"
+            ))),
+        }
+    }
+}
+
+/// How data is read or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Raw,
+    Encoded(Encoding),
+}
+
+/// The contents of a key file, which is used as it is unless it's hex or
+/// base64 text
+fn decode_file(contents: Vec<u8>) -> Vec<u8> {
+    std::str::from_utf8(&contents)
+        .ok()
+        .and_then(|text| decode_any(text.trim()).ok())
+        .unwrap_or(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use project_1_aes::pbkdf2::DEFAULT_ITERATIONS;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, CliError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Args::parse(&args)
+    }
+
+    #[test]
+    fn parse_test() {
+        let args = parse(&["encrypt", "--mode", "cbc", "--inverse", "--key", "00ff"]).unwrap();
+        assert_eq!(args.command, "encrypt");
+        assert_eq!(args.get("--mode"), Some("cbc"));
+        assert_eq!(args.get("--iv"), None);
+        assert!(args.flag("--inverse"));
+        assert_eq!(args.bytes("--key", None).unwrap(), [0x00, 0xff]);
+        assert!(matches!(
+            args.bytes("--key", Some(16)),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(args.key_size(), Ok(KeyType::Aes128)));
+        assert_eq!(
+            args.format("--in-format", Format::Raw).unwrap(),
+            Format::Raw
+        );
+
+        assert!(matches!(parse(&[]), Err(CliError::Usage(_))));
+        assert!(matches!(
+            parse(&["encrypt", "--key"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["encrypt", "--bogus"]),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            parse(&["encrypt", "--in", "a", "--in", "b"]),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn key_test() {
+        let args = parse(&["encrypt", "--key", "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX"]).unwrap();
+        let (key, key_type) = args.key().unwrap();
+        assert_eq!(key, (0..24).collect::<Vec<u8>>());
+        assert!(matches!(key_type, KeyType::Aes192));
+
+        let args = parse(&["encrypt", "--key", "00112233", "--key-size", "128"]).unwrap();
+        assert!(matches!(args.key(), Err(CliError::Usage(_))));
+        let args = parse(&["encrypt", "--key", "00112233"]).unwrap();
+        assert!(matches!(args.key(), Err(CliError::Usage(_))));
+        let args = parse(&["encrypt", "--key", "hex:xyz"]).unwrap();
+        assert!(matches!(args.key(), Err(CliError::Decoding(..))));
+    }
+
+    #[test]
+    fn ambiguous_key_test() {
+        // 16 bytes as hex, but 24 as base64
+        let value = "00112233445566778899aabbccddeeff";
+        let hex: Vec<u8> = (0..16).map(|i| i * 0x11).collect();
+
+        let args = parse(&["encrypt", "--key", value]).unwrap();
+        assert_eq!(args.key().unwrap().0, hex);
+        let args = parse(&["encrypt", "--key", value, "--key-size", "128"]).unwrap();
+        assert_eq!(args.key().unwrap().0, hex);
+        let args = parse(&["encrypt", "--key", value, "--key-size", "192"]).unwrap();
+        assert_eq!(args.key().unwrap().0.len(), 24);
+
+        let args = parse(&["encrypt", "--key", &format!("base64:{value}")]).unwrap();
+        assert_eq!(args.key().unwrap().0.len(), 24);
+        let args = parse(&[
+            "encrypt",
+            "--key",
+            &format!("hex:{value}"),
+            "--key-size",
+            "192",
+        ]);
+        assert!(matches!(args.unwrap().key(), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn password_test() {
+        let args = parse(&["encrypt", "--password", "hunter2", "--iterations", "1000"]).unwrap();
+        assert_eq!(args.password().unwrap(), b"hunter2");
+        assert_eq!(args.iterations(DEFAULT_ITERATIONS).unwrap(), 1000);
+
+        let path = env::temp_dir().join(format!("aes-password-test-{}", std::process::id()));
+        fs::write(&path, "correct horse\nbattery staple\n").unwrap();
+        let args = parse(&["encrypt", "--password", &format!("file:{}", path.display())]).unwrap();
+        assert_eq!(args.password().unwrap(), b"correct horse");
+        assert_eq!(
+            args.iterations(DEFAULT_ITERATIONS).unwrap(),
+            DEFAULT_ITERATIONS
+        );
+        fs::remove_file(path).unwrap();
+
+        let args = parse(&["encrypt", "--password", "", "--iterations", "0"]).unwrap();
+        assert!(matches!(args.password(), Err(CliError::Usage(_))));
+        assert!(matches!(
+            args.iterations(DEFAULT_ITERATIONS),
+            Err(CliError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn openssl_test() {
+        let args = parse(&["decrypt", "--openssl", "pbkdf2"]).unwrap();
+        assert_eq!(
+            args.openssl().unwrap(),
+            Some(KeyDerivation::Pbkdf2 { iterations: 10_000 })
+        );
+        let args = parse(&["decrypt", "--openssl", "md5", "--iterations", "5"]).unwrap();
+        assert_eq!(
+            args.openssl().unwrap(),
+            Some(KeyDerivation::BytesToKey(Digest::Md5))
+        );
+
+        assert_eq!(parse(&["decrypt"]).unwrap().openssl().unwrap(), None);
+        let args = parse(&["decrypt", "--openssl", "sha1"]).unwrap();
+        assert!(matches!(args.openssl(), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn chunk_size_test() {
+        assert_eq!(
+            parse(&["encrypt"]).unwrap().chunk_size().unwrap(),
+            DEFAULT_CHUNK_SIZE
+        );
+        let args = parse(&["encrypt", "--chunk-size", "4096"]).unwrap();
+        assert_eq!(args.chunk_size().unwrap(), 4096);
+
+        for chunk_size in ["0", "16777217", "64k"] {
+            let args = parse(&["encrypt", "--chunk-size", chunk_size]).unwrap();
+            assert!(matches!(args.chunk_size(), Err(CliError::Usage(_))));
+        }
+    }
+
+    #[test]
+    fn range_test() {
+        assert_eq!(parse(&["decrypt"]).unwrap().range().unwrap(), None);
+        let args = parse(&["decrypt", "--offset", "4096", "--length", "10"]).unwrap();
+        assert_eq!(args.range().unwrap(), Some((4096, 10)));
+        let args = parse(&["decrypt", "--offset", "4096"]).unwrap();
+        assert_eq!(args.range().unwrap(), Some((4096, usize::MAX)));
+        let args = parse(&["decrypt", "--length", "10"]).unwrap();
+        assert_eq!(args.range().unwrap(), Some((0, 10)));
+
+        let args = parse(&["decrypt", "--offset", "-1"]).unwrap();
+        assert!(matches!(args.range(), Err(CliError::Usage(_))));
+        let args = parse(&["decrypt", "--length", "4k"]).unwrap();
+        assert!(matches!(args.range(), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn decode_file_test() {
+        assert_eq!(decode_file(b"00ff\n".to_vec()), [0x00, 0xff]);
+        assert_eq!(decode_file(b"base64:AP8=\n".to_vec()), [0x00, 0xff]);
+
+        // anything else is a raw key
+        let raw = vec![0xff, 0x00, 0x80, 0x7f];
+        assert_eq!(decode_file(raw.clone()), raw);
+        assert_eq!(decode_file(b"not hex!".to_vec()), b"not hex!");
+    }
+}
@@ -0,0 +1,611 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Write};
+
+use project_1_aes::cbc::{CbcDecryptor, CbcEncryptor};
+use project_1_aes::cipher::cipher;
+use project_1_aes::constant::KeyType;
+use project_1_aes::container::{
+    AeadMode, ContainerDecryptor, ContainerEncryptor, ContainerReader, Header, Kdf,
+    NONCE_PREFIX_LEN,
+};
+use project_1_aes::ctr::{CtrDecryptor, CtrEncryptor};
+use project_1_aes::ctr_drbg::CtrDrbg;
+use project_1_aes::eax::{EaxDecryptor, EaxEncryptor};
+use project_1_aes::ecb::{EcbDecryptor, EcbEncryptor};
+use project_1_aes::encoding::{encode_hex, Encoding};
+use project_1_aes::inverse_cipher::inverse_cipher;
+use project_1_aes::io::{DecryptingReader, EncryptingWriter};
+use project_1_aes::key_expansion::key_expansion;
+use project_1_aes::ocb::{OcbDecryptor, OcbEncryptor, MAX_NONCE_LEN};
+use project_1_aes::openssl::{self, derive_key_and_iv, KeyDerivation};
+use project_1_aes::padding::{AnsiX923, Iso10126, Iso7816, Padding, Pkcs7, ZeroPadding};
+use project_1_aes::pbkdf2::{DEFAULT_ITERATIONS, SALT_LEN};
+use project_1_aes::stream::{Decryptor, Encryptor};
+
+use crate::args::{Args, Format};
+use crate::error::CliError;
+use crate::password::{read_openssl_salt, DerivedKey, PasswordHeader};
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Ecb,
+    Cbc,
+    Ctr,
+    Ocb,
+    Eax,
+    GcmStream,
+}
+
+impl Mode {
+    fn parse(args: &Args) -> Result<Self, CliError> {
+        match args.require("--mode")? {
+            "ecb" => Ok(Self::Ecb),
+            "cbc" => Ok(Self::Cbc),
+            "ctr" => Ok(Self::Ctr),
+            "ocb" => Ok(Self::Ocb),
+            "eax" => Ok(Self::Eax),
+            "gcm-stream" => Ok(Self::GcmStream),
+            other => Err(CliError::Usage(format!("unknown mode {other}"))),
+        }
+    }
+
+    /// Whether the mode has a tag
+    fn authenticated(self) -> bool {
+        matches!(self, Self::Ocb | Self::Eax | Self::GcmStream)
+    }
+
+    /// Whether decrypted output comes before its tag has been checked, so it
+    /// mustn't be released until the whole input has been
+    ///
+    /// gcm-stream checks every chunk before returning it, so its output can
+    /// be written as it arrives.
+    fn unverified_output(self) -> bool {
+        matches!(self, Self::Ocb | Self::Eax)
+    }
+}
+
+/// Everything `encrypt` and `decrypt` need besides the data itself
+struct Params {
+    mode: Mode,
+    key_type: KeyType,
+    expanded_key: Vec<u32>,
+    iv: [u8; 16],
+    nonce: Vec<u8>,
+    associated_data: Vec<u8>,
+    tag_len: usize,
+    padding: &'static dyn Padding,
+}
+
+impl Params {
+    /// Parse the options, taking the key, IV and nonce from the key `derived`
+    /// from a password instead if there is one
+    fn parse(args: &Args, derived: Option<&DerivedKey>) -> Result<Self, CliError> {
+        let mode = Mode::parse(args)?;
+
+        let (key, key_type) = match derived {
+            Some(derived) => {
+                if ["--key", "--iv", "--nonce"]
+                    .iter()
+                    .any(|option| args.get(option).is_some())
+                {
+                    return Err(CliError::Usage(
+                        "--key, --iv and --nonce can't be used with --password".to_string(),
+                    ));
+                }
+                (derived.key.clone(), derived.key_type)
+            }
+            None => args.key()?,
+        };
+
+        let iv = match (mode, derived) {
+            (Mode::Cbc | Mode::Ctr, Some(derived)) => derived.iv,
+            (Mode::Cbc | Mode::Ctr, None) => args.bytes("--iv", Some(16))?.try_into().unwrap(),
+            _ => [0; 16],
+        };
+        let nonce = match (mode, derived) {
+            (Mode::Ocb | Mode::Eax, Some(derived)) => derived.nonce().to_vec(),
+            (Mode::Ocb | Mode::Eax, None) => args.bytes("--nonce", None)?,
+            _ => Vec::new(),
+        };
+        if matches!(mode, Mode::Ocb) && nonce.len() > MAX_NONCE_LEN {
+            return Err(CliError::Usage(format!(
+                "OCB nonces can be at most {MAX_NONCE_LEN} bytes"
+            )));
+        }
+
+        let associated_data = match args.get("--aad") {
+            Some(_) => args.bytes("--aad", None)?,
+            None => Vec::new(),
+        };
+        let tag_len = match args.get("--tag-len").unwrap_or("16").parse() {
+            Ok(tag_len @ 1..=16) => tag_len,
+            _ => return Err(CliError::Usage("--tag-len must be 1 to 16".to_string())),
+        };
+
+        let padding: &'static dyn Padding = match args.get("--padding").unwrap_or("pkcs7") {
+            "pkcs7" => &Pkcs7,
+            "x923" => &AnsiX923,
+            "iso10126" => &Iso10126,
+            "iso7816" => &Iso7816,
+            "zero" => &ZeroPadding,
+            other => return Err(CliError::Usage(format!("unknown padding {other}"))),
+        };
+
+        Ok(Self {
+            mode,
+            key_type,
+            expanded_key: key_expansion(&key, key_type),
+            iv,
+            nonce,
+            associated_data,
+            tag_len,
+            padding,
+        })
+    }
+}
+
+/// The `--in` file or stdin, decoded according to `--in-format`
+///
+/// Encoded input is decoded all at once, so only raw input is streamed.
+fn open_input(args: &Args) -> Result<Box<dyn Read>, CliError> {
+    let mut input: Box<dyn Read> = match args.get("--in") {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+    };
+
+    match args.format("--in-format", Format::Raw)? {
+        Format::Raw => Ok(input),
+        Format::Encoded(encoding) => {
+            let mut text = String::new();
+            input.read_to_string(&mut text)?;
+            let bytes = encoding
+                .decode(&text)
+                .map_err(|error| CliError::Decoding("input".to_string(), error))?;
+            Ok(Box::new(Cursor::new(bytes)))
+        }
+    }
+}
+
+/// Run `write` against the `--out` file or stdout, encoding everything it
+/// writes according to `--out-format`
+fn with_output(
+    args: &Args,
+    default: Format,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    let format = args.format("--out-format", default)?;
+    let mut output: Box<dyn Write> = match args.get("--out") {
+        None | Some("-") => Box::new(io::stdout().lock()),
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+    };
+
+    match format {
+        Format::Raw => write(&mut output)?,
+        Format::Encoded(encoding) => {
+            let mut buffer = Vec::new();
+            write(&mut buffer)?;
+            // base64 is wrapped at 64 columns like PEM, which is also the
+            // longest line `openssl enc -a` will read back
+            let text = match encoding {
+                Encoding::Hex => encoding.encode(&buffer),
+                Encoding::Base64 => wrap_lines(&encoding.encode(&buffer), 64),
+            };
+            writeln!(output, "{text}")?;
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Break `text` (which must be ASCII) into lines of at most `width` characters
+fn wrap_lines(text: &str, width: usize) -> String {
+    text.as_bytes()
+        .chunks(width)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The key size for a password, which defaults to 256 bits
+fn password_key_type(args: &Args) -> Result<KeyType, CliError> {
+    match args.get("--key-size") {
+        Some(_) => args.key_size(),
+        None => Ok(KeyType::Aes256),
+    }
+}
+
+/// The key derivation from `--openssl`, checking it's used with a password
+/// and a mode `openssl enc` has
+fn openssl_key_derivation(args: &Args) -> Result<Option<KeyDerivation>, CliError> {
+    let Some(key_derivation) = args.openssl()? else {
+        return Ok(None);
+    };
+
+    if args.get("--password").is_none() {
+        return Err(CliError::Usage("--openssl needs --password".to_string()));
+    }
+    if Mode::parse(args)?.authenticated() {
+        return Err(CliError::Usage(
+            "openssl enc only supports ecb, cbc and ctr".to_string(),
+        ));
+    }
+    Ok(Some(key_derivation))
+}
+
+/// Derive the key for encrypting with `--password` from a new random salt,
+/// along with the header to write before the ciphertext
+fn derive_for_encryption(args: &Args) -> Result<Option<(DerivedKey, Vec<u8>)>, CliError> {
+    let openssl_key_derivation = openssl_key_derivation(args)?;
+    if args.get("--password").is_none() {
+        return Ok(None);
+    }
+    let password = args.password()?;
+    let key_type = password_key_type(args)?;
+
+    match openssl_key_derivation {
+        Some(key_derivation) => {
+            let salt = random_bytes(openssl::SALT_LEN)?.try_into().unwrap();
+            let (key, iv) = derive_key_and_iv(&password, &salt, key_derivation, key_type)
+                .expect("the iteration count isn't zero");
+            let derived = DerivedKey { key, key_type, iv };
+            Ok(Some((derived, openssl::header(&salt).to_vec())))
+        }
+        None => {
+            let random = random_bytes(SALT_LEN + 16)?;
+            let header = PasswordHeader {
+                iterations: args.iterations(DEFAULT_ITERATIONS)?,
+                key_type,
+                salt: random[..SALT_LEN].try_into().unwrap(),
+                iv: random[SALT_LEN..].try_into().unwrap(),
+            };
+            Ok(Some((header.derive(&password), header.to_bytes())))
+        }
+    }
+}
+
+/// Derive the key for decrypting with `--password` from the header at the
+/// start of `input`
+fn derive_for_decryption(
+    args: &Args,
+    input: &mut dyn Read,
+) -> Result<Option<DerivedKey>, CliError> {
+    let openssl_key_derivation = openssl_key_derivation(args)?;
+    if args.get("--password").is_none() {
+        return Ok(None);
+    }
+    let password = args.password()?;
+
+    match openssl_key_derivation {
+        Some(key_derivation) => {
+            let key_type = password_key_type(args)?;
+            let salt = read_openssl_salt(input)?;
+            let (key, iv) = derive_key_and_iv(&password, &salt, key_derivation, key_type)
+                .expect("the iteration count isn't zero");
+            Ok(Some(DerivedKey { key, key_type, iv }))
+        }
+        None => Ok(Some(PasswordHeader::read(input)?.derive(&password))),
+    }
+}
+
+pub fn encrypt(args: &Args) -> Result<(), CliError> {
+    if let Mode::GcmStream = Mode::parse(args)? {
+        return encrypt_container(args);
+    }
+
+    let (derived, header) = derive_for_encryption(args)?.unzip();
+    let params = Params::parse(args, derived.as_ref())?;
+    let mut input = open_input(args)?;
+
+    let key = &params.expanded_key;
+    let key_type = params.key_type;
+    let (aad, nonce, tag_len) = (&params.associated_data, &params.nonce, params.tag_len);
+
+    // the parameters were all checked above, so the constructors can't fail
+    with_output(args, Format::Raw, |output| {
+        if let Some(header) = &header {
+            output.write_all(header)?;
+        }
+
+        match params.mode {
+            Mode::Ecb => encrypt_with(
+                EcbEncryptor::new(params.padding, key, key_type),
+                &mut input,
+                output,
+            ),
+            Mode::Cbc => encrypt_with(
+                CbcEncryptor::new(&params.iv, params.padding, key, key_type),
+                &mut input,
+                output,
+            ),
+            Mode::Ctr => encrypt_with(
+                CtrEncryptor::new(&params.iv, key, key_type),
+                &mut input,
+                output,
+            ),
+            Mode::Ocb => encrypt_with(
+                OcbEncryptor::new(aad, nonce, tag_len, key, key_type).unwrap(),
+                &mut input,
+                output,
+            ),
+            Mode::Eax => encrypt_with(
+                EaxEncryptor::new(aad, nonce, tag_len, key, key_type).unwrap(),
+                &mut input,
+                output,
+            ),
+            Mode::GcmStream => unreachable!("gcm-stream writes a container"),
+        }
+    })
+}
+
+/// Encrypt into a container, with AES-GCM chunks and a header recording the
+/// key derivation and chunk size, so decrypting needs only the key or
+/// password
+fn encrypt_container(args: &Args) -> Result<(), CliError> {
+    check_container_options(args)?;
+    let random = random_bytes(NONCE_PREFIX_LEN + SALT_LEN)?;
+
+    let (key, key_type, kdf) = match args.get("--password") {
+        Some(_) => {
+            let kdf = Kdf::Pbkdf2 {
+                iterations: args.iterations(DEFAULT_ITERATIONS)?,
+                salt: random[NONCE_PREFIX_LEN..].try_into().unwrap(),
+            };
+            (None, password_key_type(args)?, kdf)
+        }
+        None => {
+            let (key, key_type) = args.key()?;
+            (Some(key), key_type, Kdf::None)
+        }
+    };
+    let header = Header {
+        key_type,
+        mode: AeadMode::Gcm,
+        kdf,
+        nonce_prefix: random[..NONCE_PREFIX_LEN].try_into().unwrap(),
+        chunk_size: args.chunk_size()?,
+    };
+    let key = match key {
+        Some(key) => key,
+        None => header.derive_key(&args.password()?)?,
+    };
+
+    let encryptor = ContainerEncryptor::new(&header, &key)?;
+    let mut input = open_input(args)?;
+    with_output(args, Format::Raw, |output| {
+        output.write_all(&header.to_bytes())?;
+        encrypt_with(encryptor, &mut input, output)
+    })
+}
+
+fn encrypt_with(
+    encryptor: impl Encryptor,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<(), CliError> {
+    let mut writer = EncryptingWriter::new(output, encryptor);
+    io::copy(input, &mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn decrypt(args: &Args) -> Result<(), CliError> {
+    if let Some((offset, length)) = args.range()? {
+        return decrypt_range(args, offset, length);
+    }
+    if let Mode::GcmStream = Mode::parse(args)? {
+        return decrypt_container(args);
+    }
+
+    let mut input = open_input(args)?;
+    let derived = derive_for_decryption(args, &mut input)?;
+    let params = Params::parse(args, derived.as_ref())?;
+
+    let key = &params.expanded_key;
+    let key_type = params.key_type;
+    let (aad, nonce, tag_len) = (&params.associated_data, &params.nonce, params.tag_len);
+    let hold_output = params.mode.unverified_output();
+
+    with_output(args, Format::Raw, |output| match params.mode {
+        Mode::Ecb => decrypt_with(
+            EcbDecryptor::new(params.padding, key, key_type),
+            input,
+            output,
+            hold_output,
+        ),
+        Mode::Cbc => decrypt_with(
+            CbcDecryptor::new(&params.iv, params.padding, key, key_type),
+            input,
+            output,
+            hold_output,
+        ),
+        Mode::Ctr => decrypt_with(
+            CtrDecryptor::new(&params.iv, key, key_type),
+            input,
+            output,
+            hold_output,
+        ),
+        Mode::Ocb => decrypt_with(
+            OcbDecryptor::new(aad, nonce, tag_len, key, key_type).unwrap(),
+            input,
+            output,
+            hold_output,
+        ),
+        Mode::Eax => decrypt_with(
+            EaxDecryptor::new(aad, nonce, tag_len, key, key_type).unwrap(),
+            input,
+            output,
+            hold_output,
+        ),
+        Mode::GcmStream => unreachable!("gcm-stream reads a container"),
+    })
+}
+
+/// Decrypt a container, writing each chunk as soon as it has been checked
+fn decrypt_container(args: &Args) -> Result<(), CliError> {
+    check_container_options(args)?;
+    let mut input = open_input(args)?;
+    let header = Header::read(&mut input)?;
+    let decryptor = ContainerDecryptor::new(&header, &container_key(args, &header)?)?;
+
+    with_output(args, Format::Raw, |output| {
+        decrypt_with(decryptor, input, output, false)
+    })
+}
+
+/// The key for a container, either given or derived from the password with
+/// the parameters in its header
+fn container_key(args: &Args, header: &Header) -> Result<Vec<u8>, CliError> {
+    match args.get("--password") {
+        Some(_) => Ok(header.derive_key(&args.password()?)?),
+        None => Ok(args.key()?.0),
+    }
+}
+
+/// Reject the options a container doesn't use, since its nonce is random and
+/// its header is the only associated data
+fn check_container_options(args: &Args) -> Result<(), CliError> {
+    for option in [
+        "--iv",
+        "--nonce",
+        "--aad",
+        "--tag-len",
+        "--padding",
+        "--openssl",
+    ] {
+        if args.get(option).is_some() {
+            return Err(CliError::Usage(format!("gcm-stream doesn't take {option}")));
+        }
+    }
+    if args.get("--password").is_some() && args.get("--key").is_some() {
+        return Err(CliError::Usage(
+            "--key can't be used with --password".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Decrypt `length` bytes of plaintext from `offset` in the container in a
+/// gcm-stream `--in` file, reading and checking only the chunks they fall in
+fn decrypt_range(args: &Args, offset: u64, length: usize) -> Result<(), CliError> {
+    if !matches!(Mode::parse(args)?, Mode::GcmStream) {
+        return Err(CliError::Usage(
+            "--offset and --length only work with gcm-stream".to_string(),
+        ));
+    }
+    let path = match args.get("--in") {
+        Some(path) if path != "-" => path,
+        _ => {
+            return Err(CliError::Usage(
+                "--offset and --length need an --in file".to_string(),
+            ))
+        }
+    };
+    if args.format("--in-format", Format::Raw)? != Format::Raw {
+        return Err(CliError::Usage(
+            "--offset and --length need raw input".to_string(),
+        ));
+    }
+
+    check_container_options(args)?;
+
+    let mut input = BufReader::new(File::open(path)?);
+    let header = Header::read(&mut input)?;
+    let key = container_key(args, &header)?;
+    let mut reader = ContainerReader::new(input, &header, &key)?;
+    let plaintext = reader.read_at(offset, length)?;
+
+    with_output(args, Format::Raw, |output| {
+        output.write_all(&plaintext)?;
+        Ok(())
+    })
+}
+
+/// Decrypt `input` to `output`, holding everything back until the tag has
+/// been checked if `hold_output` is set
+fn decrypt_with(
+    decryptor: impl Decryptor,
+    input: impl Read,
+    output: &mut dyn Write,
+    hold_output: bool,
+) -> Result<(), CliError> {
+    let mut reader = DecryptingReader::new(input, decryptor);
+
+    if hold_output {
+        let mut plaintext = Vec::new();
+        reader.read_to_end(&mut plaintext)?;
+        output.write_all(&plaintext)?;
+    } else {
+        io::copy(&mut reader, output)?;
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Fill `entropy` from the operating system
+///
+/// The standard library has no portable way to do this, so only Unix-like
+/// systems (through `/dev/urandom`) are supported.
+#[cfg(unix)]
+fn os_entropy(entropy: &mut [u8]) -> io::Result<()> {
+    File::open("/dev/urandom")?.read_exact(entropy)
+}
+
+#[cfg(not(unix))]
+fn os_entropy(_entropy: &mut [u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "random keys and salts need /dev/urandom, which this system doesn't have",
+    ))
+}
+
+/// `len` random bytes for keys, salts and IVs
+fn random_bytes(len: usize) -> Result<Vec<u8>, CliError> {
+    // seed a DRBG from the operating system rather than using its output
+    // directly, so a weak source is at least whitened
+    let mut entropy = [0; 48];
+    os_entropy(&mut entropy)?;
+    let mut drbg = CtrDrbg::new(
+        KeyType::Aes256,
+        &entropy[..32],
+        &entropy[32..],
+        b"aes random bytes",
+    )
+    .expect("the entropy input is long enough");
+
+    let mut bytes = vec![0; len];
+    drbg.generate(&mut bytes, &[])
+        .expect("requests are well under the limit");
+    Ok(bytes)
+}
+
+/// Print a new random key, as hex unless `--out-format` says otherwise
+pub fn keygen(args: &Args) -> Result<(), CliError> {
+    let key = random_bytes(args.key_size()?.n_k() * 4)?;
+
+    with_output(args, Format::Encoded(Encoding::Hex), |output| {
+        output.write_all(&key)?;
+        Ok(())
+    })
+}
+
+/// Encrypt (or, with `--inverse`, decrypt) a single block, printing the state
+/// after every step as in FIPS 197 Appendix C
+pub fn trace(args: &Args) -> Result<(), CliError> {
+    let (key, key_type) = args.key()?;
+    let block = args.bytes("--block", Some(16))?;
+    let expanded_key = key_expansion(&key, key_type);
+
+    env::set_var("DEBUG", "true");
+    let output = if args.flag("--inverse") {
+        inverse_cipher(&block, &expanded_key, key_type)
+    } else {
+        cipher(&block, &expanded_key, key_type)
+    };
+    println!();
+    println!("{}", encode_hex(&output));
+
+    Ok(())
+}
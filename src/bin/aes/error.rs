@@ -0,0 +1,85 @@
+use std::fmt::Display;
+use std::io;
+
+use project_1_aes::container::ContainerError;
+use project_1_aes::encoding::EncodingError;
+use project_1_aes::stream::StreamError;
+
+/// Everything that can make the tool exit unsuccessfully
+#[derive(Debug)]
+pub enum CliError {
+    /// The command line was malformed
+    Usage(String),
+    /// Reading the input or writing the output failed
+    Io(io::Error),
+    /// An option or the input wasn't validly encoded
+    Decoding(String, EncodingError),
+    /// The padding or tag was invalid, so the ciphertext (or key) is wrong
+    Decryption(StreamError),
+    /// A gcm-stream container's header was invalid or didn't match the key,
+    /// or a chunk read with `--offset` and `--length` was invalid
+    Container(ContainerError),
+    /// The input didn't start with a well-formed header
+    InvalidHeader(String),
+    /// The self-test found a mismatch
+    SelfTest(usize),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Io(_) | Self::Decoding(..) => 1,
+            Self::Usage(_) => 2,
+            Self::Decryption(_) | Self::Container(_) | Self::InvalidHeader(_) => 3,
+            Self::SelfTest(_) => 4,
+        }
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}"),
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Decoding(what, error) => write!(f, "{what}: {error}"),
+            Self::Decryption(error) => write!(f, "decryption failed: {error}"),
+            Self::Container(error) => write!(f, "decryption failed: {error}"),
+            Self::InvalidHeader(message) => write!(f, "invalid header: {message}"),
+            Self::SelfTest(failures) => write!(f, "{failures} self-test checks failed"),
+        }
+    }
+}
+
+impl From<ContainerError> for CliError {
+    fn from(error: ContainerError) -> Self {
+        Self::Container(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    /// Decryption failures from [`DecryptingReader`], [`Header::read`] and
+    /// [`ContainerReader`]
+    /// arrive wrapped in an I/O error, so they're unwrapped again here
+    ///
+    /// [`DecryptingReader`]: project_1_aes::io::DecryptingReader
+    /// [`Header::read`]: project_1_aes::container::Header::read
+    /// [`ContainerReader`]: project_1_aes::container::ContainerReader
+    fn from(error: io::Error) -> Self {
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<StreamError>())
+        {
+            let inner = error.into_inner().unwrap();
+            return Self::Decryption(*inner.downcast().unwrap());
+        }
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<ContainerError>())
+        {
+            let inner = error.into_inner().unwrap();
+            return Self::Container(*inner.downcast().unwrap());
+        }
+
+        Self::Io(error)
+    }
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::process::ExitCode;
+
+use args::Args;
+use error::CliError;
+
+mod args;
+mod commands;
+mod error;
+mod password;
+mod selftest;
+
+const USAGE: &str = "\
+usage: aes <command> [options]
+
+commands:
+  encrypt   encrypt --in (or stdin) to --out (or stdout)
+  decrypt   decrypt --in (or stdin) to --out (or stdout)
+  keygen    print a random key (Unix-like systems only: reads /dev/urandom)
+  trace     print every step of encrypting a single --block
+  selftest  check the cipher against the published examples
+  help      print this message
+
+options:
+  --key VALUE       the key, whose length picks AES-128, AES-192 or AES-256
+                    (hex is tried before base64; see values below)
+  --key-size BITS   128, 192 or 256, to check the key's length (or the size of
+                    key to make, 128 by default for keygen and 256 for a
+                    password)
+  --password VALUE  derive the key from a password instead, storing the salt,
+                    iteration count and IV (or nonce) at the start of the
+                    ciphertext; the password is taken as it is, or from
+                    env:NAME or the first line of file:PATH (encrypting reads
+                    the salt from /dev/urandom, so only works on Unix-like
+                    systems, as does encrypting with gcm-stream)
+  --iterations N    PBKDF2 iterations for a new password (default 600000, or
+                    10000 with --openssl)
+  --openssl KDF     read or write the Salted__ format of openssl enc instead,
+                    deriving the key with pbkdf2 (openssl enc -pbkdf2), or
+                    with md5 or sha256 (EVP_BytesToKey with openssl enc -md);
+                    the file doesn't record the KDF, --iterations or
+                    --key-size, so they must match when decrypting
+  --mode MODE       ecb, cbc, ctr, ocb, eax or gcm-stream (a container of
+                    AES-GCM chunks that are each checked before being
+                    written, for large files, whose header records the key
+                    size, salt, random nonce and chunk size)
+  --iv VALUE        the IV for cbc, or the initial counter block for ctr
+  --nonce VALUE     the nonce for ocb and eax
+  --aad VALUE       associated data for ocb and eax
+  --tag-len BYTES   the tag length for ocb and eax, from 1 to 16 (default 16)
+  --chunk-size N    plaintext bytes per gcm-stream chunk when encrypting
+                    (default 65536)
+  --offset N        with --length, decrypt only that much of the plaintext
+  --length N        from the offset (0 and the rest by default) of a
+                    gcm-stream --in file, reading just the chunks it's in
+  --padding NAME    pkcs7 (the default), x923, iso10126, iso7816 or zero
+  --in FILE         read from FILE instead of stdin
+  --out FILE        write to FILE instead of stdout
+  --in-format FMT   raw (the default), hex or base64
+  --out-format FMT  raw (the default, except for keygen's hex), hex or base64
+                    (wrapped at 64 columns)
+  --block VALUE     the block for trace
+  --inverse         trace the inverse cipher instead
+
+values:
+  hex or base64, or with a hex: or base64: prefix to choose explicitly, or
+  file:PATH to read the value from a file (which is used as it is unless it
+  holds hex or base64 text); without a prefix, a value that is valid as both
+  is read as hex, so a 32-character base64 (AES-192) --key needs base64: or
+  --key-size 192
+
+exit codes:
+  0  success
+  1  an input or output error, or a badly encoded value
+  2  invalid usage
+  3  decryption failed (wrong key or password, or corrupted, forged or
+     truncated ciphertext); gcm-stream may already have written the chunks
+     before the failure
+  4  the self-test failed";
+
+fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args)?;
+
+    match args.command.as_str() {
+        "encrypt" => commands::encrypt(&args),
+        "decrypt" => commands::decrypt(&args),
+        "keygen" => commands::keygen(&args),
+        "trace" => commands::trace(&args),
+        "selftest" => selftest::run(),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        other => Err(CliError::Usage(format!("unknown command {other}"))),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("aes: {error}");
+            if let CliError::Usage(_) = error {
+                eprintln!("run `aes help` for usage");
+            }
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
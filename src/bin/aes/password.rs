@@ -0,0 +1,183 @@
+use std::io::{self, Read};
+
+use project_1_aes::constant::KeyType;
+use project_1_aes::openssl::{self, parse_header};
+use project_1_aes::pbkdf2::{derive_key, SALT_LEN};
+
+use crate::error::CliError;
+
+/// Marks a file as encrypted with a password
+const MAGIC: [u8; 8] = *b"AESPBKDF";
+
+const HEADER_LEN: usize = MAGIC.len() + 4 + 1 + SALT_LEN + 16;
+
+/// The nonce for OCB and EAX is this much of the derived IV
+pub const NONCE_LEN: usize = 12;
+
+/// A key and IV derived from `--password`
+pub struct DerivedKey {
+    pub key: Vec<u8>,
+    pub key_type: KeyType,
+    pub iv: [u8; 16],
+}
+
+impl DerivedKey {
+    pub fn nonce(&self) -> &[u8] {
+        &self.iv[..NONCE_LEN]
+    }
+}
+
+/// Everything needed to derive a password-encrypted file's key again, which
+/// is written before the ciphertext
+///
+/// The header is the magic bytes, the PBKDF2 iteration count (a big-endian
+/// `u32`), the key length in bytes, the salt and the IV, which is also the
+/// source of the nonce for the modes that take one.
+pub struct PasswordHeader {
+    pub iterations: u32,
+    pub key_type: KeyType,
+    pub salt: [u8; SALT_LEN],
+    pub iv: [u8; 16],
+}
+
+impl PasswordHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend(MAGIC);
+        bytes.extend(self.iterations.to_be_bytes());
+        bytes.push((self.key_type.n_k() * 4) as u8);
+        bytes.extend(self.salt);
+        bytes.extend(self.iv);
+        bytes
+    }
+
+    /// Read and check the header at the start of `input`
+    pub fn read(input: &mut dyn Read) -> Result<Self, CliError> {
+        let mut bytes = [0; HEADER_LEN];
+        read_header(input, &mut bytes)?;
+
+        let (magic, rest) = bytes.split_at(MAGIC.len());
+        if magic != MAGIC {
+            return Err(CliError::InvalidHeader(
+                "the input wasn't encrypted with a password".to_string(),
+            ));
+        }
+
+        let iterations = u32::from_be_bytes(rest[..4].try_into().unwrap());
+        if iterations == 0 {
+            return Err(CliError::InvalidHeader(
+                "the iteration count is zero".to_string(),
+            ));
+        }
+        let key_type = KeyType::from_key_len(rest[4] as usize)
+            .ok_or_else(|| CliError::InvalidHeader(format!("unknown key length {}", rest[4])))?;
+
+        Ok(Self {
+            iterations,
+            key_type,
+            salt: rest[5..5 + SALT_LEN].try_into().unwrap(),
+            iv: rest[5 + SALT_LEN..].try_into().unwrap(),
+        })
+    }
+
+    /// Derive the key from `password`
+    pub fn derive(&self, password: &[u8]) -> DerivedKey {
+        DerivedKey {
+            key: derive_key(password, &self.salt, self.iterations, self.key_type)
+                .expect("the iteration count isn't zero"),
+            key_type: self.key_type,
+            iv: self.iv,
+        }
+    }
+}
+
+/// Read the salt from the `Salted__` header that `openssl enc` writes
+pub fn read_openssl_salt(input: &mut dyn Read) -> Result<[u8; openssl::SALT_LEN], CliError> {
+    let mut bytes = [0; openssl::HEADER_LEN];
+    read_header(input, &mut bytes)?;
+
+    parse_header(&bytes).map(|(salt, _)| salt).map_err(|_| {
+        CliError::InvalidHeader("the input wasn't encrypted by openssl enc".to_string())
+    })
+}
+
+fn read_header(input: &mut dyn Read, bytes: &mut [u8]) -> Result<(), CliError> {
+    input.read_exact(bytes).map_err(|error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            CliError::InvalidHeader("the input is too short".to_string())
+        }
+        _ => CliError::Io(error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> PasswordHeader {
+        PasswordHeader {
+            iterations: 1000,
+            key_type: KeyType::Aes192,
+            salt: [0x5a; SALT_LEN],
+            iv: [0xa5; 16],
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Result<PasswordHeader, CliError> {
+        PasswordHeader::read(&mut &bytes[..])
+    }
+
+    #[test]
+    fn header_test() {
+        let bytes = header().to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN);
+        assert_eq!(bytes[..12], *b"AESPBKDF\x00\x00\x03\xe8");
+
+        let parsed = read(&bytes).unwrap();
+        assert_eq!(parsed.to_bytes(), bytes);
+        let derived = parsed.derive(b"password");
+        assert_eq!(derived.key.len(), 24);
+        assert_eq!(derived.nonce(), [0xa5; NONCE_LEN]);
+
+        assert!(matches!(
+            read(&bytes[..HEADER_LEN - 1]),
+            Err(CliError::InvalidHeader(_))
+        ));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] ^= 1;
+        assert!(matches!(read(&bad_magic), Err(CliError::InvalidHeader(_))));
+
+        let mut zero_iterations = bytes.clone();
+        zero_iterations[8..12].fill(0);
+        assert!(matches!(
+            read(&zero_iterations),
+            Err(CliError::InvalidHeader(_))
+        ));
+
+        let mut bad_key_len = bytes;
+        bad_key_len[12] = 20;
+        assert!(matches!(
+            read(&bad_key_len),
+            Err(CliError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn openssl_salt_test() {
+        let data = b"Salted__\x01\x02\x03\x04\x05\x06\x07\x08ciphertext";
+        assert_eq!(
+            read_openssl_salt(&mut &data[..]).unwrap(),
+            [1, 2, 3, 4, 5, 6, 7, 8]
+        );
+
+        assert!(matches!(
+            read_openssl_salt(&mut &data[..12]),
+            Err(CliError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            read_openssl_salt(&mut &[0; 32][..]),
+            Err(CliError::InvalidHeader(_))
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use project_1_aes::cipher::cipher;
+use project_1_aes::constant::KeyType;
+use project_1_aes::ctr::ctr_encrypt;
+use project_1_aes::debug::hex_array_to_string;
+use project_1_aes::inverse_cipher::inverse_cipher;
+use project_1_aes::key_expansion::key_expansion;
+
+use crate::error::CliError;
+
+/// Plaintext shared by the examples in FIPS 197 Appendix C
+const PLAINTEXT: [u8; 16] = [
+    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+];
+
+/// Keys and ciphertexts from FIPS 197 Appendix C.1 to C.3
+const APPENDIX_C: [(&str, KeyType, &[u8], [u8; 16]); 3] = [
+    (
+        "C.1 AES-128",
+        KeyType::Aes128,
+        &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ],
+        [
+            0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4,
+            0xc5, 0x5a,
+        ],
+    ),
+    (
+        "C.2 AES-192",
+        KeyType::Aes192,
+        &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+        ],
+        [
+            0xdd, 0xa9, 0x7c, 0xa4, 0x86, 0x4c, 0xdf, 0xe0, 0x6e, 0xaf, 0x70, 0xa0, 0xec, 0x0d,
+            0x71, 0x91,
+        ],
+    ),
+    (
+        "C.3 AES-256",
+        KeyType::Aes256,
+        &[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b,
+            0x1c, 0x1d, 0x1e, 0x1f,
+        ],
+        [
+            0x8e, 0xa2, 0xb7, 0xca, 0x51, 0x67, 0x45, 0xbf, 0xea, 0xfc, 0x49, 0x90, 0x4b, 0x49,
+            0x60, 0x89,
+        ],
+    ),
+];
+
+/// Check the cipher against the FIPS 197 and SP 800-38A examples, printing
+/// each result
+pub fn run() -> Result<(), CliError> {
+    let mut failures = 0;
+    let mut check = |name: &str, result: &[u8], expected: &[u8]| {
+        if result == expected {
+            println!("ok      {name}");
+        } else {
+            failures += 1;
+            println!("FAILED  {name}");
+            println!("          expected {}", hex_array_to_string(expected));
+            println!("          got      {}", hex_array_to_string(result));
+        }
+    };
+
+    for (name, key_type, key, ciphertext) in APPENDIX_C {
+        let expanded_key = key_expansion(key, key_type);
+        check(
+            &format!("{name} cipher"),
+            &cipher(PLAINTEXT, &expanded_key, key_type),
+            &ciphertext,
+        );
+        check(
+            &format!("{name} inverse cipher"),
+            &inverse_cipher(ciphertext, &expanded_key, key_type),
+            &PLAINTEXT,
+        );
+    }
+
+    // the first block of F.5.1 from SP 800-38A
+    let key = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    let initial_counter = [
+        0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd, 0xfe,
+        0xff,
+    ];
+    let plaintext = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a,
+    ];
+    let ciphertext = [
+        0x87, 0x4d, 0x61, 0x91, 0xb6, 0x20, 0xe3, 0x26, 0x1b, 0xef, 0x68, 0x64, 0x99, 0x0d, 0xb6,
+        0xce,
+    ];
+    let expanded_key = key_expansion(&key, KeyType::Aes128);
+    check(
+        "SP 800-38A F.5.1 CTR-AES128",
+        &ctr_encrypt(&plaintext, &initial_counter, &expanded_key, KeyType::Aes128),
+        &ciphertext,
+    );
+
+    match failures {
+        0 => Ok(()),
+        failures => Err(CliError::SelfTest(failures)),
+    }
+}
@@ -0,0 +1,171 @@
+//! Batch encryption of independent blocks
+//!
+//! [`BlockCipher`] encrypts or decrypts many blocks in one call. The provided
+//! methods just handle one block at a time, but a backend that can keep
+//! several blocks in flight at once (interleaving table lookups, or filling
+//! the AES-NI pipeline) can override them without changing any callers.
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::key_expansion::key_expansion;
+
+pub type Block = [u8; 16];
+
+/// An expanded key, along with the key type it was expanded for
+#[derive(Clone)]
+pub struct KeySchedule {
+    expanded_key: Vec<u32>,
+    key_type: KeyType,
+}
+
+impl KeySchedule {
+    pub fn new(key: &[u8], key_type: KeyType) -> Self {
+        Self {
+            expanded_key: key_expansion(key, key_type),
+            key_type,
+        }
+    }
+
+    pub fn expanded_key(&self) -> &[u32] {
+        &self.expanded_key
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+}
+
+pub trait BlockCipher {
+    fn encrypt_block(&self, block: &mut Block);
+
+    fn decrypt_block(&self, block: &mut Block);
+
+    /// Encrypt each of `blocks` in place
+    fn encrypt_blocks(&self, blocks: &mut [Block]) {
+        for block in blocks {
+            self.encrypt_block(block);
+        }
+    }
+
+    /// Decrypt each of `blocks` in place
+    fn decrypt_blocks(&self, blocks: &mut [Block]) {
+        for block in blocks {
+            self.decrypt_block(block);
+        }
+    }
+
+    /// Encrypt each of `blocks` in place under the key at the same index of
+    /// `keys`
+    ///
+    /// # Panics
+    ///
+    /// If `keys` and `blocks` have different lengths
+    fn encrypt_blocks_with_keys(keys: &[&Self], blocks: &mut [Block])
+    where
+        Self: Sized,
+    {
+        assert_eq!(keys.len(), blocks.len(), "need one key for each block");
+        for (key, block) in keys.iter().zip(blocks) {
+            key.encrypt_block(block);
+        }
+    }
+
+    /// Decrypt each of `blocks` in place under the key at the same index of
+    /// `keys`
+    ///
+    /// # Panics
+    ///
+    /// If `keys` and `blocks` have different lengths
+    fn decrypt_blocks_with_keys(keys: &[&Self], blocks: &mut [Block])
+    where
+        Self: Sized,
+    {
+        assert_eq!(keys.len(), blocks.len(), "need one key for each block");
+        for (key, block) in keys.iter().zip(blocks) {
+            key.decrypt_block(block);
+        }
+    }
+}
+
+impl BlockCipher for KeySchedule {
+    fn encrypt_block(&self, block: &mut Block) {
+        *block = cipher_block(block, &self.expanded_key, self.key_type);
+    }
+
+    fn decrypt_block(&self, block: &mut Block) {
+        *block = inverse_cipher_block(block, &self.expanded_key, self.key_type);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_test() {
+        // C.1 from FIPS 197, repeated alongside other blocks
+        let key = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let plaintext = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff,
+        ];
+        let ciphertext = [
+            0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4,
+            0xc5, 0x5a,
+        ];
+        let schedule = KeySchedule::new(&key, KeyType::Aes128);
+
+        let original: Vec<Block> = (0..8).map(|i| [i; 16]).chain([plaintext]).collect();
+        let mut blocks = original.clone();
+        schedule.encrypt_blocks(&mut blocks);
+        assert_eq!(blocks[8], ciphertext);
+        for (block, input) in blocks.iter().zip(&original) {
+            assert_eq!(
+                *block,
+                cipher_block(input, schedule.expanded_key(), KeyType::Aes128)
+            );
+        }
+
+        schedule.decrypt_blocks(&mut blocks);
+        assert_eq!(blocks, original);
+
+        schedule.encrypt_blocks(&mut []);
+    }
+
+    #[test]
+    fn blocks_with_keys_test() {
+        let schedules = [
+            KeySchedule::new(&[0x01; 16], KeyType::Aes128),
+            KeySchedule::new(&[0x02; 24], KeyType::Aes192),
+            KeySchedule::new(&[0x03; 32], KeyType::Aes256),
+        ];
+        let keys: Vec<&KeySchedule> = schedules.iter().collect();
+
+        let original = [[0xa5; 16]; 3];
+        let mut blocks = original;
+        KeySchedule::encrypt_blocks_with_keys(&keys, &mut blocks);
+        for (block, schedule) in blocks.iter().zip(&schedules) {
+            let mut expected = original[0];
+            schedule.encrypt_block(&mut expected);
+            assert_eq!(*block, expected);
+        }
+
+        // each block really was encrypted under a different key
+        assert_ne!(blocks[0], blocks[1]);
+        assert_ne!(blocks[1], blocks[2]);
+
+        KeySchedule::decrypt_blocks_with_keys(&keys, &mut blocks);
+        assert_eq!(blocks, original);
+    }
+
+    #[test]
+    #[should_panic(expected = "need one key for each block")]
+    fn mismatched_keys_test() {
+        let schedule = KeySchedule::new(&[0; 16], KeyType::Aes128);
+        KeySchedule::encrypt_blocks_with_keys(&[&schedule], &mut [[0; 16]; 2]);
+    }
+}
@@ -0,0 +1,273 @@
+//! Cipher block chaining (CBC) mode (NIST SP 800-38A)
+//!
+//! Each plaintext block is XORed with the previous ciphertext block (or the IV)
+//! before it is enciphered. The IV must be unpredictable for each message.
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::padding::{Padding, PaddingError};
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+use crate::util::xor_in_place;
+
+/// Pad and encrypt `plaintext`
+pub fn cbc_encrypt(
+    plaintext: &[u8],
+    iv: &[u8; 16],
+    padding: &dyn Padding,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    let mut result = plaintext.to_vec();
+    padding.pad(&mut result);
+
+    let mut previous = *iv;
+    for block in result.chunks_exact_mut(16) {
+        xor_in_place(&mut previous, block);
+        previous = cipher_block(&previous, expanded_key, key_type);
+        block.copy_from_slice(&previous);
+    }
+
+    result
+}
+
+/// Decrypt `ciphertext` and remove its padding
+pub fn cbc_decrypt(
+    ciphertext: &[u8],
+    iv: &[u8; 16],
+    padding: &dyn Padding,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, PaddingError> {
+    if !ciphertext.len().is_multiple_of(16) {
+        return Err(PaddingError::InvalidLength(ciphertext.len()));
+    }
+
+    let mut result = Vec::with_capacity(ciphertext.len());
+    let mut previous = iv;
+    for block in ciphertext.chunks_exact(16) {
+        let block: &[u8; 16] = block.try_into().unwrap();
+        let mut output = inverse_cipher_block(block, expanded_key, key_type);
+        xor_in_place(&mut output, previous);
+        result.extend(output);
+        previous = block;
+    }
+
+    let len = padding.unpad(&result)?.len();
+    result.truncate(len);
+    Ok(result)
+}
+
+/// Incremental CBC encryption
+pub struct CbcEncryptor<'a> {
+    padding: &'a dyn Padding,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    previous: [u8; 16],
+    buffer: Buffer,
+}
+
+impl<'a> CbcEncryptor<'a> {
+    pub fn new(
+        iv: &[u8; 16],
+        padding: &'a dyn Padding,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Self {
+        Self {
+            padding,
+            expanded_key,
+            key_type,
+            previous: *iv,
+            buffer: Buffer::default(),
+        }
+    }
+
+    fn encrypt_blocks(&mut self, mut blocks: Vec<u8>) -> Vec<u8> {
+        for block in blocks.chunks_exact_mut(16) {
+            xor_in_place(&mut self.previous, block);
+            self.previous = cipher_block(&self.previous, self.expanded_key, self.key_type);
+            block.copy_from_slice(&self.previous);
+        }
+        blocks
+    }
+}
+
+impl Encryptor for CbcEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let blocks = self.buffer.take(plaintext, 16, 0);
+        self.encrypt_blocks(blocks)
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let mut last = std::mem::take(&mut self.buffer).remaining();
+        self.padding.pad(&mut last);
+        Ok(self.encrypt_blocks(last))
+    }
+}
+
+/// Incremental CBC decryption
+///
+/// The last block is held back until [`Decryptor::finalize`], since it holds
+/// the padding.
+pub struct CbcDecryptor<'a> {
+    padding: &'a dyn Padding,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    previous: [u8; 16],
+    buffer: Buffer,
+}
+
+impl<'a> CbcDecryptor<'a> {
+    pub fn new(
+        iv: &[u8; 16],
+        padding: &'a dyn Padding,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Self {
+        Self {
+            padding,
+            expanded_key,
+            key_type,
+            previous: *iv,
+            buffer: Buffer::default(),
+        }
+    }
+
+    fn decrypt_blocks(&mut self, mut blocks: Vec<u8>) -> Vec<u8> {
+        for block in blocks.chunks_exact_mut(16) {
+            let input: [u8; 16] = block.try_into().unwrap();
+            let mut output = inverse_cipher_block(&input, self.expanded_key, self.key_type);
+            xor_in_place(&mut output, &self.previous);
+            block.copy_from_slice(&output);
+            self.previous = input;
+        }
+        blocks
+    }
+}
+
+impl Decryptor for CbcDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let blocks = self.buffer.take(ciphertext, 16, 1);
+        self.decrypt_blocks(blocks)
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let last = std::mem::take(&mut self.buffer).remaining();
+        if !last.len().is_multiple_of(16) {
+            return Err(PaddingError::InvalidLength(last.len()).into());
+        }
+
+        let last = self.decrypt_blocks(last);
+        Ok(self.padding.unpad(&last)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::padding::{AnsiX923, Iso10126, Iso7816, Pkcs7, ZeroPadding};
+    use crate::stream::check_streaming;
+
+    const IV: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+
+    #[test]
+    fn cbc_aes_256_test() {
+        // F.2.5 and F.2.6 from NIST SP 800-38A, followed by a block of padding
+        let key = [
+            0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d,
+            0x77, 0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3,
+            0x09, 0x14, 0xdf, 0xf4,
+        ];
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac,
+            0x45, 0xaf, 0x8e, 0x51,
+        ];
+        let expected = [
+            0xf5, 0x8c, 0x4c, 0x04, 0xd6, 0xe5, 0xf1, 0xba, 0x77, 0x9e, 0xab, 0xfb, 0x5f, 0x7b,
+            0xfb, 0xd6, 0x9c, 0xfc, 0x4e, 0x96, 0x7e, 0xdb, 0x80, 0x8d, 0x67, 0x9f, 0x77, 0x7b,
+            0xc6, 0x70, 0x2c, 0x7d,
+        ];
+        let expanded_key = key_expansion(&key, KeyType::Aes256);
+
+        let result = cbc_encrypt(&plaintext, &IV, &Pkcs7, &expanded_key, KeyType::Aes256);
+        assert_eq!(result.len(), 48);
+        assert_eq!(result[..32], expected);
+
+        let result = cbc_decrypt(&result, &IV, &Pkcs7, &expanded_key, KeyType::Aes256).unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn padding_schemes_test() {
+        let expanded_key = key_expansion(&[0x42; 16], KeyType::Aes128);
+        let schemes: [&dyn Padding; 5] = [&Pkcs7, &AnsiX923, &Iso10126, &Iso7816, &ZeroPadding];
+
+        for padding in schemes {
+            for len in [0, 1, 15, 16, 17, 31, 32] {
+                let plaintext = vec![0x5a; len];
+                let ciphertext =
+                    cbc_encrypt(&plaintext, &IV, padding, &expanded_key, KeyType::Aes128);
+                assert_eq!(ciphertext.len() % 16, 0);
+
+                let result = cbc_decrypt(&ciphertext, &IV, padding, &expanded_key, KeyType::Aes128);
+                assert_eq!(result, Ok(plaintext));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_ciphertext_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+
+        let result = cbc_decrypt(&[0; 33], &IV, &Pkcs7, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(PaddingError::InvalidLength(33)));
+
+        // flipping the last byte of the IV flips the padding length byte
+        let ciphertext = cbc_encrypt(b"message", &IV, &Pkcs7, &expanded_key, KeyType::Aes128);
+        let mut iv = IV;
+        iv[15] ^= 0x01;
+        let result = cbc_decrypt(&ciphertext, &iv, &Pkcs7, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(PaddingError::InvalidPadding));
+    }
+
+    #[test]
+    fn streaming_test() {
+        let expanded_key = key_expansion(&[0x42; 16], KeyType::Aes128);
+        let plaintext: Vec<u8> = (0..100).collect();
+
+        for len in [0, 15, 16, 17, 100] {
+            let expected = cbc_encrypt(
+                &plaintext[..len],
+                &IV,
+                &Iso7816,
+                &expanded_key,
+                KeyType::Aes128,
+            );
+
+            check_streaming(
+                &plaintext[..len],
+                &expected,
+                || CbcEncryptor::new(&IV, &Iso7816, &expanded_key, KeyType::Aes128),
+                || CbcDecryptor::new(&IV, &Iso7816, &expanded_key, KeyType::Aes128),
+            );
+        }
+
+        // nothing is returned before a whole block arrives
+        let mut encryptor = CbcEncryptor::new(&IV, &ZeroPadding, &expanded_key, KeyType::Aes128);
+        assert_eq!(encryptor.update(&[]), []);
+        assert_eq!(encryptor.finalize(), Ok(Vec::new()));
+
+        let mut decryptor = CbcDecryptor::new(&IV, &Pkcs7, &expanded_key, KeyType::Aes128);
+        decryptor.update(&[0; 32]);
+        assert_eq!(
+            decryptor.finalize(),
+            Err(StreamError::Padding(PaddingError::InvalidPadding))
+        );
+    }
+}
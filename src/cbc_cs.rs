@@ -0,0 +1,517 @@
+//! CBC with ciphertext stealing (NIST SP 800-38A Addendum)
+//!
+//! The final partial block is zero-padded and encrypted as in ordinary CBC,
+//! and then the padding is "stolen" back from the penultimate ciphertext
+//! block, so the ciphertext is exactly as long as the plaintext. The three
+//! variants only differ in how the last two blocks are ordered:
+//!
+//! - CS1 keeps the CBC order, with the truncated penultimate block first
+//! - CS2 swaps the last two blocks, unless the final block is already complete
+//! - CS3 always swaps them, as Kerberos does (RFC 3962)
+
+use std::fmt::Display;
+
+use crate::cbc::cbc_encrypt;
+use crate::constant::KeyType;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::padding::ZeroPadding;
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+use crate::util::xor_in_place;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Cs1,
+    Cs2,
+    Cs3,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CbcCsError {
+    /// The input was shorter than one block
+    TooShort(usize),
+}
+
+impl Display for CbcCsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooShort(len) => write!(f, "input of {len} bytes is shorter than a block"),
+        }
+    }
+}
+
+impl std::error::Error for CbcCsError {}
+
+impl Variant {
+    /// Whether the last two ciphertext blocks are swapped, given the length of
+    /// the final block
+    fn swaps(self, last_len: usize) -> bool {
+        match self {
+            Self::Cs1 => false,
+            Self::Cs2 => last_len != 16,
+            Self::Cs3 => true,
+        }
+    }
+}
+
+/// The length of the final (possibly partial) block of an input of `len`
+/// bytes, from 1 to 16
+fn last_block_len(len: usize) -> usize {
+    match len % 16 {
+        0 => 16,
+        remainder => remainder,
+    }
+}
+
+/// Encrypt `plaintext`, which must be at least one block long
+pub fn cbc_cs_encrypt(
+    plaintext: &[u8],
+    iv: &[u8; 16],
+    variant: Variant,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, CbcCsError> {
+    if plaintext.len() < 16 {
+        return Err(CbcCsError::TooShort(plaintext.len()));
+    }
+
+    // zero padding is only added to a partial final block, which is what
+    // ciphertext stealing pads with before truncating
+    let mut result = cbc_encrypt(plaintext, iv, &ZeroPadding, expanded_key, key_type);
+
+    // a single block is ordinary CBC, since there is nothing to steal from
+    if plaintext.len() == 16 {
+        return Ok(result);
+    }
+
+    let last_len = last_block_len(plaintext.len());
+    let penultimate_start = result.len() - 32;
+    let last: [u8; 16] = result[result.len() - 16..].try_into().unwrap();
+    let penultimate = &mut result[penultimate_start..];
+
+    if variant.swaps(last_len) {
+        penultimate.copy_within(..16, 16);
+        penultimate[..16].copy_from_slice(&last);
+    } else {
+        penultimate.copy_within(16.., last_len);
+    }
+
+    result.truncate(plaintext.len());
+    Ok(result)
+}
+
+/// Decrypt a ciphertext produced by [`cbc_cs_encrypt`] with the same variant
+pub fn cbc_cs_decrypt(
+    ciphertext: &[u8],
+    iv: &[u8; 16],
+    variant: Variant,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, CbcCsError> {
+    if ciphertext.len() < 16 {
+        return Err(CbcCsError::TooShort(ciphertext.len()));
+    }
+
+    if ciphertext.len() == 16 {
+        let block: [u8; 16] = ciphertext.try_into().unwrap();
+        let mut output = inverse_cipher_block(&block, expanded_key, key_type);
+        xor_in_place(&mut output, iv);
+        return Ok(output.to_vec());
+    }
+
+    let last_len = last_block_len(ciphertext.len());
+    let (head, tail) = ciphertext.split_at(ciphertext.len() - 16 - last_len);
+
+    // put the last two blocks back in CS1 order: the truncated penultimate
+    // block `C_{n-1}*`, then the full final block `C_n`
+    let (stolen, last) = if variant.swaps(last_len) {
+        (&tail[16..], &tail[..16])
+    } else {
+        tail.split_at(last_len)
+    };
+    let last: [u8; 16] = last.try_into().unwrap();
+
+    let mut result = Vec::with_capacity(ciphertext.len());
+    let mut previous = *iv;
+    for block in head.chunks_exact(16) {
+        let block: [u8; 16] = block.try_into().unwrap();
+        let mut output = inverse_cipher_block(&block, expanded_key, key_type);
+        xor_in_place(&mut output, &previous);
+        result.extend(output);
+        previous = block;
+    }
+
+    // decrypting C_n gives C_{n-1} XOR (P_n || 0), which holds both the final
+    // plaintext and the bytes of C_{n-1} that were stolen
+    let z = inverse_cipher_block(&last, expanded_key, key_type);
+    let mut penultimate = z;
+    penultimate[..last_len].copy_from_slice(stolen);
+
+    let mut output = inverse_cipher_block(&penultimate, expanded_key, key_type);
+    xor_in_place(&mut output, &previous);
+    result.extend(output);
+
+    let mut final_block = z[..last_len].to_vec();
+    xor_in_place(&mut final_block, stolen);
+    result.extend(final_block);
+
+    Ok(result)
+}
+
+impl From<CbcCsError> for StreamError {
+    fn from(error: CbcCsError) -> Self {
+        match error {
+            CbcCsError::TooShort(len) => Self::TooShort(len),
+        }
+    }
+}
+
+/// Incremental encryption with ciphertext stealing
+///
+/// The last two blocks are held back until [`Encryptor::finalize`], since the
+/// stealing rearranges them.
+pub struct CbcCsEncryptor<'a> {
+    variant: Variant,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    previous: [u8; 16],
+    buffer: Buffer,
+}
+
+impl<'a> CbcCsEncryptor<'a> {
+    pub fn new(
+        iv: &[u8; 16],
+        variant: Variant,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Self {
+        Self {
+            variant,
+            expanded_key,
+            key_type,
+            previous: *iv,
+            buffer: Buffer::default(),
+        }
+    }
+}
+
+impl Encryptor for CbcCsEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        // anything more than a block and a byte can't be part of the last two
+        // blocks, so it's ordinary CBC
+        let blocks = self.buffer.take(plaintext, 16, 17);
+        let result = cbc_encrypt(
+            &blocks,
+            &self.previous,
+            &ZeroPadding,
+            self.expanded_key,
+            self.key_type,
+        );
+        if let Some(last) = result.rchunks_exact(16).next() {
+            self.previous = last.try_into().unwrap();
+        }
+        result
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let tail = std::mem::take(&mut self.buffer).remaining();
+        Ok(cbc_cs_encrypt(
+            &tail,
+            &self.previous,
+            self.variant,
+            self.expanded_key,
+            self.key_type,
+        )?)
+    }
+}
+
+/// Incremental decryption with ciphertext stealing
+pub struct CbcCsDecryptor<'a> {
+    variant: Variant,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    previous: [u8; 16],
+    buffer: Buffer,
+}
+
+impl<'a> CbcCsDecryptor<'a> {
+    pub fn new(
+        iv: &[u8; 16],
+        variant: Variant,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Self {
+        Self {
+            variant,
+            expanded_key,
+            key_type,
+            previous: *iv,
+            buffer: Buffer::default(),
+        }
+    }
+}
+
+impl Decryptor for CbcCsDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let mut blocks = self.buffer.take(ciphertext, 16, 17);
+        for block in blocks.chunks_exact_mut(16) {
+            let input: [u8; 16] = block.try_into().unwrap();
+            let mut output = inverse_cipher_block(&input, self.expanded_key, self.key_type);
+            xor_in_place(&mut output, &self.previous);
+            block.copy_from_slice(&output);
+            self.previous = input;
+        }
+        blocks
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let tail = std::mem::take(&mut self.buffer).remaining();
+        Ok(cbc_cs_decrypt(
+            &tail,
+            &self.previous,
+            self.variant,
+            self.expanded_key,
+            self.key_type,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::stream::check_streaming;
+
+    #[test]
+    fn kerberos_test() {
+        // Appendix B of RFC 3962, which uses CS3 with a zero IV
+        let key = b"chicken teriyaki";
+        let message = b"I would like the General Gau's Chicken, please, and wonton soup.";
+        let expanded_key = key_expansion(key, KeyType::Aes128);
+
+        let vectors: [(usize, &[u8]); 6] = [
+            (
+                17,
+                &[
+                    0xc6, 0x35, 0x35, 0x68, 0xf2, 0xbf, 0x8c, 0xb4, 0xd8, 0xa5, 0x80, 0x36, 0x2d,
+                    0xa7, 0xff, 0x7f, 0x97,
+                ],
+            ),
+            (
+                31,
+                &[
+                    0xfc, 0x00, 0x78, 0x3e, 0x0e, 0xfd, 0xb2, 0xc1, 0xd4, 0x45, 0xd4, 0xc8, 0xef,
+                    0xf7, 0xed, 0x22, 0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0, 0xc0, 0x7b,
+                    0x25, 0xe2, 0x5e, 0xcf, 0xe5,
+                ],
+            ),
+            (
+                32,
+                &[
+                    0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62, 0xd5, 0xbe, 0x7f, 0xcb, 0xcc, 0x98,
+                    0xeb, 0xf5, 0xa8, 0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0, 0xc0, 0x7b,
+                    0x25, 0xe2, 0x5e, 0xcf, 0xe5, 0x84,
+                ],
+            ),
+            (
+                47,
+                &[
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0, 0xc0, 0x7b, 0x25, 0xe2, 0x5e,
+                    0xcf, 0xe5, 0x84, 0xb3, 0xff, 0xfd, 0x94, 0x0c, 0x16, 0xa1, 0x8c, 0x1b, 0x55,
+                    0x49, 0xd2, 0xf8, 0x38, 0x02, 0x9e, 0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62,
+                    0xd5, 0xbe, 0x7f, 0xcb, 0xcc, 0x98, 0xeb, 0xf5,
+                ],
+            ),
+            (
+                48,
+                &[
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0, 0xc0, 0x7b, 0x25, 0xe2, 0x5e,
+                    0xcf, 0xe5, 0x84, 0x9d, 0xad, 0x8b, 0xbb, 0x96, 0xc4, 0xcd, 0xc0, 0x3b, 0xc1,
+                    0x03, 0xe1, 0xa1, 0x94, 0xbb, 0xd8, 0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62,
+                    0xd5, 0xbe, 0x7f, 0xcb, 0xcc, 0x98, 0xeb, 0xf5, 0xa8,
+                ],
+            ),
+            (
+                64,
+                &[
+                    0x97, 0x68, 0x72, 0x68, 0xd6, 0xec, 0xcc, 0xc0, 0xc0, 0x7b, 0x25, 0xe2, 0x5e,
+                    0xcf, 0xe5, 0x84, 0x39, 0x31, 0x25, 0x23, 0xa7, 0x86, 0x62, 0xd5, 0xbe, 0x7f,
+                    0xcb, 0xcc, 0x98, 0xeb, 0xf5, 0xa8, 0x48, 0x07, 0xef, 0xe8, 0x36, 0xee, 0x89,
+                    0xa5, 0x26, 0x73, 0x0d, 0xbc, 0x2f, 0x7b, 0xc8, 0x40, 0x9d, 0xad, 0x8b, 0xbb,
+                    0x96, 0xc4, 0xcd, 0xc0, 0x3b, 0xc1, 0x03, 0xe1, 0xa1, 0x94, 0xbb, 0xd8,
+                ],
+            ),
+        ];
+
+        for (len, expected) in vectors {
+            let result = cbc_cs_encrypt(
+                &message[..len],
+                &[0; 16],
+                Variant::Cs3,
+                &expanded_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+            assert_eq!(result, expected, "message of {len} bytes");
+
+            let result = cbc_cs_decrypt(
+                expected,
+                &[0; 16],
+                Variant::Cs3,
+                &expanded_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+            assert_eq!(result, &message[..len]);
+        }
+    }
+
+    #[test]
+    fn variants_test() {
+        // the SP 800-38A key, IV and plaintext, on either side of the block
+        // boundaries, checked against a reference built on AES-CBC
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let iv = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac,
+            0x45, 0xaf, 0x8e, 0x51, 0x30,
+        ];
+        let expanded_key = key_expansion(&key, KeyType::Aes128);
+
+        // the CBC ciphertext of the first two blocks, whose second block is
+        // stolen from when the third is partial
+        let c_1 = [
+            0x76, 0x49, 0xab, 0xac, 0x81, 0x19, 0xb2, 0x46, 0xce, 0xe9, 0x8e, 0x9b, 0x12, 0xe9,
+            0x19, 0x7d,
+        ];
+        let c_2 = [
+            0x50, 0x86, 0xcb, 0x9b, 0x50, 0x72, 0x19, 0xee, 0x95, 0xdb, 0x11, 0x3a, 0x91, 0x76,
+            0x78, 0xb2,
+        ];
+        let c_3 = [
+            0x3b, 0xdc, 0xc9, 0x5a, 0xb1, 0x08, 0xda, 0x14, 0x4e, 0x7f, 0x2e, 0x5d, 0x2e, 0xee,
+            0x13, 0x25,
+        ];
+
+        let expected: Vec<u8> = [&c_1[..], &c_2[..1], &c_3].concat();
+        let result = cbc_cs_encrypt(
+            &plaintext,
+            &iv,
+            Variant::Cs1,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+
+        let expected: Vec<u8> = [&c_1[..], &c_3, &c_2[..1]].concat();
+        for variant in [Variant::Cs2, Variant::Cs3] {
+            let result =
+                cbc_cs_encrypt(&plaintext, &iv, variant, &expanded_key, KeyType::Aes128).unwrap();
+            assert_eq!(result, expected);
+        }
+
+        // with whole blocks, CS1 and CS2 are plain CBC and CS3 swaps the last two
+        let expected: Vec<u8> = [c_1, c_2].concat();
+        for variant in [Variant::Cs1, Variant::Cs2] {
+            let result = cbc_cs_encrypt(
+                &plaintext[..32],
+                &iv,
+                variant,
+                &expanded_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+            assert_eq!(result, expected);
+        }
+        let result = cbc_cs_encrypt(
+            &plaintext[..32],
+            &iv,
+            Variant::Cs3,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, [c_2, c_1].concat());
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let expanded_key = key_expansion(&[0x24; 32], KeyType::Aes256);
+        let iv = [0x81; 16];
+        let plaintext: Vec<u8> = (0..80).collect();
+
+        for variant in [Variant::Cs1, Variant::Cs2, Variant::Cs3] {
+            for len in 16..=80 {
+                let ciphertext = cbc_cs_encrypt(
+                    &plaintext[..len],
+                    &iv,
+                    variant,
+                    &expanded_key,
+                    KeyType::Aes256,
+                )
+                .unwrap();
+                assert_eq!(ciphertext.len(), len);
+
+                let result =
+                    cbc_cs_decrypt(&ciphertext, &iv, variant, &expanded_key, KeyType::Aes256)
+                        .unwrap();
+                assert_eq!(result, &plaintext[..len], "{variant:?} with {len} bytes");
+            }
+        }
+    }
+
+    #[test]
+    fn too_short_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+
+        let result = cbc_cs_encrypt(
+            &[0; 15],
+            &[0; 16],
+            Variant::Cs1,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(CbcCsError::TooShort(15)));
+
+        let result = cbc_cs_decrypt(&[], &[0; 16], Variant::Cs3, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(CbcCsError::TooShort(0)));
+    }
+
+    #[test]
+    fn streaming_test() {
+        let expanded_key = key_expansion(&[0x24; 32], KeyType::Aes256);
+        let iv = [0x81; 16];
+        let plaintext: Vec<u8> = (0..80).collect();
+
+        for variant in [Variant::Cs1, Variant::Cs2, Variant::Cs3] {
+            for len in [16, 17, 31, 32, 33, 48, 80] {
+                let expected = cbc_cs_encrypt(
+                    &plaintext[..len],
+                    &iv,
+                    variant,
+                    &expanded_key,
+                    KeyType::Aes256,
+                )
+                .unwrap();
+
+                check_streaming(
+                    &plaintext[..len],
+                    &expected,
+                    || CbcCsEncryptor::new(&iv, variant, &expanded_key, KeyType::Aes256),
+                    || CbcCsDecryptor::new(&iv, variant, &expanded_key, KeyType::Aes256),
+                );
+            }
+        }
+
+        let mut encryptor = CbcCsEncryptor::new(&iv, Variant::Cs3, &expanded_key, KeyType::Aes256);
+        assert_eq!(encryptor.update(&[0; 15]), []);
+        assert_eq!(encryptor.finalize(), Err(StreamError::TooShort(15)));
+    }
+}
@@ -0,0 +1,99 @@
+//! CBC-MAC with the message length prepended
+//!
+//! Raw CBC-MAC is only secure for messages of one fixed length; otherwise a
+//! tag can be extended into a forgery for a longer message. Prepending the
+//! length as the first block makes the inputs prefix-free. The length is the
+//! number of bits as a 128-bit big-endian integer, and the message is then
+//! zero-padded to a whole number of blocks.
+//!
+//! New protocols should prefer [`cmac`](crate::cmac), which doesn't need to
+//! know the length up front.
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::util::xor_in_place;
+
+/// Compute the 16-byte CBC-MAC tag of the length block followed by `message`
+pub fn cbc_mac(message: &[u8], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let bit_len = message.len() as u128 * 8;
+    let mut mac = cipher_block(&bit_len.to_be_bytes(), expanded_key, key_type);
+
+    for block in message.chunks(16) {
+        // the final chunk is zero-padded, which XORing a short slice does
+        xor_in_place(&mut mac, block);
+        mac = cipher_block(&mac, expanded_key, key_type);
+    }
+
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    #[test]
+    fn cbc_mac_test() {
+        // the RFC 4493 key and message prefixes, checked against AES-CBC with
+        // a zero IV over the length block and padded message
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let message = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac,
+            0x45, 0xaf, 0x8e, 0x51, 0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11, 0xe5, 0xfb,
+            0xc1, 0x19, 0x1a, 0x0a, 0x52, 0xef, 0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17,
+            0xad, 0x2b, 0x41, 0x7b, 0xe6, 0x6c, 0x37, 0x10,
+        ];
+        let expanded_key = key_expansion(&key, KeyType::Aes128);
+
+        let vectors = [
+            (
+                0,
+                [
+                    0x7d, 0xf7, 0x6b, 0x0c, 0x1a, 0xb8, 0x99, 0xb3, 0x3e, 0x42, 0xf0, 0x47, 0xb9,
+                    0x1b, 0x54, 0x6f,
+                ],
+            ),
+            (
+                16,
+                [
+                    0xc9, 0x88, 0x61, 0xab, 0x37, 0xa9, 0xcc, 0x12, 0x19, 0x6a, 0xe1, 0x7e, 0xe9,
+                    0xdf, 0x6f, 0xd0,
+                ],
+            ),
+            (
+                40,
+                [
+                    0x0b, 0xd6, 0xc8, 0x3c, 0x75, 0xe9, 0xd2, 0xb0, 0x0b, 0xf4, 0x37, 0x5a, 0x88,
+                    0xc3, 0x31, 0xc0,
+                ],
+            ),
+            (
+                64,
+                [
+                    0x5e, 0xac, 0xa6, 0x6a, 0x3e, 0xd4, 0x48, 0x3b, 0x39, 0xed, 0x0b, 0xe8, 0x23,
+                    0xe7, 0x59, 0xfa,
+                ],
+            ),
+        ];
+
+        for (len, expected) in vectors {
+            let result = cbc_mac(&message[..len], &expanded_key, KeyType::Aes128);
+            assert_eq!(result, expected, "message of {len} bytes");
+        }
+    }
+
+    #[test]
+    fn length_binding_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+
+        // zero padding alone would make these collide
+        assert_ne!(
+            cbc_mac(b"abc", &expanded_key, KeyType::Aes128),
+            cbc_mac(b"abc\0", &expanded_key, KeyType::Aes128)
+        );
+    }
+}
@@ -0,0 +1,508 @@
+//! CCM* mode, as used by IEEE 802.15.4 and Zigbee to secure frames
+//!
+//! CCM* is CCM with two extensions: the MIC may be empty (encryption-only
+//! operation), and the 13-byte nonce is built from the sender's extended
+//! address, the frame counter and the security level of the frame.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::util::{constant_time_eq, pad_to_block, xor_in_place};
+
+/// Length of the nonce used by IEEE 802.15.4 frames
+pub const NONCE_LEN: usize = 13;
+
+/// Width of the length field, in bytes (`15 - NONCE_LEN`)
+const L: usize = 15 - NONCE_LEN;
+
+/// The eight security levels defined by IEEE 802.15.4
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecurityLevel {
+    None,
+    Mic32,
+    Mic64,
+    Mic128,
+    Enc,
+    EncMic32,
+    EncMic64,
+    EncMic128,
+}
+
+impl SecurityLevel {
+    /// Length of the message integrity code, in bytes
+    pub fn mic_len(&self) -> usize {
+        match self {
+            Self::None | Self::Enc => 0,
+            Self::Mic32 | Self::EncMic32 => 4,
+            Self::Mic64 | Self::EncMic64 => 8,
+            Self::Mic128 | Self::EncMic128 => 16,
+        }
+    }
+
+    /// Whether the payload is encrypted at this level
+    pub fn encrypts(&self) -> bool {
+        matches!(
+            self,
+            Self::Enc | Self::EncMic32 | Self::EncMic64 | Self::EncMic128
+        )
+    }
+}
+
+impl From<SecurityLevel> for u8 {
+    fn from(level: SecurityLevel) -> Self {
+        match level {
+            SecurityLevel::None => 0,
+            SecurityLevel::Mic32 => 1,
+            SecurityLevel::Mic64 => 2,
+            SecurityLevel::Mic128 => 3,
+            SecurityLevel::Enc => 4,
+            SecurityLevel::EncMic32 => 5,
+            SecurityLevel::EncMic64 => 6,
+            SecurityLevel::EncMic128 => 7,
+        }
+    }
+}
+
+impl TryFrom<u8> for SecurityLevel {
+    type Error = CcmStarError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Mic32),
+            2 => Ok(Self::Mic64),
+            3 => Ok(Self::Mic128),
+            4 => Ok(Self::Enc),
+            5 => Ok(Self::EncMic32),
+            6 => Ok(Self::EncMic64),
+            7 => Ok(Self::EncMic128),
+            _ => Err(CcmStarError::InvalidSecurityLevel(value)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CcmStarError {
+    /// The security level is not one of the eight defined levels
+    InvalidSecurityLevel(u8),
+    /// The payload doesn't fit in the 2-byte length field
+    PayloadTooLong(usize),
+    /// The secured payload is shorter than the MIC it should contain
+    TooShort,
+    /// The MIC didn't match the header and payload
+    AuthenticationFailed,
+}
+
+impl Display for CcmStarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidSecurityLevel(level) => write!(f, "invalid security level {level}"),
+            Self::PayloadTooLong(len) => write!(f, "payload of {len} bytes is too long"),
+            Self::TooShort => write!(f, "secured payload is shorter than its MIC"),
+            Self::AuthenticationFailed => write!(f, "MIC verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for CcmStarError {}
+
+/// Build the nonce for a frame from the sender's extended address, the frame
+/// counter and the security level
+pub fn build_nonce(
+    source_address: u64,
+    frame_counter: u32,
+    level: SecurityLevel,
+) -> [u8; NONCE_LEN] {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..8].copy_from_slice(&source_address.to_be_bytes());
+    nonce[8..12].copy_from_slice(&frame_counter.to_be_bytes());
+    nonce[12] = level.into();
+    nonce
+}
+
+/// Secure a frame, returning the (possibly encrypted) payload followed by the MIC
+///
+/// The header is always authenticated but never encrypted. At levels without
+/// encryption the payload is authenticated along with the header.
+pub fn ccm_star_encrypt(
+    header: &[u8],
+    payload: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    level: SecurityLevel,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, CcmStarError> {
+    if payload.len() >= 1 << (8 * L) {
+        return Err(CcmStarError::PayloadTooLong(payload.len()));
+    }
+
+    let (auth_data, message) = split_inputs(header, payload, level);
+    let tag = authenticate(&auth_data, message, nonce, level, expanded_key, key_type);
+
+    let mut result = payload.to_vec();
+    if level.encrypts() {
+        apply_keystream(&mut result, nonce, expanded_key, key_type);
+    }
+    result.extend(encrypt_tag(&tag, nonce, level, expanded_key, key_type));
+
+    Ok(result)
+}
+
+/// Unsecure a frame produced by [`ccm_star_encrypt`], verifying its MIC
+pub fn ccm_star_decrypt(
+    header: &[u8],
+    secured_payload: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    level: SecurityLevel,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, CcmStarError> {
+    let payload_len = secured_payload
+        .len()
+        .checked_sub(level.mic_len())
+        .ok_or(CcmStarError::TooShort)?;
+    if payload_len >= 1 << (8 * L) {
+        return Err(CcmStarError::PayloadTooLong(payload_len));
+    }
+
+    let (payload, mic) = secured_payload.split_at(payload_len);
+
+    let mut payload = payload.to_vec();
+    if level.encrypts() {
+        apply_keystream(&mut payload, nonce, expanded_key, key_type);
+    }
+
+    let (auth_data, message) = split_inputs(header, &payload, level);
+    let tag = authenticate(&auth_data, message, nonce, level, expanded_key, key_type);
+    let expected_mic = encrypt_tag(&tag, nonce, level, expanded_key, key_type);
+
+    if !constant_time_eq(&expected_mic, mic) {
+        return Err(CcmStarError::AuthenticationFailed);
+    }
+
+    Ok(payload)
+}
+
+/// Split the frame into the data that is only authenticated (`a`) and the
+/// data that is also encrypted (`m`)
+fn split_inputs<'a>(header: &[u8], payload: &'a [u8], level: SecurityLevel) -> (Vec<u8>, &'a [u8]) {
+    if level.encrypts() {
+        (header.to_vec(), payload)
+    } else {
+        ([header, payload].concat(), &[])
+    }
+}
+
+/// Compute the CBC-MAC `T` over the formatted nonce, auth data and message
+fn authenticate(
+    auth_data: &[u8],
+    message: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    level: SecurityLevel,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mic_len = level.mic_len();
+    if mic_len == 0 {
+        return [0; 16];
+    }
+
+    let adata_flag = if auth_data.is_empty() { 0 } else { 1 << 6 };
+    let mic_flag = (((mic_len - 2) / 2) as u8) << 3;
+    let length_flag = (L - 1) as u8;
+
+    let mut b_0 = [0; 16];
+    b_0[0] = adata_flag | mic_flag | length_flag;
+    b_0[1..=NONCE_LEN].copy_from_slice(nonce);
+    b_0[16 - L..].copy_from_slice(&(message.len() as u16).to_be_bytes());
+
+    let mut blocks = b_0.to_vec();
+    if !auth_data.is_empty() {
+        blocks.extend(encode_auth_len(auth_data.len()));
+        blocks.extend(auth_data);
+        pad_to_block(&mut blocks);
+    }
+    blocks.extend(message);
+    pad_to_block(&mut blocks);
+
+    blocks.chunks(16).fold([0; 16], |mut mac, block| {
+        xor_in_place(&mut mac, block);
+        cipher_block(&mac, expanded_key, key_type)
+    })
+}
+
+/// Encode the length of the auth data as described in RFC 3610 section 2.2
+fn encode_auth_len(len: usize) -> Vec<u8> {
+    if len < 0xff00 {
+        (len as u16).to_be_bytes().to_vec()
+    } else if len <= u32::MAX as usize {
+        [&[0xff, 0xfe][..], &(len as u32).to_be_bytes()].concat()
+    } else {
+        [&[0xff, 0xff][..], &(len as u64).to_be_bytes()].concat()
+    }
+}
+
+/// Build the counter block `A_i`
+fn counter_block(nonce: &[u8; NONCE_LEN], i: u16) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[0] = (L - 1) as u8;
+    block[1..=NONCE_LEN].copy_from_slice(nonce);
+    block[16 - L..].copy_from_slice(&i.to_be_bytes());
+    block
+}
+
+/// Encrypt or decrypt `data` in place with the keystream `S_1, S_2, ...`
+fn apply_keystream(
+    data: &mut [u8],
+    nonce: &[u8; NONCE_LEN],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) {
+    for (i, chunk) in data.chunks_mut(16).enumerate() {
+        let s_i = cipher_block(&counter_block(nonce, i as u16 + 1), expanded_key, key_type);
+        xor_in_place(chunk, &s_i);
+    }
+}
+
+/// Turn the CBC-MAC into the MIC by encrypting it with `S_0` and truncating it
+fn encrypt_tag(
+    tag: &[u8; 16],
+    nonce: &[u8; NONCE_LEN],
+    level: SecurityLevel,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    let mic_len = level.mic_len();
+    if mic_len == 0 {
+        return vec![];
+    }
+
+    let mut mic = tag[..mic_len].to_vec();
+    xor_in_place(
+        &mut mic,
+        &cipher_block(&counter_block(nonce, 0), expanded_key, key_type),
+    );
+    mic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    // Test vectors from IEEE 802.15.4-2011 Annex C.2
+    const KEY: [u8; 16] = [
+        0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xcb, 0xcc, 0xcd, 0xce,
+        0xcf,
+    ];
+    const SOURCE_ADDRESS: u64 = 0xacde480000000001;
+    const FRAME_COUNTER: u32 = 5;
+
+    #[test]
+    fn build_nonce_test() {
+        let nonce = build_nonce(SOURCE_ADDRESS, FRAME_COUNTER, SecurityLevel::Mic64);
+        let expected = [
+            0xac, 0xde, 0x48, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x02,
+        ];
+        assert_eq!(nonce, expected);
+    }
+
+    #[test]
+    fn security_level_test() {
+        for value in 0..8 {
+            let level = SecurityLevel::try_from(value).unwrap();
+            assert_eq!(u8::from(level), value);
+        }
+        assert_eq!(
+            SecurityLevel::try_from(8),
+            Err(CcmStarError::InvalidSecurityLevel(8))
+        );
+    }
+
+    #[test]
+    fn beacon_frame_mic_64_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let level = SecurityLevel::Mic64;
+        let nonce = build_nonce(SOURCE_ADDRESS, FRAME_COUNTER, level);
+
+        let header = [
+            0x08, 0xd0, 0x84, 0x21, 0x43, 0x01, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x02,
+            0x05, 0x00, 0x00, 0x00,
+        ];
+        let payload = [0x55, 0xcf, 0x00, 0x00, 0x51, 0x52, 0x53, 0x54];
+        let expected = [
+            0x55, 0xcf, 0x00, 0x00, 0x51, 0x52, 0x53, 0x54, 0x22, 0x3b, 0xc1, 0xec, 0x84, 0x1a,
+            0xb5, 0x53,
+        ];
+
+        let secured = ccm_star_encrypt(
+            &header,
+            &payload,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(secured, expected);
+
+        let unsecured = ccm_star_decrypt(
+            &header,
+            &secured,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(unsecured, payload);
+    }
+
+    #[test]
+    fn data_frame_enc_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let level = SecurityLevel::Enc;
+        let nonce = build_nonce(SOURCE_ADDRESS, FRAME_COUNTER, level);
+
+        let header = [
+            0x69, 0xdc, 0x84, 0x21, 0x43, 0x02, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x01,
+            0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x04, 0x05, 0x00, 0x00, 0x00,
+        ];
+        let payload = [0x61, 0x62, 0x63, 0x64];
+        let expected = [0xd4, 0x3e, 0x02, 0x2b];
+
+        let secured = ccm_star_encrypt(
+            &header,
+            &payload,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(secured, expected);
+
+        let unsecured = ccm_star_decrypt(
+            &header,
+            &secured,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(unsecured, payload);
+    }
+
+    #[test]
+    fn command_frame_enc_mic_64_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let level = SecurityLevel::EncMic64;
+        let nonce = build_nonce(SOURCE_ADDRESS, FRAME_COUNTER, level);
+
+        let header = [
+            0x2b, 0xdc, 0x84, 0x21, 0x43, 0x02, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0xff,
+            0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x06, 0x05, 0x00, 0x00, 0x00,
+            0x01,
+        ];
+        let payload = [0xce];
+        let expected = [0xd8, 0x4f, 0xde, 0x52, 0x90, 0x61, 0xf9, 0xc6, 0xf1];
+
+        let secured = ccm_star_encrypt(
+            &header,
+            &payload,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(secured, expected);
+
+        let unsecured = ccm_star_decrypt(
+            &header,
+            &secured,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(unsecured, payload);
+    }
+
+    #[test]
+    fn all_levels_round_trip_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let header = [0x01, 0x02, 0x03];
+        let payload: Vec<u8> = (0..40).collect();
+
+        for value in 0..8 {
+            let level = SecurityLevel::try_from(value).unwrap();
+            let nonce = build_nonce(SOURCE_ADDRESS, FRAME_COUNTER, level);
+
+            let secured = ccm_star_encrypt(
+                &header,
+                &payload,
+                &nonce,
+                level,
+                &expanded_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+            assert_eq!(secured.len(), payload.len() + level.mic_len());
+            assert_eq!(secured[..payload.len()] == payload[..], !level.encrypts());
+
+            let unsecured = ccm_star_decrypt(
+                &header,
+                &secured,
+                &nonce,
+                level,
+                &expanded_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+            assert_eq!(unsecured, payload);
+        }
+    }
+
+    #[test]
+    fn tampered_frame_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let level = SecurityLevel::EncMic32;
+        let nonce = build_nonce(SOURCE_ADDRESS, FRAME_COUNTER, level);
+        let header = [0x01, 0x02, 0x03];
+
+        let mut secured = ccm_star_encrypt(
+            &header,
+            b"hello",
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        secured[0] ^= 1;
+
+        let result = ccm_star_decrypt(
+            &header,
+            &secured,
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(CcmStarError::AuthenticationFailed));
+
+        let result = ccm_star_decrypt(
+            &header,
+            &[0; 3],
+            &nonce,
+            level,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(CcmStarError::TooShort));
+    }
+}
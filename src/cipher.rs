@@ -96,7 +96,7 @@ where
         .as_ref()
         .iter()
         .map(AsRef::as_ref)
-        .map(|row| row.iter().copied().collect())
+        .map(|row| row.to_vec())
         .collect();
     let num_rows = state.len();
     let num_cols = state[0].len();
@@ -113,9 +113,6 @@ where
     }
 
     result
-        .into_iter()
-        .map(|row| row.into_iter().map(Into::into).collect())
-        .collect()
 }
 
 pub fn cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: KeyType) -> Vec<u8> {
@@ -128,7 +125,7 @@ pub fn cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: KeyType)
 
     let state = input
         .as_ref()
-        .into_iter()
+        .iter()
         .enumerate()
         .fold(vec![], |mut result, (i, value)| {
             if i / n_b == 0 {
@@ -146,7 +143,7 @@ pub fn cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: KeyType)
 
     let state = add_round_key(state, key_sched);
 
-    let state = (1..n_r).into_iter().fold(state, |state, round| {
+    let state = (1..n_r).fold(state, |state, round| {
         debug!(print_state(round, Step::Start, &state));
 
         let state = sub_bytes(state);
@@ -161,9 +158,7 @@ pub fn cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: KeyType)
         let key_sched = get_key_sched(expanded_key, round, n_b);
         debug!(print_key_sched(round, Step::KeySchedule, key_sched));
 
-        let state = add_round_key(state, key_sched);
-
-        state
+        add_round_key(state, key_sched)
     });
 
     let round = n_r;
@@ -183,8 +178,8 @@ pub fn cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: KeyType)
 
     let mut result = Vec::with_capacity(state.len() * state[0].len());
     for i in 0..state[0].len() {
-        for j in 0..state.len() {
-            result.push(state[j][i]);
+        for row in &state {
+            result.push(row[i]);
         }
     }
 
@@ -194,6 +189,13 @@ pub fn cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: KeyType)
     result
 }
 
+/// Encrypt a single block, returning it as an array instead of a `Vec`
+pub fn cipher_block(input: &[u8; 16], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    cipher(input, expanded_key, key_type)
+        .try_into()
+        .expect("the cipher always outputs a single block")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,276 @@
+//! CMAC message authentication (NIST SP 800-38B / RFC 4493)
+//!
+//! Messages can be authenticated in one call with [`cmac`], or incrementally
+//! with [`Cmac`] when they arrive in pieces.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::finite_field::dbl;
+use crate::util::{constant_time_eq, xor_in_place};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CmacError {
+    /// Tags must be between 1 and 16 bytes
+    InvalidTagLength(usize),
+    /// The tag didn't match the message
+    VerificationFailed,
+}
+
+impl Display for CmacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidTagLength(len) => write!(f, "invalid tag length {len}"),
+            Self::VerificationFailed => write!(f, "tag verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for CmacError {}
+
+/// Generate the subkeys `K1` and `K2` by doubling the encrypted zero block
+pub fn subkeys(expanded_key: &[u32], key_type: KeyType) -> ([u8; 16], [u8; 16]) {
+    let l = cipher_block(&[0; 16], expanded_key, key_type);
+    let k_1 = dbl(&l);
+    let k_2 = dbl(&k_1);
+    (k_1, k_2)
+}
+
+/// An in-progress CMAC computation
+///
+/// The most recent block is held back until more data arrives or the tag is
+/// finalized, since the last block is masked with a subkey before encryption.
+pub struct Cmac<'a> {
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    k_1: [u8; 16],
+    k_2: [u8; 16],
+    mac: [u8; 16],
+    buffer: [u8; 16],
+    buffer_len: usize,
+}
+
+impl<'a> Cmac<'a> {
+    pub fn new(expanded_key: &'a [u32], key_type: KeyType) -> Self {
+        let (k_1, k_2) = subkeys(expanded_key, key_type);
+
+        Self {
+            expanded_key,
+            key_type,
+            k_1,
+            k_2,
+            mac: [0; 16],
+            buffer: [0; 16],
+            buffer_len: 0,
+        }
+    }
+
+    /// Add `data` to the message being authenticated
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.buffer_len == 16 {
+                xor_in_place(&mut self.mac, &self.buffer);
+                self.mac = cipher_block(&self.mac, self.expanded_key, self.key_type);
+                self.buffer_len = 0;
+            }
+
+            let take = (16 - self.buffer_len).min(data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+        }
+    }
+
+    /// Compute the full 16-byte tag
+    pub fn finalize(self) -> [u8; 16] {
+        let mut last_block = [0; 16];
+        last_block[..self.buffer_len].copy_from_slice(&self.buffer[..self.buffer_len]);
+
+        if self.buffer_len == 16 {
+            xor_in_place(&mut last_block, &self.k_1);
+        } else {
+            last_block[self.buffer_len] = 0x80;
+            xor_in_place(&mut last_block, &self.k_2);
+        }
+
+        xor_in_place(&mut last_block, &self.mac);
+        cipher_block(&last_block, self.expanded_key, self.key_type)
+    }
+
+    /// Check `tag` against the message in constant time. The tag may be
+    /// truncated to any length from 1 to 16 bytes, although SP 800-38B
+    /// recommends at least 8.
+    pub fn verify(self, tag: &[u8]) -> Result<(), CmacError> {
+        if tag.is_empty() || tag.len() > 16 {
+            return Err(CmacError::InvalidTagLength(tag.len()));
+        }
+
+        let expected = self.finalize();
+        if !constant_time_eq(&expected[..tag.len()], tag) {
+            return Err(CmacError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+}
+
+/// Compute the full 16-byte CMAC tag of `message`
+pub fn cmac(message: &[u8], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let mut cmac = Cmac::new(expanded_key, key_type);
+    cmac.update(message);
+    cmac.finalize()
+}
+
+/// Check a possibly truncated `tag` for `message`, in constant time
+pub fn cmac_verify(
+    message: &[u8],
+    tag: &[u8],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<(), CmacError> {
+    let mut cmac = Cmac::new(expanded_key, key_type);
+    cmac.update(message);
+    cmac.verify(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    // the key and message used by RFC 4493 and SP 800-38B
+    const KEY: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    const MESSAGE: [u8; 64] = [
+        0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93, 0x17,
+        0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac, 0x45, 0xaf,
+        0x8e, 0x51, 0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11, 0xe5, 0xfb, 0xc1, 0x19, 0x1a,
+        0x0a, 0x52, 0xef, 0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17, 0xad, 0x2b, 0x41, 0x7b,
+        0xe6, 0x6c, 0x37, 0x10,
+    ];
+
+    #[test]
+    fn subkeys_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let k_1 = [
+            0xfb, 0xee, 0xd6, 0x18, 0x35, 0x71, 0x33, 0x66, 0x7c, 0x85, 0xe0, 0x8f, 0x72, 0x36,
+            0xa8, 0xde,
+        ];
+        let k_2 = [
+            0xf7, 0xdd, 0xac, 0x30, 0x6a, 0xe2, 0x66, 0xcc, 0xf9, 0x0b, 0xc1, 0x1e, 0xe4, 0x6d,
+            0x51, 0x3b,
+        ];
+
+        assert_eq!(subkeys(&expanded_key, KeyType::Aes128), (k_1, k_2));
+    }
+
+    #[test]
+    fn cmac_empty_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let expected = [
+            0xbb, 0x1d, 0x69, 0x29, 0xe9, 0x59, 0x37, 0x28, 0x7f, 0xa3, 0x7d, 0x12, 0x9b, 0x75,
+            0x67, 0x46,
+        ];
+
+        assert_eq!(cmac(&[], &expanded_key, KeyType::Aes128), expected);
+    }
+
+    #[test]
+    fn cmac_test() {
+        // examples 2 to 4 from RFC 4493 section 4
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+
+        let expected = [
+            0x07, 0x0a, 0x16, 0xb4, 0x6b, 0x4d, 0x41, 0x44, 0xf7, 0x9b, 0xdd, 0x9d, 0xd0, 0x4a,
+            0x28, 0x7c,
+        ];
+        assert_eq!(
+            cmac(&MESSAGE[..16], &expanded_key, KeyType::Aes128),
+            expected
+        );
+
+        let expected = [
+            0xdf, 0xa6, 0x67, 0x47, 0xde, 0x9a, 0xe6, 0x30, 0x30, 0xca, 0x32, 0x61, 0x14, 0x97,
+            0xc8, 0x27,
+        ];
+        assert_eq!(
+            cmac(&MESSAGE[..40], &expanded_key, KeyType::Aes128),
+            expected
+        );
+
+        let expected = [
+            0x51, 0xf0, 0xbe, 0xbf, 0x7e, 0x3b, 0x9d, 0x92, 0xfc, 0x49, 0x74, 0x17, 0x79, 0x36,
+            0x3c, 0xfe,
+        ];
+        assert_eq!(cmac(&MESSAGE, &expanded_key, KeyType::Aes128), expected);
+    }
+
+    #[test]
+    fn cmac_aes_256_test() {
+        // example D.3 from NIST SP 800-38B
+        let key = [
+            0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d,
+            0x77, 0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3,
+            0x09, 0x14, 0xdf, 0xf4,
+        ];
+        let expanded_key = key_expansion(&key, KeyType::Aes256);
+        let expected = [
+            0xaa, 0xf3, 0xd8, 0xf1, 0xde, 0x56, 0x40, 0xc2, 0x32, 0xf5, 0xb1, 0x69, 0xb9, 0xc9,
+            0x11, 0xe6,
+        ];
+
+        assert_eq!(
+            cmac(&MESSAGE[..40], &expanded_key, KeyType::Aes256),
+            expected
+        );
+    }
+
+    #[test]
+    fn incremental_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let expected = cmac(&MESSAGE, &expanded_key, KeyType::Aes128);
+
+        // split points on and off block boundaries give the same tag
+        for chunk_size in [1, 7, 16, 17, 64] {
+            let mut cmac = Cmac::new(&expanded_key, KeyType::Aes128);
+            for chunk in MESSAGE.chunks(chunk_size) {
+                cmac.update(chunk);
+            }
+            cmac.update(&[]);
+            assert_eq!(cmac.finalize(), expected);
+        }
+    }
+
+    #[test]
+    fn verify_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let tag = cmac(&MESSAGE, &expanded_key, KeyType::Aes128);
+
+        assert_eq!(
+            cmac_verify(&MESSAGE, &tag, &expanded_key, KeyType::Aes128),
+            Ok(())
+        );
+        assert_eq!(
+            cmac_verify(&MESSAGE, &tag[..8], &expanded_key, KeyType::Aes128),
+            Ok(())
+        );
+
+        let mut wrong_tag = tag;
+        wrong_tag[7] ^= 1;
+        assert_eq!(
+            cmac_verify(&MESSAGE, &wrong_tag[..8], &expanded_key, KeyType::Aes128),
+            Err(CmacError::VerificationFailed)
+        );
+        assert_eq!(
+            cmac_verify(&MESSAGE[..63], &tag, &expanded_key, KeyType::Aes128),
+            Err(CmacError::VerificationFailed)
+        );
+        assert_eq!(
+            cmac_verify(&MESSAGE, &[], &expanded_key, KeyType::Aes128),
+            Err(CmacError::InvalidTagLength(0))
+        );
+    }
+}
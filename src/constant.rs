@@ -53,7 +53,7 @@ pub const INV_S_BOX: [[u8; 16]; 16] = [
     [ 0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d ]
 ];
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyType {
     Aes128,
     Aes192,
@@ -61,6 +61,16 @@ pub enum KeyType {
 }
 
 impl KeyType {
+    /// The key type for a key of `len` bytes, if there is one
+    pub fn from_key_len(len: usize) -> Option<Self> {
+        match len {
+            16 => Some(Self::Aes128),
+            24 => Some(Self::Aes192),
+            32 => Some(Self::Aes256),
+            _ => None,
+        }
+    }
+
     pub fn n_k(&self) -> usize {
         match self {
             Self::Aes128 => 4,
@@ -0,0 +1,1236 @@
+//! A versioned, self-describing container for authenticated encryption
+//!
+//! A container is a header followed by the plaintext split into chunks, each
+//! encrypted and authenticated separately, so a damaged file is rejected at
+//! the first bad chunk rather than only at the end. This is the STREAM
+//! construction (Hoang, Reyhanitabar, Rogaway and Vizár, "Online
+//! Authenticated-Encryption and its Nonce-Reuse Misuse-Resistance"). The
+//! header records everything needed to decrypt except the key (or password):
+//!
+//! | Field        | Size | Contents                                         |
+//! |--------------|------|--------------------------------------------------|
+//! | magic        | 8    | `\x89AES\r\n\x1a\n`                              |
+//! | version      | 1    | [`VERSION`]                                      |
+//! | algorithm    | 1    | 1, 2 or 3 for AES-128, AES-192 or AES-256        |
+//! | mode         | 1    | an [`AeadMode`]                                  |
+//! | KDF          | 1    | 0 for a raw key, or 1 for PBKDF2-HMAC-SHA-256    |
+//! | KDF params   | 0/20 | for PBKDF2, the iteration count (`u32`) and salt |
+//! | nonce prefix | 8    | random, and unique for each container            |
+//! | chunk size   | 4    | plaintext bytes in every chunk but the last      |
+//!
+//! Each chunk is then its index (`u32`), a flag that is 1 for the final chunk
+//! and 0 otherwise, and its ciphertext and 16-byte tag. The nonce is the
+//! prefix followed by the index and flag, and the whole header is the
+//! associated data, so chunks can't be reordered, dropped from the end, or
+//! moved between containers without failing authentication. The index and
+//! flag are also stored in the clear so those cases are reported precisely.
+//! Integers are big-endian. GCM runs the 13-byte nonce through GHASH, as it
+//! does any nonce that isn't 12 bytes.
+//!
+//! Besides [`seal`] and [`open`] for whole containers, [`ContainerEncryptor`]
+//! and [`ContainerDecryptor`] work through the chunks incrementally. Since
+//! every chunk sits at a fixed offset and can be checked on its own,
+//! [`ContainerReader`] can decrypt any range of a container in a seekable
+//! source by reading only the chunks it falls in.
+
+use std::fmt::Display;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+
+use crate::constant::KeyType;
+use crate::eax::{eax_decrypt, eax_encrypt};
+use crate::gcm::{gcm_decrypt, gcm_encrypt};
+use crate::key_expansion::key_expansion;
+use crate::ocb::{ocb_decrypt, ocb_encrypt};
+use crate::pbkdf2::{derive_key, SALT_LEN};
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+
+pub const MAGIC: [u8; 8] = *b"\x89AES\r\n\x1a\n";
+pub const VERSION: u8 = 1;
+
+/// Where the KDF parameters start, after the magic, version, algorithm, mode
+/// and KDF
+const KDF_PARAMS_OFFSET: usize = MAGIC.len() + 4;
+
+pub const NONCE_PREFIX_LEN: usize = 8;
+pub const TAG_LEN: usize = 16;
+
+/// The length of the index and flag before each chunk's ciphertext
+pub const CHUNK_HEADER_LEN: usize = 5;
+
+/// The largest chunk size a header may give, so a corrupt header can't ask
+/// for an enormous buffer
+pub const MAX_CHUNK_SIZE: u32 = 1 << 24;
+
+/// A reasonable chunk size for new containers
+pub const DEFAULT_CHUNK_SIZE: u32 = 1 << 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ContainerError {
+    /// The data doesn't start with the container magic bytes
+    NotAContainer,
+    /// The container was written by a newer (or unknown) version of the format
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(u8),
+    UnknownMode(u8),
+    UnknownKdf(u8),
+    /// A header field was out of range
+    InvalidHeader(&'static str),
+    /// The data ends partway through the header or a chunk, or before the
+    /// final chunk
+    Truncated,
+    /// The chunk at `position` says it's chunk `index`
+    ChunkOutOfOrder {
+        position: u32,
+        index: u32,
+    },
+    /// More data follows the final chunk
+    TrailingData,
+    /// The chunk at this position failed authentication, so it was modified
+    /// or the key is wrong
+    AuthenticationFailed(u32),
+    /// The key's length doesn't match the header's algorithm
+    WrongKeyLength(usize),
+    /// A password was given for a container encrypted with a raw key
+    NoPassword,
+    /// The plaintext needs more chunks than a `u32` index can number
+    TooLong,
+}
+
+impl Display for ContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAContainer => write!(f, "not an encrypted container"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported container version {version}")
+            }
+            Self::UnknownAlgorithm(id) => write!(f, "unknown algorithm {id}"),
+            Self::UnknownMode(id) => write!(f, "unknown mode {id}"),
+            Self::UnknownKdf(id) => write!(f, "unknown key derivation function {id}"),
+            Self::InvalidHeader(message) => write!(f, "invalid header: {message}"),
+            Self::Truncated => write!(f, "the container is truncated"),
+            Self::ChunkOutOfOrder { position, index } => {
+                write!(f, "chunk {index} was found at position {position}")
+            }
+            Self::TrailingData => write!(f, "data follows the final chunk"),
+            Self::AuthenticationFailed(position) => {
+                write!(f, "chunk {position} failed authentication")
+            }
+            Self::WrongKeyLength(len) => {
+                write!(f, "a {len}-byte key doesn't match the container")
+            }
+            Self::NoPassword => write!(f, "the container was encrypted with a raw key"),
+            Self::TooLong => write!(f, "the plaintext has too many chunks"),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+impl From<ContainerError> for StreamError {
+    fn from(error: ContainerError) -> Self {
+        match error {
+            ContainerError::Truncated => Self::Truncated,
+            _ => Self::AuthenticationFailed,
+        }
+    }
+}
+
+/// The authenticated encryption mode used for each chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AeadMode {
+    Eax,
+    Ocb,
+    Gcm,
+}
+
+impl AeadMode {
+    fn id(self) -> u8 {
+        match self {
+            Self::Eax => 1,
+            Self::Ocb => 2,
+            Self::Gcm => 3,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, ContainerError> {
+        match id {
+            1 => Ok(Self::Eax),
+            2 => Ok(Self::Ocb),
+            3 => Ok(Self::Gcm),
+            _ => Err(ContainerError::UnknownMode(id)),
+        }
+    }
+
+    fn seal(
+        self,
+        header: &[u8],
+        plaintext: &[u8],
+        nonce: &[u8],
+        expanded_key: &[u32],
+        key_type: KeyType,
+    ) -> Vec<u8> {
+        // the nonce and tag lengths are fixed, and chunks are well under
+        // GCM's limit, so no mode can fail
+        match self {
+            Self::Eax => eax_encrypt(header, plaintext, nonce, TAG_LEN, expanded_key, key_type)
+                .expect("the tag length is valid"),
+            Self::Ocb => ocb_encrypt(header, plaintext, nonce, TAG_LEN, expanded_key, key_type)
+                .expect("the nonce and tag lengths are valid"),
+            Self::Gcm => gcm_encrypt(header, plaintext, nonce, TAG_LEN, expanded_key, key_type)
+                .expect("the nonce, tag and chunk lengths are valid"),
+        }
+    }
+
+    fn open(
+        self,
+        header: &[u8],
+        ciphertext: &[u8],
+        nonce: &[u8],
+        expanded_key: &[u32],
+        key_type: KeyType,
+    ) -> Option<Vec<u8>> {
+        match self {
+            Self::Eax => {
+                eax_decrypt(header, ciphertext, nonce, TAG_LEN, expanded_key, key_type).ok()
+            }
+            Self::Ocb => {
+                ocb_decrypt(header, ciphertext, nonce, TAG_LEN, expanded_key, key_type).ok()
+            }
+            Self::Gcm => {
+                gcm_decrypt(header, ciphertext, nonce, TAG_LEN, expanded_key, key_type).ok()
+            }
+        }
+    }
+}
+
+/// How the key was chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kdf {
+    /// The key was given directly
+    None,
+    /// The key was derived from a password with PBKDF2-HMAC-SHA-256
+    Pbkdf2 {
+        iterations: u32,
+        salt: [u8; SALT_LEN],
+    },
+}
+
+/// The parameters at the start of a container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub key_type: KeyType,
+    pub mode: AeadMode,
+    pub kdf: Kdf,
+    pub nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    pub chunk_size: u32,
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(match self.key_type {
+            KeyType::Aes128 => 1,
+            KeyType::Aes192 => 2,
+            KeyType::Aes256 => 3,
+        });
+        bytes.push(self.mode.id());
+
+        match self.kdf {
+            Kdf::None => bytes.push(0),
+            Kdf::Pbkdf2 { iterations, salt } => {
+                bytes.push(1);
+                bytes.extend(iterations.to_be_bytes());
+                bytes.extend(salt);
+            }
+        }
+
+        bytes.extend(self.nonce_prefix);
+        bytes.extend(self.chunk_size.to_be_bytes());
+        bytes
+    }
+
+    /// Parse the header at the start of `data`, returning it along with its
+    /// length in bytes
+    pub fn parse(data: &[u8]) -> Result<(Self, usize), ContainerError> {
+        let mut reader = Reader { data, position: 0 };
+
+        let magic = reader.take(MAGIC.len()).map_err(|_| {
+            // a prefix of the magic bytes is a truncated container
+            if MAGIC.starts_with(data) {
+                ContainerError::Truncated
+            } else {
+                ContainerError::NotAContainer
+            }
+        })?;
+        if magic != MAGIC {
+            return Err(ContainerError::NotAContainer);
+        }
+
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(ContainerError::UnsupportedVersion(version));
+        }
+
+        let key_type = match reader.byte()? {
+            1 => KeyType::Aes128,
+            2 => KeyType::Aes192,
+            3 => KeyType::Aes256,
+            id => return Err(ContainerError::UnknownAlgorithm(id)),
+        };
+        let mode = AeadMode::from_id(reader.byte()?)?;
+
+        let kdf = match reader.byte()? {
+            0 => Kdf::None,
+            1 => {
+                let iterations = reader.u32()?;
+                if iterations == 0 {
+                    return Err(ContainerError::InvalidHeader("the iteration count is zero"));
+                }
+                let salt = reader.take(SALT_LEN)?.try_into().unwrap();
+                Kdf::Pbkdf2 { iterations, salt }
+            }
+            id => return Err(ContainerError::UnknownKdf(id)),
+        };
+
+        let nonce_prefix = reader.take(NONCE_PREFIX_LEN)?.try_into().unwrap();
+        let chunk_size = reader.u32()?;
+        check_chunk_size(chunk_size)?;
+
+        let header = Self {
+            key_type,
+            mode,
+            kdf,
+            nonce_prefix,
+            chunk_size,
+        };
+        Ok((header, reader.position))
+    }
+
+    /// Read the header from the start of `input`, leaving `input` at the
+    /// first chunk
+    ///
+    /// Problems with the header are returned as errors of kind
+    /// [`ErrorKind::InvalidData`] wrapping a [`ContainerError`].
+    pub fn read(input: &mut impl Read) -> io::Result<Self> {
+        // the KDF's id, just before its parameters, says how long the rest is
+        let mut bytes = Vec::new();
+        input
+            .take(KDF_PARAMS_OFFSET as u64)
+            .read_to_end(&mut bytes)?;
+        let kdf_params_len = match bytes.get(KDF_PARAMS_OFFSET - 1) {
+            Some(1) => 4 + SALT_LEN,
+            _ => 0,
+        };
+        let rest_len = kdf_params_len + NONCE_PREFIX_LEN + 4;
+        input.take(rest_len as u64).read_to_end(&mut bytes)?;
+
+        Self::parse(&bytes)
+            .map(|(header, _)| header)
+            .map_err(to_io_error)
+    }
+
+    /// Derive the key from `password`, for a container whose KDF is PBKDF2
+    pub fn derive_key(&self, password: &[u8]) -> Result<Vec<u8>, ContainerError> {
+        match self.kdf {
+            Kdf::None => Err(ContainerError::NoPassword),
+            Kdf::Pbkdf2 { iterations, salt } => {
+                Ok(derive_key(password, &salt, iterations, self.key_type)
+                    .expect("the iteration count isn't zero"))
+            }
+        }
+    }
+
+    /// The nonce for the chunk at `index`
+    fn nonce(&self, index: u32, last: bool) -> [u8; NONCE_PREFIX_LEN + CHUNK_HEADER_LEN] {
+        let mut nonce = [0; NONCE_PREFIX_LEN + CHUNK_HEADER_LEN];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..].copy_from_slice(&chunk_header(index, last));
+        nonce
+    }
+
+    fn expand_key(&self, key: &[u8]) -> Result<Vec<u32>, ContainerError> {
+        if key.len() != self.key_type.n_k() * 4 {
+            return Err(ContainerError::WrongKeyLength(key.len()));
+        }
+        Ok(key_expansion(key, self.key_type))
+    }
+}
+
+/// Encrypt `plaintext` under `key` into a container described by `header`
+pub fn seal(header: &Header, plaintext: &[u8], key: &[u8]) -> Result<Vec<u8>, ContainerError> {
+    let chunks = Chunks::new(header, key)?;
+
+    // an empty plaintext is still one (empty) final chunk
+    let pieces: Vec<&[u8]> = if plaintext.is_empty() {
+        vec![&[]]
+    } else {
+        plaintext.chunks(header.chunk_size as usize).collect()
+    };
+    if u32::try_from(pieces.len()).is_err() {
+        return Err(ContainerError::TooLong);
+    }
+
+    let mut result = chunks.header_bytes.clone();
+    for (index, piece) in pieces.iter().enumerate() {
+        let last = index == pieces.len() - 1;
+        result.extend(chunks.seal(index as u32, last, piece));
+    }
+
+    Ok(result)
+}
+
+/// Check and decrypt the container in `data` with `key`
+pub fn open(data: &[u8], key: &[u8]) -> Result<Vec<u8>, ContainerError> {
+    let (header, header_len) = Header::parse(data)?;
+    let chunks = Chunks::new(&header, key)?;
+
+    let mut reader = Reader {
+        data,
+        position: header_len,
+    };
+    let mut plaintext = Vec::new();
+
+    for position in 0..=u32::MAX {
+        if reader.remaining() == 0 {
+            return Err(ContainerError::Truncated);
+        }
+
+        let record = reader.take(reader.remaining().min(chunks.record_len()))?;
+        let (chunk, last) = chunks.open(position, record)?;
+        plaintext.extend(chunk);
+
+        if last {
+            if reader.remaining() > 0 {
+                return Err(ContainerError::TrailingData);
+            }
+            return Ok(plaintext);
+        }
+    }
+
+    // a u32 index can't number any more chunks than this
+    Err(ContainerError::TrailingData)
+}
+
+fn check_chunk_size(chunk_size: u32) -> Result<(), ContainerError> {
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(ContainerError::InvalidHeader(
+            "the chunk size is out of range",
+        ));
+    }
+    Ok(())
+}
+
+/// The index and flag before a chunk's ciphertext
+fn chunk_header(index: u32, last: bool) -> [u8; CHUNK_HEADER_LEN] {
+    let mut bytes = [0; CHUNK_HEADER_LEN];
+    bytes[..4].copy_from_slice(&index.to_be_bytes());
+    bytes[4] = last as u8;
+    bytes
+}
+
+/// A checked header with its key expanded, for sealing and opening its chunks
+struct Chunks {
+    header: Header,
+    /// The associated data for every chunk
+    header_bytes: Vec<u8>,
+    expanded_key: Vec<u32>,
+}
+
+impl Chunks {
+    fn new(header: &Header, key: &[u8]) -> Result<Self, ContainerError> {
+        // a container that `open` would reject shouldn't be written at all
+        check_chunk_size(header.chunk_size)?;
+
+        Ok(Self {
+            header: header.clone(),
+            header_bytes: header.to_bytes(),
+            expanded_key: header.expand_key(key)?,
+        })
+    }
+
+    fn chunk_size(&self) -> usize {
+        self.header.chunk_size as usize
+    }
+
+    /// The length of every chunk but the last, with its index, flag and tag
+    fn record_len(&self) -> usize {
+        CHUNK_HEADER_LEN + self.chunk_size() + TAG_LEN
+    }
+
+    /// Encrypt the chunk at `index`, with its index and flag in front
+    fn seal(&self, index: u32, last: bool, plaintext: &[u8]) -> Vec<u8> {
+        let mut record = chunk_header(index, last).to_vec();
+        record.extend(self.header.mode.seal(
+            &self.header_bytes,
+            plaintext,
+            &self.header.nonce(index, last),
+            &self.expanded_key,
+            self.header.key_type,
+        ));
+        record
+    }
+
+    /// Check and decrypt `record`, which is at most [`Self::record_len`]
+    /// bytes found at `position`, returning the plaintext and whether it's
+    /// the final chunk
+    fn open(&self, position: u32, record: &[u8]) -> Result<(Vec<u8>, bool), ContainerError> {
+        if record.len() < CHUNK_HEADER_LEN {
+            return Err(ContainerError::Truncated);
+        }
+        let (chunk_header, ciphertext) = record.split_at(CHUNK_HEADER_LEN);
+
+        let index = u32::from_be_bytes(chunk_header[..4].try_into().unwrap());
+        let last = match chunk_header[4] {
+            0 => false,
+            1 => true,
+            // a flag that was never written can only come from tampering
+            _ => return Err(ContainerError::AuthenticationFailed(position)),
+        };
+        if index != position {
+            return Err(ContainerError::ChunkOutOfOrder { position, index });
+        }
+
+        // only the final chunk may be short, and even then it has a tag
+        if (!last && record.len() < self.record_len()) || ciphertext.len() < TAG_LEN {
+            return Err(ContainerError::Truncated);
+        }
+
+        let plaintext = self
+            .header
+            .mode
+            .open(
+                &self.header_bytes,
+                ciphertext,
+                &self.header.nonce(index, last),
+                &self.expanded_key,
+                self.header.key_type,
+            )
+            .ok_or(ContainerError::AuthenticationFailed(position))?;
+        Ok((plaintext, last))
+    }
+}
+
+/// Incremental encryption of a container's chunks
+///
+/// The output is only the chunks, so the header's bytes must be written
+/// before it. A chunk is only encrypted once input past it arrives, since
+/// until then it might be the final one, and [`Encryptor::finalize`] encrypts
+/// what's left (which may be nothing) as the final chunk.
+///
+/// # Panics
+///
+/// If the plaintext needs more chunks than a `u32` index can number.
+pub struct ContainerEncryptor {
+    chunks: Chunks,
+    next_index: u64,
+    buffer: Buffer,
+}
+
+impl ContainerEncryptor {
+    pub fn new(header: &Header, key: &[u8]) -> Result<Self, ContainerError> {
+        Ok(Self {
+            chunks: Chunks::new(header, key)?,
+            next_index: 0,
+            buffer: Buffer::default(),
+        })
+    }
+
+    fn next_index(&self) -> u32 {
+        u32::try_from(self.next_index).expect("a container holds at most 2^32 chunks")
+    }
+}
+
+impl Encryptor for ContainerEncryptor {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let chunk_size = self.chunks.chunk_size();
+        let ready = self.buffer.take(plaintext, chunk_size, 1);
+
+        let mut ciphertext =
+            Vec::with_capacity(ready.len() / chunk_size * self.chunks.record_len());
+        for chunk in ready.chunks(chunk_size) {
+            ciphertext.extend(self.chunks.seal(self.next_index(), false, chunk));
+            self.next_index += 1;
+        }
+        ciphertext
+    }
+
+    fn finalize(self) -> Result<Vec<u8>, StreamError> {
+        let index = self.next_index();
+        Ok(self.chunks.seal(index, true, &self.buffer.remaining()))
+    }
+}
+
+/// Incremental decryption of a container's chunks
+///
+/// The input is only the chunks, so the header must already have been read
+/// (with [`Header::read`], say). Each chunk is returned only once it has been
+/// authenticated, but the input may still turn out to be truncated, so the
+/// output is only complete once [`Decryptor::finalize`] succeeds. After a
+/// chunk fails, the rest of the input is ignored and `finalize` returns the
+/// error, which [`ContainerDecryptor::error`] gives in full.
+pub struct ContainerDecryptor {
+    chunks: Chunks,
+    next_position: u64,
+    buffer: Buffer,
+    finished: bool,
+    error: Option<ContainerError>,
+}
+
+impl ContainerDecryptor {
+    pub fn new(header: &Header, key: &[u8]) -> Result<Self, ContainerError> {
+        Ok(Self {
+            chunks: Chunks::new(header, key)?,
+            next_position: 0,
+            buffer: Buffer::default(),
+            finished: false,
+            error: None,
+        })
+    }
+
+    /// The problem with the input so far, if there is one
+    pub fn error(&self) -> Option<&ContainerError> {
+        self.error.as_ref()
+    }
+
+    fn open(&mut self, record: &[u8]) -> Result<Vec<u8>, ContainerError> {
+        if self.finished {
+            return Err(ContainerError::TrailingData);
+        }
+        // a u32 index can't number a chunk past this
+        let position =
+            u32::try_from(self.next_position).map_err(|_| ContainerError::TrailingData)?;
+
+        let (plaintext, last) = self.chunks.open(position, record)?;
+        self.next_position += 1;
+        self.finished = last;
+        Ok(plaintext)
+    }
+}
+
+impl Decryptor for ContainerDecryptor {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        if self.error.is_some() {
+            return Vec::new();
+        }
+
+        // whole records can be opened as they arrive, since each one's flag
+        // says whether it's the last
+        let record_len = self.chunks.record_len();
+        let ready = self.buffer.take(ciphertext, record_len, 0);
+
+        let mut plaintext = Vec::with_capacity(ready.len());
+        for record in ready.chunks(record_len) {
+            match self.open(record) {
+                Ok(chunk) => plaintext.extend(chunk),
+                Err(error) => {
+                    self.error = Some(error);
+                    break;
+                }
+            }
+        }
+        plaintext
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        if let Some(error) = self.error {
+            return Err(error.into());
+        }
+
+        let record = std::mem::take(&mut self.buffer).remaining();
+        match (self.finished, record.is_empty()) {
+            (true, true) => Ok(Vec::new()),
+            (false, true) => Err(StreamError::Truncated),
+            (_, false) => match self.open(&record)? {
+                plaintext if self.finished => Ok(plaintext),
+                _ => Err(StreamError::Truncated),
+            },
+        }
+    }
+}
+
+/// Random-access decryption of a container in a seekable source
+///
+/// The chunks run from wherever `inner` is positioned when the reader is made
+/// (just after the header, as [`Header::read`] leaves it) to the end of
+/// `inner`. Their number is worked out from the length of the data, which is
+/// only authenticated when the final chunk is read: a container cut short at
+/// a chunk boundary is reported as [`ContainerError::Truncated`] by a read
+/// that reaches the end, but not by reads before it.
+///
+/// Problems with the container itself are returned as errors of kind
+/// [`ErrorKind::InvalidData`] wrapping a [`ContainerError`].
+pub struct ContainerReader<R: Read + Seek> {
+    inner: R,
+    chunks: Chunks,
+    /// The position of the first chunk in `inner`
+    start: u64,
+    data_len: u64,
+    chunk_count: u64,
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    pub fn new(mut inner: R, header: &Header, key: &[u8]) -> io::Result<Self> {
+        let chunks = Chunks::new(header, key).map_err(to_io_error)?;
+
+        let start = inner.stream_position()?;
+        let data_len = inner.seek(SeekFrom::End(0))?.saturating_sub(start);
+
+        // every chunk, including the last, has an index, flag and tag
+        let record_len = chunks.record_len() as u64;
+        let chunk_count = data_len.div_ceil(record_len).max(1);
+        let last_len = data_len - (chunk_count - 1) * record_len;
+        if last_len < (CHUNK_HEADER_LEN + TAG_LEN) as u64 {
+            return Err(to_io_error(ContainerError::Truncated));
+        }
+        if chunk_count > 1 << 32 {
+            return Err(to_io_error(ContainerError::TrailingData));
+        }
+
+        Ok(Self {
+            inner,
+            chunks,
+            start,
+            data_len,
+            chunk_count,
+        })
+    }
+
+    /// The length of the plaintext
+    pub fn len(&self) -> u64 {
+        self.data_len - self.chunk_count * (CHUNK_HEADER_LEN + TAG_LEN) as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decrypt up to `len` bytes of plaintext starting at `offset`, reading
+    /// and authenticating only the chunks they're in
+    ///
+    /// As with `pread`, the result is shorter than `len` if it would go past
+    /// the end, and empty if `offset` is at or past the end.
+    pub fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let end = offset.saturating_add(len as u64).min(self.len());
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let chunk_size = self.chunks.chunk_size() as u64;
+        let first = offset / chunk_size;
+        let mut plaintext = Vec::new();
+        for position in first..=(end - 1) / chunk_size {
+            plaintext.extend(self.read_chunk(position)?);
+        }
+
+        let skip = (offset - first * chunk_size) as usize;
+        plaintext.drain(..skip);
+        plaintext.truncate((end - offset) as usize);
+        Ok(plaintext)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_chunk(&mut self, position: u64) -> io::Result<Vec<u8>> {
+        let record_len = self.chunks.record_len() as u64;
+        let record_start = position * record_len;
+        let mut record = vec![0; (self.data_len - record_start).min(record_len) as usize];
+        self.inner
+            .seek(SeekFrom::Start(self.start + record_start))?;
+        self.inner.read_exact(&mut record)?;
+
+        // `new` checked there are at most 2^32 chunks
+        let (plaintext, last) = self
+            .chunks
+            .open(position as u32, &record)
+            .map_err(to_io_error)?;
+
+        let final_position = position == self.chunk_count - 1;
+        match (last, final_position) {
+            (true, false) => Err(to_io_error(ContainerError::TrailingData)),
+            (false, true) => Err(to_io_error(ContainerError::Truncated)),
+            _ => Ok(plaintext),
+        }
+    }
+}
+
+fn to_io_error(error: ContainerError) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// A cursor over `data` that reports running out as truncation
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ContainerError> {
+        if self.remaining() < len {
+            return Err(ContainerError::Truncated);
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, ContainerError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ContainerError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::encode_hex;
+    use crate::stream::check_streaming;
+    use std::io::Cursor;
+
+    const KEY: [u8; 32] = [0x42; 32];
+    const MODES: [AeadMode; 3] = [AeadMode::Eax, AeadMode::Ocb, AeadMode::Gcm];
+
+    fn header(mode: AeadMode, chunk_size: u32) -> Header {
+        Header {
+            key_type: KeyType::Aes256,
+            mode,
+            kdf: Kdf::None,
+            nonce_prefix: [1, 2, 3, 4, 5, 6, 7, 8],
+            chunk_size,
+        }
+    }
+
+    /// The offset of the chunk at `position` in a container of `header`
+    fn chunk_offset(header: &Header, position: usize) -> usize {
+        header.to_bytes().len()
+            + position * (CHUNK_HEADER_LEN + header.chunk_size as usize + TAG_LEN)
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for mode in MODES {
+            let header = header(mode, 16);
+
+            for len in [0usize, 1, 15, 16, 17, 32, 100] {
+                let plaintext: Vec<u8> = (0..len as u8).collect();
+                let sealed = seal(&header, &plaintext, &KEY).unwrap();
+
+                let chunks = len.div_ceil(16).max(1);
+                assert_eq!(
+                    sealed.len(),
+                    header.to_bytes().len() + len + chunks * (CHUNK_HEADER_LEN + TAG_LEN)
+                );
+                assert_eq!(open(&sealed, &KEY).unwrap(), plaintext);
+            }
+        }
+    }
+
+    #[test]
+    fn gcm_test() {
+        // every chunk is plain GCM under its own 13-byte nonce, with the
+        // header as the associated data
+        let header = header(AeadMode::Gcm, 16);
+        let header_bytes = header.to_bytes();
+        let expanded_key = key_expansion(&KEY, KeyType::Aes256);
+        let plaintext: Vec<u8> = (0..40).collect();
+        let sealed = seal(&header, &plaintext, &KEY).unwrap();
+
+        for (index, chunk) in plaintext.chunks(16).enumerate() {
+            let last = index == 2;
+            let nonce = header.nonce(index as u32, last);
+            let expected = gcm_encrypt(
+                &header_bytes,
+                chunk,
+                &nonce,
+                TAG_LEN,
+                &expanded_key,
+                KeyType::Aes256,
+            );
+
+            let start = chunk_offset(&header, index);
+            let end = (start + CHUNK_HEADER_LEN + 16 + TAG_LEN).min(sealed.len());
+            assert_eq!(sealed[start..start + CHUNK_HEADER_LEN], nonce[8..]);
+            assert_eq!(sealed[start + CHUNK_HEADER_LEN..end], expected.unwrap());
+        }
+
+        // checked against pyca/cryptography's AESGCM, which takes the same
+        // 13-byte nonce
+        let sealed = seal(&header, b"hello", &KEY).unwrap();
+        assert_eq!(
+            encode_hex(&sealed[header_bytes.len()..]),
+            "00000000011c3e1ac035d0e9a6134121bb0b180752547778418d"
+        );
+    }
+
+    #[test]
+    fn header_test() {
+        let mut header = header(AeadMode::Ocb, DEFAULT_CHUNK_SIZE);
+        header.key_type = KeyType::Aes192;
+        header.kdf = Kdf::Pbkdf2 {
+            iterations: 1000,
+            salt: [0x5a; SALT_LEN],
+        };
+
+        let bytes = header.to_bytes();
+        assert_eq!(bytes[..12], *b"\x89AES\r\n\x1a\n\x01\x02\x02\x01");
+        assert_eq!(bytes.len(), 8 + 4 + 20 + 8 + 4);
+        assert_eq!(Header::parse(&bytes), Ok((header.clone(), bytes.len())));
+
+        let key = header.derive_key(b"password").unwrap();
+        let sealed = seal(&header, b"hello", &key).unwrap();
+        assert_eq!(open(&sealed, &key).unwrap(), b"hello");
+
+        let raw = self::header(AeadMode::Eax, 16);
+        assert_eq!(raw.derive_key(b"password"), Err(ContainerError::NoPassword));
+    }
+
+    #[test]
+    fn invalid_header_test() {
+        let bytes = header(AeadMode::Eax, 16).to_bytes();
+        let with = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            Header::parse(&bytes)
+        };
+
+        assert_eq!(with(0, b'x'), Err(ContainerError::NotAContainer));
+        assert_eq!(with(8, 2), Err(ContainerError::UnsupportedVersion(2)));
+        assert_eq!(with(9, 4), Err(ContainerError::UnknownAlgorithm(4)));
+        assert_eq!(with(10, 0), Err(ContainerError::UnknownMode(0)));
+        assert_eq!(with(11, 7), Err(ContainerError::UnknownKdf(7)));
+        assert!(matches!(
+            with(bytes.len() - 4, 0xff),
+            Err(ContainerError::InvalidHeader(_))
+        ));
+
+        for chunk_size in [0, MAX_CHUNK_SIZE + 1] {
+            assert_eq!(
+                seal(&header(AeadMode::Eax, chunk_size), b"hello", &KEY),
+                Err(ContainerError::InvalidHeader(
+                    "the chunk size is out of range"
+                ))
+            );
+        }
+        assert!(seal(&header(AeadMode::Eax, MAX_CHUNK_SIZE), b"hello", &KEY).is_ok());
+
+        assert_eq!(
+            Header::parse(b"plain text"),
+            Err(ContainerError::NotAContainer)
+        );
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Header::parse(&bytes[..len]),
+                Err(ContainerError::Truncated),
+                "{len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn truncation_test() {
+        let header = header(AeadMode::Eax, 16);
+        let sealed = seal(&header, &[0x33; 40], &KEY).unwrap();
+
+        // cutting at a chunk boundary leaves no final chunk
+        for position in 1..3 {
+            let cut = chunk_offset(&header, position);
+            assert_eq!(open(&sealed[..cut], &KEY), Err(ContainerError::Truncated));
+        }
+        // and cutting inside a chunk leaves it short
+        for cut in chunk_offset(&header, 0)..sealed.len() {
+            assert!(open(&sealed[..cut], &KEY).is_err(), "cut at {cut}");
+        }
+
+        // dropping the final chunk's flag to pretend there's more doesn't work
+        let mut unflagged = sealed.clone();
+        unflagged[chunk_offset(&header, 2) + 4] = 0;
+        assert_eq!(open(&unflagged, &KEY), Err(ContainerError::Truncated));
+
+        // and neither does flagging an earlier chunk as the final one
+        let mut early_end = sealed[..chunk_offset(&header, 2)].to_vec();
+        early_end[chunk_offset(&header, 1) + 4] = 1;
+        assert_eq!(
+            open(&early_end, &KEY),
+            Err(ContainerError::AuthenticationFailed(1))
+        );
+    }
+
+    #[test]
+    fn reordering_test() {
+        let header = header(AeadMode::Ocb, 16);
+        let sealed = seal(&header, &[0x33; 40], &KEY).unwrap();
+        let chunk_len = CHUNK_HEADER_LEN + 16 + TAG_LEN;
+
+        let mut swapped = sealed.clone();
+        let (first, second) = (chunk_offset(&header, 0), chunk_offset(&header, 1));
+        swapped[first..first + chunk_len].copy_from_slice(&sealed[second..second + chunk_len]);
+        swapped[second..second + chunk_len].copy_from_slice(&sealed[first..first + chunk_len]);
+        assert_eq!(
+            open(&swapped, &KEY),
+            Err(ContainerError::ChunkOutOfOrder {
+                position: 0,
+                index: 1
+            })
+        );
+
+        // relabelling the swapped chunks makes them fail authentication instead
+        swapped[first + 3] = 0;
+        swapped[second + 3] = 1;
+        assert_eq!(
+            open(&swapped, &KEY),
+            Err(ContainerError::AuthenticationFailed(0))
+        );
+
+        let mut duplicated = sealed[..chunk_offset(&header, 2)].to_vec();
+        duplicated.extend_from_slice(&sealed[second..]);
+        assert_eq!(
+            open(&duplicated, &KEY),
+            Err(ContainerError::ChunkOutOfOrder {
+                position: 2,
+                index: 1
+            })
+        );
+    }
+
+    #[test]
+    fn tampering_test() {
+        let header = header(AeadMode::Eax, 16);
+        let sealed = seal(&header, &[0x33; 40], &KEY).unwrap();
+
+        // a short final chunk can't tell extra bytes from its own, but a full
+        // one can
+        let mut appended = sealed.clone();
+        appended.push(0);
+        assert_eq!(
+            open(&appended, &KEY),
+            Err(ContainerError::AuthenticationFailed(2))
+        );
+        let mut appended = seal(&header, &[0x33; 48], &KEY).unwrap();
+        appended.push(0);
+        assert_eq!(open(&appended, &KEY), Err(ContainerError::TrailingData));
+
+        let mut flipped = sealed.clone();
+        flipped[chunk_offset(&header, 1) + CHUNK_HEADER_LEN] ^= 1;
+        assert_eq!(
+            open(&flipped, &KEY),
+            Err(ContainerError::AuthenticationFailed(1))
+        );
+
+        // the header is authenticated with every chunk
+        let mut other_prefix = sealed.clone();
+        other_prefix[12] ^= 1;
+        assert_eq!(
+            open(&other_prefix, &KEY),
+            Err(ContainerError::AuthenticationFailed(0))
+        );
+
+        assert_eq!(
+            open(&sealed, &[0x43; 32]),
+            Err(ContainerError::AuthenticationFailed(0))
+        );
+        assert_eq!(
+            open(&sealed, &[0x42; 16]),
+            Err(ContainerError::WrongKeyLength(16))
+        );
+    }
+
+    #[test]
+    fn read_test() {
+        let mut header = header(AeadMode::Gcm, 16);
+        for kdf in [
+            Kdf::None,
+            Kdf::Pbkdf2 {
+                iterations: 1000,
+                salt: [0x5a; SALT_LEN],
+            },
+        ] {
+            header.kdf = kdf;
+            let sealed = seal(&header, &[0x33; 40], &KEY).unwrap();
+
+            // reading stops at the first chunk
+            let mut input = Cursor::new(&sealed);
+            assert_eq!(Header::read(&mut input).unwrap(), header);
+            assert_eq!(input.position() as usize, header.to_bytes().len());
+        }
+
+        let read_error = |bytes: &[u8]| {
+            let error = Header::read(&mut &bytes[..]).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+            container_error(error)
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(read_error(&bytes[..20]), ContainerError::Truncated);
+        assert_eq!(read_error(b"plain text"), ContainerError::NotAContainer);
+        assert_eq!(read_error(b""), ContainerError::Truncated);
+    }
+
+    fn container_error(error: io::Error) -> ContainerError {
+        *error.into_inner().unwrap().downcast().unwrap()
+    }
+
+    #[test]
+    fn streaming_test() {
+        for mode in MODES {
+            let header = header(mode, 16);
+            let header_len = header.to_bytes().len();
+
+            for len in [0, 15, 16, 40, 100] {
+                let plaintext: Vec<u8> = (0..len as u8).collect();
+                let sealed = seal(&header, &plaintext, &KEY).unwrap();
+                check_streaming(
+                    &plaintext,
+                    &sealed[header_len..],
+                    || ContainerEncryptor::new(&header, &KEY).unwrap(),
+                    || ContainerDecryptor::new(&header, &KEY).unwrap(),
+                );
+            }
+        }
+
+        assert_eq!(
+            ContainerEncryptor::new(&header(AeadMode::Gcm, 0), &KEY).err(),
+            Some(ContainerError::InvalidHeader(
+                "the chunk size is out of range"
+            ))
+        );
+        assert_eq!(
+            ContainerDecryptor::new(&header(AeadMode::Gcm, 16), &[0x42; 16]).err(),
+            Some(ContainerError::WrongKeyLength(16))
+        );
+    }
+
+    #[test]
+    fn streaming_failure_test() {
+        let header = header(AeadMode::Gcm, 64);
+        let sealed = seal(&header, &[0x42; 200], &KEY).unwrap();
+        let chunks = &sealed[header.to_bytes().len()..];
+        let record_len = CHUNK_HEADER_LEN + 64 + TAG_LEN;
+        let decryptor = || ContainerDecryptor::new(&header, &KEY).unwrap();
+
+        // chunks before the damage are released, and nothing after it
+        let mut forged = chunks.to_vec();
+        forged[record_len + CHUNK_HEADER_LEN + 1] ^= 1;
+        let mut decryptor_1 = decryptor();
+        assert_eq!(decryptor_1.update(&forged), [0x42; 64]);
+        assert_eq!(
+            decryptor_1.error(),
+            Some(&ContainerError::AuthenticationFailed(1))
+        );
+        assert_eq!(
+            decryptor_1.finalize(),
+            Err(StreamError::AuthenticationFailed)
+        );
+
+        // dropping whole chunks from the end is caught by the final flag
+        let mut decryptor_2 = decryptor();
+        assert_eq!(decryptor_2.update(&chunks[..2 * record_len]).len(), 128);
+        assert_eq!(decryptor_2.finalize(), Err(StreamError::Truncated));
+
+        let mut decryptor_3 = decryptor();
+        assert_eq!(decryptor_3.update(&chunks[..10]), []);
+        assert_eq!(decryptor_3.finalize(), Err(StreamError::Truncated));
+
+        // as is anything after the final chunk, even a whole one
+        let full = seal(&header, &[0x42; 128], &KEY).unwrap();
+        let mut extended = full[header.to_bytes().len()..].to_vec();
+        extended.extend(&chunks[..record_len]);
+        let mut decryptor_4 = decryptor();
+        assert_eq!(decryptor_4.update(&extended).len(), 128);
+        assert_eq!(decryptor_4.error(), Some(&ContainerError::TrailingData));
+        assert_eq!(
+            decryptor_4.finalize(),
+            Err(StreamError::AuthenticationFailed)
+        );
+    }
+
+    /// A reader over the chunks of `sealed`, positioned as [`Header::read`]
+    /// leaves it
+    fn reader(sealed: Vec<u8>, header: &Header) -> io::Result<ContainerReader<Cursor<Vec<u8>>>> {
+        let mut cursor = Cursor::new(sealed);
+        cursor.set_position(header.to_bytes().len() as u64);
+        ContainerReader::new(cursor, header, &KEY)
+    }
+
+    #[test]
+    fn read_at_test() {
+        let plaintext: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for mode in MODES {
+            let header = header(mode, 64);
+
+            for len in [0, 1, 64, 640, 1000] {
+                let sealed = seal(&header, &plaintext[..len], &KEY).unwrap();
+                let mut reader = reader(sealed, &header).unwrap();
+                assert_eq!(reader.len(), len as u64);
+
+                for (offset, read_len) in
+                    [(0, 1), (0, 64), (10, 100), (63, 2), (128, 64), (990, 50)]
+                {
+                    let start = offset.min(len);
+                    let end = (offset + read_len).min(len);
+                    assert_eq!(
+                        reader.read_at(offset as u64, read_len).unwrap(),
+                        plaintext[start..end],
+                        "length {len}, offset {offset}, read length {read_len}"
+                    );
+                }
+                assert_eq!(reader.read_at(len as u64 + 1, 10).unwrap(), []);
+                assert_eq!(reader.read_at(u64::MAX, usize::MAX).unwrap(), []);
+            }
+        }
+    }
+
+    #[test]
+    fn read_at_failure_test() {
+        let header = header(AeadMode::Gcm, 64);
+        let sealed = seal(&header, &[0x42; 300], &KEY).unwrap();
+
+        // only the chunks that are read are checked
+        let mut forged = sealed.clone();
+        forged[chunk_offset(&header, 2) + CHUNK_HEADER_LEN + 5] ^= 1;
+        let mut reader_1 = reader(forged, &header).unwrap();
+        assert_eq!(reader_1.read_at(0, 128).unwrap(), [0x42; 128]);
+        assert_eq!(reader_1.read_at(192, 10).unwrap(), [0x42; 10]);
+        let error = reader_1.read_at(100, 50).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            container_error(error),
+            ContainerError::AuthenticationFailed(2)
+        );
+
+        // a truncated container is caught when reading what looks like its
+        // end, and so is data after the final chunk
+        let mut reader_2 = reader(sealed[..chunk_offset(&header, 3)].to_vec(), &header).unwrap();
+        assert_eq!(reader_2.len(), 192);
+        assert_eq!(reader_2.read_at(0, 64).unwrap(), [0x42; 64]);
+        assert_eq!(
+            container_error(reader_2.read_at(150, 64).unwrap_err()),
+            ContainerError::Truncated
+        );
+
+        let mut extended = seal(&header, &[0x42; 128], &KEY).unwrap();
+        extended.extend_from_slice(&sealed[chunk_offset(&header, 2)..chunk_offset(&header, 3)]);
+        let mut reader_3 = reader(extended, &header).unwrap();
+        assert_eq!(
+            container_error(reader_3.read_at(64, 64).unwrap_err()),
+            ContainerError::TrailingData
+        );
+
+        // every chunk has at least an index, flag and tag
+        for len in [0, 5, CHUNK_HEADER_LEN + 64 + TAG_LEN + 20] {
+            let cut = chunk_offset(&header, 0) + len;
+            assert_eq!(
+                container_error(reader(sealed[..cut].to_vec(), &header).err().unwrap()),
+                ContainerError::Truncated
+            );
+        }
+    }
+}
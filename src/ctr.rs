@@ -0,0 +1,266 @@
+//! Counter (CTR) mode (NIST SP 800-38A)
+//!
+//! The counter block is incremented as a single 128-bit big-endian integer, so
+//! encryption and decryption are the same operation.
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::stream::{Decryptor, Encryptor, StreamError};
+use crate::util::{process_parallel, xor_in_place};
+
+/// Encrypt `input` with the keystream that starts at `initial_counter`
+pub fn ctr_encrypt(
+    input: &[u8],
+    initial_counter: &[u8; 16],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    let mut result = input.to_vec();
+    let mut counter = *initial_counter;
+
+    for chunk in result.chunks_mut(16) {
+        xor_in_place(chunk, &cipher_block(&counter, expanded_key, key_type));
+        counter = increment(&counter);
+    }
+
+    result
+}
+
+/// Decrypt `input`, which is the same operation as encrypting it
+pub fn ctr_decrypt(
+    input: &[u8],
+    initial_counter: &[u8; 16],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    ctr_encrypt(input, initial_counter, expanded_key, key_type)
+}
+
+/// Encrypt `input` like [`ctr_encrypt`], splitting it between up to `threads`
+/// threads
+///
+/// Each thread starts its keystream at the counter block its first block
+/// would have reached, so the output is the same as the serial function's.
+pub fn ctr_encrypt_parallel(
+    input: &[u8],
+    initial_counter: &[u8; 16],
+    threads: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    let initial = u128::from_be_bytes(*initial_counter);
+    let mut result = input.to_vec();
+
+    process_parallel(&mut result, 16, threads, |first_block, run| {
+        let counter = initial.wrapping_add(first_block as u128).to_be_bytes();
+        let output = ctr_encrypt(run, &counter, expanded_key, key_type);
+        run.copy_from_slice(&output);
+    });
+
+    result
+}
+
+/// Decrypt `input` like [`ctr_decrypt`], splitting it between up to `threads`
+/// threads
+pub fn ctr_decrypt_parallel(
+    input: &[u8],
+    initial_counter: &[u8; 16],
+    threads: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    ctr_encrypt_parallel(input, initial_counter, threads, expanded_key, key_type)
+}
+
+/// Add one to the counter block, wrapping around on overflow
+pub fn increment(counter: &[u8; 16]) -> [u8; 16] {
+    u128::from_be_bytes(*counter).wrapping_add(1).to_be_bytes()
+}
+
+/// Incremental CTR encryption, which never needs to buffer input
+pub struct CtrEncryptor<'a> {
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    counter: [u8; 16],
+    keystream: [u8; 16],
+    /// How much of `keystream` has been used
+    used: usize,
+}
+
+impl<'a> CtrEncryptor<'a> {
+    pub fn new(initial_counter: &[u8; 16], expanded_key: &'a [u32], key_type: KeyType) -> Self {
+        Self {
+            expanded_key,
+            key_type,
+            counter: *initial_counter,
+            keystream: [0; 16],
+            used: 16,
+        }
+    }
+
+    /// XOR the next `input.len()` bytes of keystream into `input`
+    pub(crate) fn apply_keystream(&mut self, input: &mut [u8]) {
+        for byte in input {
+            if self.used == 16 {
+                self.keystream = cipher_block(&self.counter, self.expanded_key, self.key_type);
+                self.counter = increment(&self.counter);
+                self.used = 0;
+            }
+
+            *byte ^= self.keystream[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+impl Encryptor for CtrEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let mut result = plaintext.to_vec();
+        self.apply_keystream(&mut result);
+        result
+    }
+
+    fn finalize(self) -> Result<Vec<u8>, StreamError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Incremental CTR decryption, which is the same operation as encryption
+pub struct CtrDecryptor<'a>(CtrEncryptor<'a>);
+
+impl<'a> CtrDecryptor<'a> {
+    pub fn new(initial_counter: &[u8; 16], expanded_key: &'a [u32], key_type: KeyType) -> Self {
+        Self(CtrEncryptor::new(initial_counter, expanded_key, key_type))
+    }
+}
+
+impl Decryptor for CtrDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        self.0.update(ciphertext)
+    }
+
+    fn finalize(self) -> Result<Vec<u8>, StreamError> {
+        self.0.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::stream::check_streaming;
+
+    #[test]
+    fn increment_test() {
+        let mut counter = [0xff; 16];
+        counter[0] = 0x00;
+        let mut expected = [0x00; 16];
+        expected[0] = 0x01;
+        assert_eq!(increment(&counter), expected);
+
+        assert_eq!(increment(&[0xff; 16]), [0x00; 16]);
+    }
+
+    #[test]
+    fn ctr_aes128_test() {
+        // F.5.1 and F.5.2 from NIST SP 800-38A
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let initial_counter = [
+            0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd,
+            0xfe, 0xff,
+        ];
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac,
+            0x45, 0xaf, 0x8e, 0x51, 0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11, 0xe5, 0xfb,
+            0xc1, 0x19, 0x1a, 0x0a, 0x52, 0xef, 0xf6, 0x9f, 0x24, 0x45, 0xdf, 0x4f, 0x9b, 0x17,
+            0xad, 0x2b, 0x41, 0x7b, 0xe6, 0x6c, 0x37, 0x10,
+        ];
+        let ciphertext = [
+            0x87, 0x4d, 0x61, 0x91, 0xb6, 0x20, 0xe3, 0x26, 0x1b, 0xef, 0x68, 0x64, 0x99, 0x0d,
+            0xb6, 0xce, 0x98, 0x06, 0xf6, 0x6b, 0x79, 0x70, 0xfd, 0xff, 0x86, 0x17, 0x18, 0x7b,
+            0xb9, 0xff, 0xfd, 0xff, 0x5a, 0xe4, 0xdf, 0x3e, 0xdb, 0xd5, 0xd3, 0x5e, 0x5b, 0x4f,
+            0x09, 0x02, 0x0d, 0xb0, 0x3e, 0xab, 0x1e, 0x03, 0x1d, 0xda, 0x2f, 0xbe, 0x03, 0xd1,
+            0x79, 0x21, 0x70, 0xa0, 0xf3, 0x00, 0x9c, 0xee,
+        ];
+        let expanded_key = key_expansion(&key, KeyType::Aes128);
+
+        let result = ctr_encrypt(&plaintext, &initial_counter, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, ciphertext);
+
+        let result = ctr_decrypt(
+            &ciphertext,
+            &initial_counter,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, plaintext);
+
+        // a partial final block only uses part of the keystream
+        let result = ctr_encrypt(
+            &plaintext[..20],
+            &initial_counter,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, ciphertext[..20]);
+    }
+
+    #[test]
+    fn parallel_test() {
+        let expanded_key = key_expansion(&[0x90; 16], KeyType::Aes128);
+        // close enough to overflowing that some threads wrap around
+        let initial_counter = [0xff; 16];
+        let input: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for len in [0, 5, 16, 100, 1000] {
+            let expected = ctr_encrypt(
+                &input[..len],
+                &initial_counter,
+                &expanded_key,
+                KeyType::Aes128,
+            );
+            for threads in [0, 1, 3, 8, 100] {
+                let result = ctr_encrypt_parallel(
+                    &input[..len],
+                    &initial_counter,
+                    threads,
+                    &expanded_key,
+                    KeyType::Aes128,
+                );
+                assert_eq!(result, expected, "{len} bytes on {threads} threads");
+            }
+        }
+
+        let result = ctr_decrypt_parallel(&input, &[0; 16], 4, &expanded_key, KeyType::Aes128);
+        assert_eq!(
+            result,
+            ctr_decrypt(&input, &[0; 16], &expanded_key, KeyType::Aes128)
+        );
+    }
+
+    #[test]
+    fn streaming_test() {
+        let expanded_key = key_expansion(&[0x33; 32], KeyType::Aes256);
+        let initial_counter = [0xfe; 16];
+        let plaintext: Vec<u8> = (0..100).collect();
+        let expected = ctr_encrypt(&plaintext, &initial_counter, &expanded_key, KeyType::Aes256);
+
+        check_streaming(
+            &plaintext,
+            &expected,
+            || CtrEncryptor::new(&initial_counter, &expanded_key, KeyType::Aes256),
+            || CtrDecryptor::new(&initial_counter, &expanded_key, KeyType::Aes256),
+        );
+
+        // output is always as long as the input
+        let mut encryptor = CtrEncryptor::new(&initial_counter, &expanded_key, KeyType::Aes256);
+        for chunk in plaintext.chunks(7) {
+            assert_eq!(encryptor.update(chunk).len(), chunk.len());
+        }
+        assert_eq!(encryptor.finalize(), Ok(Vec::new()));
+    }
+}
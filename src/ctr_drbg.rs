@@ -0,0 +1,524 @@
+//! CTR_DRBG deterministic random bit generator (NIST SP 800-90A section 10.2)
+//!
+//! The generator's state is an AES key and a counter block `V`. Output is the
+//! encryption of successive counter values, after which the key and counter
+//! are replaced so earlier output can't be recovered from the new state.
+//!
+//! The caller supplies all entropy, so the same inputs always give the same
+//! output. With the derivation function, entropy and other inputs may be any
+//! length and are condensed by a CBC-MAC based function. Without it, entropy
+//! must be exactly the seed length and must already be full entropy.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::ctr::increment;
+use crate::key_expansion::key_expansion;
+use crate::util::{pad_to_block, xor_in_place};
+
+/// The most bytes a single [`CtrDrbg::generate`] call may return (2^19 bits)
+pub const MAX_REQUEST_LEN: usize = 1 << 16;
+
+/// The most requests allowed between reseeds
+pub const RESEED_INTERVAL: u64 = 1 << 48;
+
+/// The longest entropy input, personalization string or additional input the
+/// derivation function accepts, since it encodes lengths in 32 bits
+pub const MAX_INPUT_LEN: usize = u32::MAX as usize;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DrbgError {
+    /// The entropy input was too short (or, without the derivation function,
+    /// not exactly the seed length)
+    InvalidEntropyLength(usize),
+    /// A personalization string or additional input was too long
+    InputTooLong(usize),
+    /// More than [`MAX_REQUEST_LEN`] bytes were requested at once
+    RequestTooLong(usize),
+    /// The reseed interval has been reached, so [`CtrDrbg::reseed`] must be
+    /// called before generating more output
+    ReseedRequired,
+}
+
+impl Display for DrbgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidEntropyLength(len) => write!(f, "invalid entropy input length {len}"),
+            Self::InputTooLong(len) => write!(f, "input of {len} bytes is too long"),
+            Self::RequestTooLong(len) => write!(f, "request for {len} bytes is too long"),
+            Self::ReseedRequired => write!(f, "reseed required"),
+        }
+    }
+}
+
+impl std::error::Error for DrbgError {}
+
+/// An instantiation of CTR_DRBG, with or without the derivation function
+pub struct CtrDrbg {
+    key_type: KeyType,
+    derivation_function: bool,
+    expanded_key: Vec<u32>,
+    v: [u8; 16],
+    reseed_counter: u64,
+    reseed_interval: u64,
+}
+
+impl CtrDrbg {
+    /// Instantiate using the derivation function
+    ///
+    /// `entropy_input` must be at least as long as the key (the security
+    /// strength), and `nonce` should be at least half that length.
+    pub fn new(
+        key_type: KeyType,
+        entropy_input: &[u8],
+        nonce: &[u8],
+        personalization: &[u8],
+    ) -> Result<Self, DrbgError> {
+        let mut drbg = Self::empty(key_type, true);
+        drbg.check_entropy(entropy_input)?;
+        drbg.check_input(personalization)?;
+
+        let seed_material = drbg.derive(&[entropy_input, nonce, personalization])?;
+        drbg.update(&seed_material);
+        Ok(drbg)
+    }
+
+    /// Instantiate without the derivation function
+    ///
+    /// `entropy_input` must be exactly the seed length (the key length plus
+    /// 16 bytes), and `personalization` at most that long.
+    pub fn new_without_df(
+        key_type: KeyType,
+        entropy_input: &[u8],
+        personalization: &[u8],
+    ) -> Result<Self, DrbgError> {
+        let mut drbg = Self::empty(key_type, false);
+        drbg.check_entropy(entropy_input)?;
+        drbg.check_input(personalization)?;
+
+        let mut seed_material = entropy_input.to_vec();
+        xor_in_place(&mut seed_material, personalization);
+        drbg.update(&seed_material);
+        Ok(drbg)
+    }
+
+    fn empty(key_type: KeyType, derivation_function: bool) -> Self {
+        let key = vec![0; key_type.n_k() * 4];
+
+        Self {
+            key_type,
+            derivation_function,
+            expanded_key: key_expansion(&key, key_type),
+            v: [0; 16],
+            reseed_counter: 1,
+            reseed_interval: RESEED_INTERVAL,
+        }
+    }
+
+    /// Mix fresh entropy (and optional additional input) into the state
+    pub fn reseed(
+        &mut self,
+        entropy_input: &[u8],
+        additional_input: &[u8],
+    ) -> Result<(), DrbgError> {
+        self.check_entropy(entropy_input)?;
+        self.check_input(additional_input)?;
+
+        let seed_material = if self.derivation_function {
+            self.derive(&[entropy_input, additional_input])?
+        } else {
+            let mut seed_material = entropy_input.to_vec();
+            xor_in_place(&mut seed_material, additional_input);
+            seed_material
+        };
+
+        self.update(&seed_material);
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /// Fill `output` with pseudorandom bytes
+    pub fn generate(
+        &mut self,
+        output: &mut [u8],
+        additional_input: &[u8],
+    ) -> Result<(), DrbgError> {
+        if output.len() > MAX_REQUEST_LEN {
+            return Err(DrbgError::RequestTooLong(output.len()));
+        }
+        if self.reseed_counter > self.reseed_interval {
+            return Err(DrbgError::ReseedRequired);
+        }
+        self.check_input(additional_input)?;
+
+        // the (derived) additional input is used for both updates
+        let additional_input = if additional_input.is_empty() {
+            vec![0; self.seed_len()]
+        } else if self.derivation_function {
+            let additional_input = self.derive(&[additional_input])?;
+            self.update(&additional_input);
+            additional_input
+        } else {
+            let mut padded = vec![0; self.seed_len()];
+            xor_in_place(&mut padded, additional_input);
+            self.update(&padded);
+            padded
+        };
+
+        for chunk in output.chunks_mut(16) {
+            self.v = increment(&self.v);
+            let block = cipher_block(&self.v, &self.expanded_key, self.key_type);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+
+        self.update(&additional_input);
+        self.reseed_counter += 1;
+        Ok(())
+    }
+
+    /// Reseed with `entropy_input` and then generate, so the output is
+    /// unpredictable even to someone who has learned the previous state
+    pub fn generate_with_prediction_resistance(
+        &mut self,
+        output: &mut [u8],
+        entropy_input: &[u8],
+        additional_input: &[u8],
+    ) -> Result<(), DrbgError> {
+        if output.len() > MAX_REQUEST_LEN {
+            return Err(DrbgError::RequestTooLong(output.len()));
+        }
+
+        self.reseed(entropy_input, additional_input)?;
+        self.generate(output, &[])
+    }
+
+    /// The number of requests since the last (re)seed, plus one
+    pub fn reseed_counter(&self) -> u64 {
+        self.reseed_counter
+    }
+
+    /// Require a reseed after fewer requests than [`RESEED_INTERVAL`]
+    pub fn set_reseed_interval(&mut self, interval: u64) {
+        self.reseed_interval = interval.min(RESEED_INTERVAL);
+    }
+
+    /// The length of the key plus one block
+    fn seed_len(&self) -> usize {
+        self.key_type.n_k() * 4 + 16
+    }
+
+    fn check_entropy(&self, entropy_input: &[u8]) -> Result<(), DrbgError> {
+        let valid = if self.derivation_function {
+            (self.key_type.n_k() * 4..=MAX_INPUT_LEN).contains(&entropy_input.len())
+        } else {
+            entropy_input.len() == self.seed_len()
+        };
+
+        if !valid {
+            return Err(DrbgError::InvalidEntropyLength(entropy_input.len()));
+        }
+        Ok(())
+    }
+
+    fn check_input(&self, input: &[u8]) -> Result<(), DrbgError> {
+        let max_len = if self.derivation_function {
+            MAX_INPUT_LEN
+        } else {
+            self.seed_len()
+        };
+
+        if input.len() > max_len {
+            return Err(DrbgError::InputTooLong(input.len()));
+        }
+        Ok(())
+    }
+
+    /// CTR_DRBG_Update: replace the key and `V` using `provided_data`, which
+    /// is exactly the seed length
+    fn update(&mut self, provided_data: &[u8]) {
+        let mut temp = Vec::with_capacity(self.seed_len() + 16);
+        while temp.len() < self.seed_len() {
+            self.v = increment(&self.v);
+            temp.extend(cipher_block(&self.v, &self.expanded_key, self.key_type));
+        }
+        temp.truncate(self.seed_len());
+        xor_in_place(&mut temp, provided_data);
+
+        let (key, v) = temp.split_at(self.key_type.n_k() * 4);
+        self.expanded_key = key_expansion(key, self.key_type);
+        self.v = v.try_into().unwrap();
+    }
+
+    /// Block_Cipher_df over the concatenation of `inputs`, returning the seed
+    /// length in bytes
+    ///
+    /// The combined length is encoded in 32 bits, so inputs that are each
+    /// within [`MAX_INPUT_LEN`] can still be too long together.
+    fn derive(&self, inputs: &[&[u8]]) -> Result<Vec<u8>, DrbgError> {
+        let key_len = self.key_type.n_k() * 4;
+        let input_len: usize = inputs.iter().map(|input| input.len()).sum();
+        let encoded_len =
+            u32::try_from(input_len).map_err(|_| DrbgError::InputTooLong(input_len))?;
+
+        let mut s = Vec::with_capacity(input_len + 25);
+        s.extend(encoded_len.to_be_bytes());
+        s.extend((self.seed_len() as u32).to_be_bytes());
+        for input in inputs {
+            s.extend(*input);
+        }
+        s.push(0x80);
+        pad_to_block(&mut s);
+
+        let key: Vec<u8> = (0..key_len as u8).collect();
+        let expanded_key = key_expansion(&key, self.key_type);
+
+        let mut temp = Vec::with_capacity(key_len + 32);
+        let mut i = 0u32;
+        while temp.len() < key_len + 16 {
+            let mut iv = [0; 16];
+            iv[..4].copy_from_slice(&i.to_be_bytes());
+            temp.extend(bcc(&iv, &s, &expanded_key, self.key_type));
+            i += 1;
+        }
+
+        let expanded_key = key_expansion(&temp[..key_len], self.key_type);
+        let mut x: [u8; 16] = temp[key_len..key_len + 16].try_into().unwrap();
+
+        let mut result = Vec::with_capacity(self.seed_len() + 16);
+        while result.len() < self.seed_len() {
+            x = cipher_block(&x, &expanded_key, self.key_type);
+            result.extend(x);
+        }
+        result.truncate(self.seed_len());
+        Ok(result)
+    }
+}
+
+/// The CBC-MAC of the block `iv` followed by `data`, which is a whole number
+/// of blocks
+fn bcc(iv: &[u8; 16], data: &[u8], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let mut chaining_value = cipher_block(iv, expanded_key, key_type);
+
+    for block in data.chunks_exact(16) {
+        xor_in_place(&mut chaining_value, block);
+        chaining_value = cipher_block(&chaining_value, expanded_key, key_type);
+    }
+
+    chaining_value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The inputs and expected output of one CAVP DRBGVS test case
+    #[derive(Default)]
+    struct TestCase {
+        entropy_input: Vec<u8>,
+        nonce: Vec<u8>,
+        personalization: Vec<u8>,
+        entropy_input_reseed: Option<Vec<u8>>,
+        additional_input_reseed: Vec<u8>,
+        additional_input: Vec<Vec<u8>>,
+        entropy_input_pr: Vec<Vec<u8>>,
+    }
+
+    fn parse_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Run a case the way DRBGVS does: instantiate, optionally reseed, then
+    /// generate twice and return the output of the second call
+    fn run_case(
+        key_type: KeyType,
+        derivation_function: bool,
+        prediction_resistance: bool,
+        case: &TestCase,
+        output_len: usize,
+    ) -> Vec<u8> {
+        let mut drbg = if derivation_function {
+            CtrDrbg::new(
+                key_type,
+                &case.entropy_input,
+                &case.nonce,
+                &case.personalization,
+            )
+        } else {
+            CtrDrbg::new_without_df(key_type, &case.entropy_input, &case.personalization)
+        }
+        .unwrap();
+
+        if let Some(entropy_input) = &case.entropy_input_reseed {
+            drbg.reseed(entropy_input, &case.additional_input_reseed)
+                .unwrap();
+        }
+
+        let mut output = vec![0; output_len];
+        for i in 0..2 {
+            if prediction_resistance {
+                drbg.generate_with_prediction_resistance(
+                    &mut output,
+                    &case.entropy_input_pr[i],
+                    &case.additional_input[i],
+                )
+            } else {
+                drbg.generate(&mut output, &case.additional_input[i])
+            }
+            .unwrap();
+        }
+        output
+    }
+
+    /// Run every AES case in a DRBGVS response file and return how many ran.
+    /// Sections for other block ciphers and fields the test doesn't use are
+    /// skipped, so the official files load unchanged.
+    fn run_vectors(vectors: &str) -> usize {
+        let mut key_type = None;
+        let mut derivation_function = false;
+        let mut prediction_resistance = false;
+        let mut case = TestCase::default();
+        let mut cases_run = 0;
+
+        for line in vectors.lines().map(str::trim) {
+            if let Some(section) = line.strip_prefix('[') {
+                let section = section.trim_end_matches(']');
+                if let Some(value) = section.strip_prefix("PredictionResistance = ") {
+                    prediction_resistance = value == "True";
+                } else if !section.contains(" = ") {
+                    key_type = match section.get(..7) {
+                        Some("AES-128") => Some(KeyType::Aes128),
+                        Some("AES-192") => Some(KeyType::Aes192),
+                        Some("AES-256") => Some(KeyType::Aes256),
+                        _ => None,
+                    };
+                    derivation_function = section.ends_with("use df");
+                }
+                continue;
+            }
+
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let Some(key_type) = key_type else {
+                continue;
+            };
+            let name = name.trim();
+            let value = value.trim();
+
+            match name {
+                "COUNT" => case = TestCase::default(),
+                "EntropyInput" => case.entropy_input = parse_hex(value),
+                "Nonce" => case.nonce = parse_hex(value),
+                "PersonalizationString" => case.personalization = parse_hex(value),
+                "EntropyInputReseed" => case.entropy_input_reseed = Some(parse_hex(value)),
+                "AdditionalInputReseed" => case.additional_input_reseed = parse_hex(value),
+                "AdditionalInput" => case.additional_input.push(parse_hex(value)),
+                "EntropyInputPR" => case.entropy_input_pr.push(parse_hex(value)),
+                "ReturnedBits" => {
+                    let expected = parse_hex(value);
+                    let result = run_case(
+                        key_type,
+                        derivation_function,
+                        prediction_resistance,
+                        &case,
+                        expected.len(),
+                    );
+                    assert_eq!(result, expected);
+                    cases_run += 1;
+                }
+                _ => {}
+            }
+        }
+        cases_run
+    }
+
+    #[test]
+    fn cavp_test() {
+        let vectors = include_str!("../test_vectors/ctr_drbg.rsp");
+        assert_eq!(run_vectors(vectors), 16);
+    }
+
+    #[test]
+    fn openssl_test() {
+        let vectors = include_str!("../test_vectors/ctr_drbg_openssl.rsp");
+        assert_eq!(run_vectors(vectors), 14);
+    }
+
+    #[test]
+    fn unknown_fields_test() {
+        let vectors = "[3KeyTDEA use df]\n\
+                       COUNT = 0\n\
+                       EntropyInput = 00\n\
+                       ReturnedBits = 00\n\
+                       [AES-128 use df]\n\
+                       [PredictionResistance = False]\n\
+                       COUNT = 0\n\
+                       EntropyInput = 890eb067acf7382eff80b0c73bc872c6\n\
+                       Nonce = aad471ef3ef1d203\n\
+                       PersonalizationString = \n\
+                       AdditionalInput = \n\
+                       AdditionalInput = \n\
+                       ** INTERMEDIATE = not hex\n\
+                       ReturnedBits = a5514ed7095f64f3d0d3a5760394ab42062f373a25072a6ea6bcfd84\
+                       89e94af6cf18659fea22ed1ca0a9e33f718b115ee536b12809c31b72b08ddd8be1910fa3";
+        assert_eq!(run_vectors(vectors), 1);
+    }
+
+    #[test]
+    fn reseed_counter_test() {
+        let mut drbg = CtrDrbg::new(KeyType::Aes128, &[0x11; 16], &[0x22; 8], b"").unwrap();
+        drbg.set_reseed_interval(2);
+
+        let mut output = [0; 32];
+        drbg.generate(&mut output, b"").unwrap();
+        drbg.generate(&mut output, b"").unwrap();
+        assert_eq!(drbg.reseed_counter(), 3);
+        assert_eq!(
+            drbg.generate(&mut output, b""),
+            Err(DrbgError::ReseedRequired)
+        );
+
+        drbg.reseed(&[0x33; 16], b"").unwrap();
+        assert_eq!(drbg.reseed_counter(), 1);
+        drbg.generate(&mut output, b"").unwrap();
+    }
+
+    #[test]
+    fn errors_test() {
+        let result = CtrDrbg::new(KeyType::Aes256, &[0; 31], &[0; 16], b"");
+        assert_eq!(result.err(), Some(DrbgError::InvalidEntropyLength(31)));
+
+        let result = CtrDrbg::new_without_df(KeyType::Aes128, &[0; 16], b"");
+        assert_eq!(result.err(), Some(DrbgError::InvalidEntropyLength(16)));
+
+        let result = CtrDrbg::new_without_df(KeyType::Aes128, &[0; 32], &[0; 33]);
+        assert_eq!(result.err(), Some(DrbgError::InputTooLong(33)));
+
+        let mut drbg = CtrDrbg::new_without_df(KeyType::Aes128, &[0; 32], b"").unwrap();
+        let mut output = vec![0; MAX_REQUEST_LEN + 1];
+        assert_eq!(
+            drbg.generate(&mut output, b""),
+            Err(DrbgError::RequestTooLong(MAX_REQUEST_LEN + 1))
+        );
+        assert_eq!(
+            drbg.generate(&mut output[..16], &[0; 33]),
+            Err(DrbgError::InputTooLong(33))
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn derive_length_test() {
+        // zeroed allocations aren't touched before the length is checked
+        let input = vec![0; 1 << 31];
+        let drbg = CtrDrbg::new(KeyType::Aes128, &[0; 16], &[0; 8], b"").unwrap();
+        assert_eq!(
+            drbg.derive(&[&input, &input]),
+            Err(DrbgError::InputTooLong(1 << 32))
+        );
+    }
+}
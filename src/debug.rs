@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::encoding::encode_hex;
+
 #[macro_export]
 macro_rules! debug {
     ( $dbg_stmt:stmt ) => {
@@ -94,5 +96,5 @@ pub fn print_hex_array(round: usize, step: Step, array: &[u8]) {
 }
 
 pub fn hex_array_to_string(array: &[u8]) -> String {
-	array.iter().map(|value| format!("{:02x}", value)).collect()
+	encode_hex(array)
 }
\ No newline at end of file
@@ -0,0 +1,430 @@
+//! EAX authenticated encryption (Bellare, Rogaway and Wagner)
+//!
+//! EAX combines CTR mode with three tweaked OMAC (CMAC) computations over the
+//! nonce, the header and the ciphertext. It only ever uses the forward cipher,
+//! and both the nonce and the header may be of any length.
+
+use std::fmt::Display;
+
+use crate::cmac::{cmac, Cmac};
+use crate::constant::KeyType;
+use crate::ctr::{ctr_encrypt, CtrEncryptor};
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+use crate::util::{constant_time_eq, xor_in_place};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EaxError {
+    /// Tags must be between 1 and 16 bytes
+    InvalidTagLength(usize),
+    /// The ciphertext is shorter than the tag
+    TooShort,
+    /// The tag didn't match the nonce, header and ciphertext
+    AuthenticationFailed,
+}
+
+impl Display for EaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidTagLength(len) => write!(f, "invalid tag length {len}"),
+            Self::TooShort => write!(f, "ciphertext is shorter than the tag"),
+            Self::AuthenticationFailed => write!(f, "tag verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for EaxError {}
+
+/// Encrypt `plaintext`, returning the ciphertext followed by a `tag_len`-byte tag
+pub fn eax_encrypt(
+    header: &[u8],
+    plaintext: &[u8],
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, EaxError> {
+    check_tag_len(tag_len)?;
+
+    let n = omac(0, nonce, expanded_key, key_type);
+    let h = omac(1, header, expanded_key, key_type);
+
+    let mut result = ctr_encrypt(plaintext, &n, expanded_key, key_type);
+    let tag = compute_tag(&n, &h, &result, expanded_key, key_type);
+    result.extend(&tag[..tag_len]);
+
+    Ok(result)
+}
+
+/// Decrypt a ciphertext produced by [`eax_encrypt`], verifying its tag
+pub fn eax_decrypt(
+    header: &[u8],
+    ciphertext: &[u8],
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, EaxError> {
+    check_tag_len(tag_len)?;
+
+    let ciphertext_len = ciphertext
+        .len()
+        .checked_sub(tag_len)
+        .ok_or(EaxError::TooShort)?;
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext_len);
+
+    let n = omac(0, nonce, expanded_key, key_type);
+    let h = omac(1, header, expanded_key, key_type);
+
+    // the tag covers the ciphertext, so it is checked before decrypting
+    let expected_tag = compute_tag(&n, &h, ciphertext, expanded_key, key_type);
+    if !constant_time_eq(&expected_tag[..tag_len], tag) {
+        return Err(EaxError::AuthenticationFailed);
+    }
+
+    Ok(ctr_encrypt(ciphertext, &n, expanded_key, key_type))
+}
+
+/// The running state shared by [`EaxEncryptor`] and [`EaxDecryptor`]
+struct State<'a> {
+    tag_len: usize,
+    n: [u8; 16],
+    h: [u8; 16],
+    ctr: CtrEncryptor<'a>,
+    /// The tweaked OMAC of the ciphertext so far
+    mac: Cmac<'a>,
+}
+
+impl<'a> State<'a> {
+    fn new(
+        header: &[u8],
+        nonce: &[u8],
+        tag_len: usize,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, EaxError> {
+        check_tag_len(tag_len)?;
+
+        let n = omac(0, nonce, expanded_key, key_type);
+        let h = omac(1, header, expanded_key, key_type);
+
+        let mut tweak = [0; 16];
+        tweak[15] = 2;
+        let mut mac = Cmac::new(expanded_key, key_type);
+        mac.update(&tweak);
+
+        Ok(Self {
+            tag_len,
+            n,
+            h,
+            ctr: CtrEncryptor::new(&n, expanded_key, key_type),
+            mac,
+        })
+    }
+
+    fn tag(self) -> [u8; 16] {
+        let mut tag = self.mac.finalize();
+        xor_in_place(&mut tag, &self.n);
+        xor_in_place(&mut tag, &self.h);
+        tag
+    }
+}
+
+/// Incremental EAX encryption, which appends the tag in
+/// [`Encryptor::finalize`]
+pub struct EaxEncryptor<'a> {
+    state: State<'a>,
+}
+
+impl<'a> EaxEncryptor<'a> {
+    pub fn new(
+        header: &[u8],
+        nonce: &[u8],
+        tag_len: usize,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, EaxError> {
+        Ok(Self {
+            state: State::new(header, nonce, tag_len, expanded_key, key_type)?,
+        })
+    }
+}
+
+impl Encryptor for EaxEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let ciphertext = self.state.ctr.update(plaintext);
+        self.state.mac.update(&ciphertext);
+        ciphertext
+    }
+
+    fn finalize(self) -> Result<Vec<u8>, StreamError> {
+        let tag_len = self.state.tag_len;
+        Ok(self.state.tag()[..tag_len].to_vec())
+    }
+}
+
+/// Incremental EAX decryption
+///
+/// The last `tag_len` bytes are held back as the tag, which is checked by
+/// [`Decryptor::finalize`]. Unlike [`eax_decrypt`], plaintext is returned
+/// before the tag has been checked, so none of it is authentic until then.
+pub struct EaxDecryptor<'a> {
+    state: State<'a>,
+    buffer: Buffer,
+}
+
+impl<'a> EaxDecryptor<'a> {
+    pub fn new(
+        header: &[u8],
+        nonce: &[u8],
+        tag_len: usize,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, EaxError> {
+        Ok(Self {
+            state: State::new(header, nonce, tag_len, expanded_key, key_type)?,
+            buffer: Buffer::default(),
+        })
+    }
+}
+
+impl Decryptor for EaxDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let mut ready = self.buffer.take(ciphertext, 1, self.state.tag_len);
+        self.state.mac.update(&ready);
+        self.state.ctr.apply_keystream(&mut ready);
+        ready
+    }
+
+    fn finalize(self) -> Result<Vec<u8>, StreamError> {
+        let tag = self.buffer.remaining();
+        if tag.len() < self.state.tag_len {
+            return Err(StreamError::TooShort(tag.len()));
+        }
+
+        let tag_len = self.state.tag_len;
+        let expected_tag = self.state.tag();
+        if !constant_time_eq(&expected_tag[..tag_len], &tag) {
+            return Err(StreamError::AuthenticationFailed);
+        }
+
+        Ok(Vec::new())
+    }
+}
+
+fn check_tag_len(tag_len: usize) -> Result<(), EaxError> {
+    if tag_len == 0 || tag_len > 16 {
+        return Err(EaxError::InvalidTagLength(tag_len));
+    }
+    Ok(())
+}
+
+/// OMAC of `message`, tweaked by prepending a block holding `tweak`
+fn omac(tweak: u8, message: &[u8], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let mut input = vec![0; 16];
+    input[15] = tweak;
+    input.extend(message);
+    cmac(&input, expanded_key, key_type)
+}
+
+fn compute_tag(
+    n: &[u8; 16],
+    h: &[u8; 16],
+    ciphertext: &[u8],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut tag = omac(2, ciphertext, expanded_key, key_type);
+    xor_in_place(&mut tag, n);
+    xor_in_place(&mut tag, h);
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::stream::check_streaming;
+
+    // Test vectors from "The EAX Mode of Operation", Appendix E
+
+    fn check_vector(key: &[u8], nonce: &[u8], header: &[u8], message: &[u8], expected: &[u8]) {
+        let expanded_key = key_expansion(key, KeyType::Aes128);
+
+        let result =
+            eax_encrypt(header, message, nonce, 16, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(result, expected);
+
+        let result =
+            eax_decrypt(header, expected, nonce, 16, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(result, message);
+    }
+
+    #[test]
+    fn empty_message_test() {
+        let key = [
+            0x23, 0x39, 0x52, 0xde, 0xe4, 0xd5, 0xed, 0x5f, 0x9b, 0x9c, 0x6d, 0x6f, 0xf8, 0x0f,
+            0xf4, 0x78,
+        ];
+        let nonce = [
+            0x62, 0xec, 0x67, 0xf9, 0xc3, 0xa4, 0xa4, 0x07, 0xfc, 0xb2, 0xa8, 0xc4, 0x90, 0x31,
+            0xa8, 0xb3,
+        ];
+        let header = [0x6b, 0xfb, 0x91, 0x4f, 0xd0, 0x7e, 0xae, 0x6b];
+        let expected = [
+            0xe0, 0x37, 0x83, 0x0e, 0x83, 0x89, 0xf2, 0x7b, 0x02, 0x5a, 0x2d, 0x65, 0x27, 0xe7,
+            0x9d, 0x01,
+        ];
+
+        check_vector(&key, &nonce, &header, &[], &expected);
+    }
+
+    #[test]
+    fn short_messages_test() {
+        let key = [
+            0x91, 0x94, 0x5d, 0x3f, 0x4d, 0xcb, 0xee, 0x0b, 0xf4, 0x5e, 0xf5, 0x22, 0x55, 0xf0,
+            0x95, 0xa4,
+        ];
+        let nonce = [
+            0xbe, 0xca, 0xf0, 0x43, 0xb0, 0xa2, 0x3d, 0x84, 0x31, 0x94, 0xba, 0x97, 0x2c, 0x66,
+            0xde, 0xbd,
+        ];
+        let header = [0xfa, 0x3b, 0xfd, 0x48, 0x06, 0xeb, 0x53, 0xfa];
+        let expected = [
+            0x19, 0xdd, 0x5c, 0x4c, 0x93, 0x31, 0x04, 0x9d, 0x0b, 0xda, 0xb0, 0x27, 0x74, 0x08,
+            0xf6, 0x79, 0x67, 0xe5,
+        ];
+        check_vector(&key, &nonce, &header, &[0xf7, 0xfb], &expected);
+
+        let key = [
+            0x01, 0xf7, 0x4a, 0xd6, 0x40, 0x77, 0xf2, 0xe7, 0x04, 0xc0, 0xf6, 0x0a, 0xda, 0x3d,
+            0xd5, 0x23,
+        ];
+        let nonce = [
+            0x70, 0xc3, 0xdb, 0x4f, 0x0d, 0x26, 0x36, 0x84, 0x00, 0xa1, 0x0e, 0xd0, 0x5d, 0x2b,
+            0xff, 0x5e,
+        ];
+        let header = [0x23, 0x4a, 0x34, 0x63, 0xc1, 0x26, 0x4a, 0xc6];
+        let message = [0x1a, 0x47, 0xcb, 0x49, 0x33];
+        let expected = [
+            0xd8, 0x51, 0xd5, 0xba, 0xe0, 0x3a, 0x59, 0xf2, 0x38, 0xa2, 0x3e, 0x39, 0x19, 0x9d,
+            0xc9, 0x26, 0x66, 0x26, 0xc4, 0x0f, 0x80,
+        ];
+        check_vector(&key, &nonce, &header, &message, &expected);
+
+        let key = [
+            0xd0, 0x7c, 0xf6, 0xcb, 0xb7, 0xf3, 0x13, 0xbd, 0xde, 0x66, 0xb7, 0x27, 0xaf, 0xd3,
+            0xc5, 0xe8,
+        ];
+        let nonce = [
+            0x84, 0x08, 0xdf, 0xff, 0x3c, 0x1a, 0x2b, 0x12, 0x92, 0xdc, 0x19, 0x9e, 0x46, 0xb7,
+            0xd6, 0x17,
+        ];
+        let header = [0x33, 0xcc, 0xe2, 0xea, 0xbf, 0xf5, 0xa7, 0x9d];
+        let message = [0x48, 0x1c, 0x9e, 0x39, 0xb1];
+        let expected = [
+            0x63, 0x2a, 0x9d, 0x13, 0x1a, 0xd4, 0xc1, 0x68, 0xa4, 0x22, 0x5d, 0x8e, 0x1f, 0xf7,
+            0x55, 0x93, 0x99, 0x74, 0xa7, 0xbe, 0xde,
+        ];
+        check_vector(&key, &nonce, &header, &message, &expected);
+
+        let key = [
+            0x35, 0xb6, 0xd0, 0x58, 0x00, 0x05, 0xbb, 0xc1, 0x2b, 0x05, 0x87, 0x12, 0x45, 0x57,
+            0xd2, 0xc2,
+        ];
+        let nonce = [
+            0xfd, 0xb6, 0xb0, 0x66, 0x76, 0xee, 0xdc, 0x5c, 0x61, 0xd7, 0x42, 0x76, 0xe1, 0xf8,
+            0xe8, 0x16,
+        ];
+        let header = [0xae, 0xb9, 0x6e, 0xae, 0xbe, 0x29, 0x70, 0xe9];
+        let message = [0x40, 0xd0, 0xc0, 0x7d, 0xa5, 0xe4];
+        let expected = [
+            0x07, 0x1d, 0xfe, 0x16, 0xc6, 0x75, 0xcb, 0x06, 0x77, 0xe5, 0x36, 0xf7, 0x3a, 0xfe,
+            0x6a, 0x14, 0xb7, 0x4e, 0xe4, 0x98, 0x44, 0xdd,
+        ];
+        check_vector(&key, &nonce, &header, &message, &expected);
+    }
+
+    #[test]
+    fn errors_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+        let nonce = b"any length of nonce";
+
+        let mut ciphertext = eax_encrypt(
+            b"header",
+            b"message",
+            nonce,
+            8,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(ciphertext.len(), 15);
+
+        let result = eax_decrypt(
+            b"other",
+            &ciphertext,
+            nonce,
+            8,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(EaxError::AuthenticationFailed));
+
+        ciphertext[0] ^= 1;
+        let result = eax_decrypt(
+            b"header",
+            &ciphertext,
+            nonce,
+            8,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(EaxError::AuthenticationFailed));
+
+        let result = eax_decrypt(b"", &[0; 7], nonce, 8, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(EaxError::TooShort));
+
+        let result = eax_encrypt(b"", b"", nonce, 0, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(EaxError::InvalidTagLength(0)));
+    }
+
+    #[test]
+    fn streaming_test() {
+        let expanded_key = key_expansion(&[0x3e; 16], KeyType::Aes128);
+        let header = b"header";
+        let nonce = b"streaming nonce";
+        let plaintext: Vec<u8> = (0..100).collect();
+
+        for len in [0, 15, 16, 17, 100] {
+            let expected = eax_encrypt(
+                header,
+                &plaintext[..len],
+                nonce,
+                16,
+                &expanded_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+
+            check_streaming(
+                &plaintext[..len],
+                &expected,
+                || EaxEncryptor::new(header, nonce, 16, &expanded_key, KeyType::Aes128).unwrap(),
+                || EaxDecryptor::new(header, nonce, 16, &expanded_key, KeyType::Aes128).unwrap(),
+            );
+        }
+
+        let mut ciphertext =
+            eax_encrypt(header, b"message", nonce, 8, &expanded_key, KeyType::Aes128).unwrap();
+        ciphertext[14] ^= 0x80;
+        let mut decryptor =
+            EaxDecryptor::new(header, nonce, 8, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(decryptor.update(&ciphertext), b"message");
+        assert_eq!(decryptor.finalize(), Err(StreamError::AuthenticationFailed));
+
+        let mut decryptor =
+            EaxDecryptor::new(header, nonce, 8, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(decryptor.update(&[0; 7]), []);
+        assert_eq!(decryptor.finalize(), Err(StreamError::TooShort(7)));
+    }
+}
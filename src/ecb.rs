@@ -0,0 +1,298 @@
+//! Electronic codebook (ECB) mode (NIST SP 800-38A)
+//!
+//! Each block is enciphered on its own, so equal plaintext blocks give equal
+//! ciphertext blocks. ECB is only here for interoperating with systems that
+//! already use it.
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::padding::{Padding, PaddingError};
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+use crate::util::process_parallel;
+
+/// Pad and encrypt `plaintext`
+pub fn ecb_encrypt(
+    plaintext: &[u8],
+    padding: &dyn Padding,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    let mut result = plaintext.to_vec();
+    padding.pad(&mut result);
+
+    for block in result.chunks_exact_mut(16) {
+        let input: [u8; 16] = block.try_into().unwrap();
+        let output = cipher_block(&input, expanded_key, key_type);
+        block.copy_from_slice(&output);
+    }
+
+    result
+}
+
+/// Decrypt `ciphertext` and remove its padding
+pub fn ecb_decrypt(
+    ciphertext: &[u8],
+    padding: &dyn Padding,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, PaddingError> {
+    if !ciphertext.len().is_multiple_of(16) {
+        return Err(PaddingError::InvalidLength(ciphertext.len()));
+    }
+
+    let mut result = ciphertext.to_vec();
+    for block in result.chunks_exact_mut(16) {
+        let input: [u8; 16] = block.try_into().unwrap();
+        let output = inverse_cipher_block(&input, expanded_key, key_type);
+        block.copy_from_slice(&output);
+    }
+
+    let len = padding.unpad(&result)?.len();
+    result.truncate(len);
+    Ok(result)
+}
+
+/// Pad and encrypt `plaintext` like [`ecb_encrypt`], splitting the blocks
+/// between up to `threads` threads
+pub fn ecb_encrypt_parallel(
+    plaintext: &[u8],
+    padding: &dyn Padding,
+    threads: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Vec<u8> {
+    let mut result = plaintext.to_vec();
+    padding.pad(&mut result);
+
+    process_parallel(&mut result, 16, threads, |_, run| {
+        for block in run.chunks_exact_mut(16) {
+            let input: [u8; 16] = block.try_into().unwrap();
+            block.copy_from_slice(&cipher_block(&input, expanded_key, key_type));
+        }
+    });
+
+    result
+}
+
+/// Decrypt `ciphertext` like [`ecb_decrypt`], splitting the blocks between up
+/// to `threads` threads
+pub fn ecb_decrypt_parallel(
+    ciphertext: &[u8],
+    padding: &dyn Padding,
+    threads: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, PaddingError> {
+    if !ciphertext.len().is_multiple_of(16) {
+        return Err(PaddingError::InvalidLength(ciphertext.len()));
+    }
+
+    let mut result = ciphertext.to_vec();
+    process_parallel(&mut result, 16, threads, |_, run| {
+        for block in run.chunks_exact_mut(16) {
+            let input: [u8; 16] = block.try_into().unwrap();
+            block.copy_from_slice(&inverse_cipher_block(&input, expanded_key, key_type));
+        }
+    });
+
+    let len = padding.unpad(&result)?.len();
+    result.truncate(len);
+    Ok(result)
+}
+
+/// Incremental ECB encryption
+pub struct EcbEncryptor<'a> {
+    padding: &'a dyn Padding,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    buffer: Buffer,
+}
+
+impl<'a> EcbEncryptor<'a> {
+    pub fn new(padding: &'a dyn Padding, expanded_key: &'a [u32], key_type: KeyType) -> Self {
+        Self {
+            padding,
+            expanded_key,
+            key_type,
+            buffer: Buffer::default(),
+        }
+    }
+
+    fn encrypt_blocks(&self, mut blocks: Vec<u8>) -> Vec<u8> {
+        for block in blocks.chunks_exact_mut(16) {
+            let input: [u8; 16] = block.try_into().unwrap();
+            block.copy_from_slice(&cipher_block(&input, self.expanded_key, self.key_type));
+        }
+        blocks
+    }
+}
+
+impl Encryptor for EcbEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let blocks = self.buffer.take(plaintext, 16, 0);
+        self.encrypt_blocks(blocks)
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let mut last = std::mem::take(&mut self.buffer).remaining();
+        self.padding.pad(&mut last);
+        Ok(self.encrypt_blocks(last))
+    }
+}
+
+/// Incremental ECB decryption
+///
+/// The last block is held back until [`Decryptor::finalize`], since it holds
+/// the padding.
+pub struct EcbDecryptor<'a> {
+    padding: &'a dyn Padding,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    buffer: Buffer,
+}
+
+impl<'a> EcbDecryptor<'a> {
+    pub fn new(padding: &'a dyn Padding, expanded_key: &'a [u32], key_type: KeyType) -> Self {
+        Self {
+            padding,
+            expanded_key,
+            key_type,
+            buffer: Buffer::default(),
+        }
+    }
+
+    fn decrypt_blocks(&self, mut blocks: Vec<u8>) -> Vec<u8> {
+        for block in blocks.chunks_exact_mut(16) {
+            let input: [u8; 16] = block.try_into().unwrap();
+            block.copy_from_slice(&inverse_cipher_block(
+                &input,
+                self.expanded_key,
+                self.key_type,
+            ));
+        }
+        blocks
+    }
+}
+
+impl Decryptor for EcbDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let blocks = self.buffer.take(ciphertext, 16, 1);
+        self.decrypt_blocks(blocks)
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let last = std::mem::take(&mut self.buffer).remaining();
+        if !last.len().is_multiple_of(16) {
+            return Err(PaddingError::InvalidLength(last.len()).into());
+        }
+
+        let last = self.decrypt_blocks(last);
+        Ok(self.padding.unpad(&last)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::padding::Pkcs7;
+    use crate::stream::check_streaming;
+
+    #[test]
+    fn ecb_aes_128_test() {
+        // F.1.1 and F.1.2 from NIST SP 800-38A, followed by a block of padding
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let plaintext = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac,
+            0x45, 0xaf, 0x8e, 0x51,
+        ];
+        let expected = [
+            0x3a, 0xd7, 0x7b, 0xb4, 0x0d, 0x7a, 0x36, 0x60, 0xa8, 0x9e, 0xca, 0xf3, 0x24, 0x66,
+            0xef, 0x97, 0xf5, 0xd3, 0xd5, 0x85, 0x03, 0xb9, 0x69, 0x9d, 0xe7, 0x85, 0x89, 0x5a,
+            0x96, 0xfd, 0xba, 0xaf,
+        ];
+        let expanded_key = key_expansion(&key, KeyType::Aes128);
+
+        let result = ecb_encrypt(&plaintext, &Pkcs7, &expanded_key, KeyType::Aes128);
+        assert_eq!(result.len(), 48);
+        assert_eq!(result[..32], expected);
+
+        let result = ecb_decrypt(&result, &Pkcs7, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn invalid_ciphertext_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+
+        let result = ecb_decrypt(&[0; 20], &Pkcs7, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(PaddingError::InvalidLength(20)));
+
+        // random-looking plaintext almost never ends in valid padding
+        let ciphertext = ecb_encrypt(b"message", &Pkcs7, &expanded_key, KeyType::Aes128);
+        let other_key = key_expansion(&[1; 16], KeyType::Aes128);
+        let result = ecb_decrypt(&ciphertext, &Pkcs7, &other_key, KeyType::Aes128);
+        assert_eq!(result, Err(PaddingError::InvalidPadding));
+    }
+
+    #[test]
+    fn parallel_test() {
+        let expanded_key = key_expansion(&[0x17; 32], KeyType::Aes256);
+        let plaintext: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for len in [0, 15, 16, 1000] {
+            let expected = ecb_encrypt(&plaintext[..len], &Pkcs7, &expanded_key, KeyType::Aes256);
+            for threads in [0, 1, 3, 8, 100] {
+                let ciphertext = ecb_encrypt_parallel(
+                    &plaintext[..len],
+                    &Pkcs7,
+                    threads,
+                    &expanded_key,
+                    KeyType::Aes256,
+                );
+                assert_eq!(ciphertext, expected);
+
+                let result = ecb_decrypt_parallel(
+                    &ciphertext,
+                    &Pkcs7,
+                    threads,
+                    &expanded_key,
+                    KeyType::Aes256,
+                );
+                assert_eq!(result.unwrap(), &plaintext[..len]);
+            }
+        }
+
+        let result = ecb_decrypt_parallel(&[0; 20], &Pkcs7, 4, &expanded_key, KeyType::Aes256);
+        assert_eq!(result, Err(PaddingError::InvalidLength(20)));
+    }
+
+    #[test]
+    fn streaming_test() {
+        let expanded_key = key_expansion(&[0x17; 24], KeyType::Aes192);
+        let plaintext: Vec<u8> = (0..100).collect();
+
+        for len in [0, 15, 16, 17, 100] {
+            let expected = ecb_encrypt(&plaintext[..len], &Pkcs7, &expanded_key, KeyType::Aes192);
+
+            check_streaming(
+                &plaintext[..len],
+                &expected,
+                || EcbEncryptor::new(&Pkcs7, &expanded_key, KeyType::Aes192),
+                || EcbDecryptor::new(&Pkcs7, &expanded_key, KeyType::Aes192),
+            );
+        }
+
+        let mut decryptor = EcbDecryptor::new(&Pkcs7, &expanded_key, KeyType::Aes192);
+        assert_eq!(decryptor.update(&[0; 20]).len(), 16);
+        assert_eq!(
+            decryptor.finalize(),
+            Err(StreamError::Padding(PaddingError::InvalidLength(4)))
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Hex and base64 encodings for keys, IVs and data
+//!
+//! Decoding ignores ASCII whitespace, so values can be pasted with line breaks
+//! or spaces between groups of digits. Base64 uses the standard alphabet
+//! (RFC 4648) and accepts input with or without its trailing `=` padding.
+
+use std::fmt::Display;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncodingError {
+    /// A character that isn't part of the encoding, and its position among
+    /// the characters that aren't whitespace
+    InvalidCharacter(char, usize),
+    /// The number of digits can't have come from a whole number of bytes
+    InvalidLength(usize),
+    /// Base64 padding in the wrong place, or with nonzero bits before it
+    InvalidPadding,
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCharacter(character, position) => {
+                write!(f, "invalid character {character:?} at position {position}")
+            }
+            Self::InvalidLength(len) => write!(f, "invalid encoded length {len}"),
+            Self::InvalidPadding => write!(f, "invalid base64 padding"),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Hex,
+    Base64,
+}
+
+impl Encoding {
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Hex => encode_hex(bytes),
+            Self::Base64 => encode_base64(bytes),
+        }
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<u8>, EncodingError> {
+        match self {
+            Self::Hex => decode_hex(text),
+            Self::Base64 => decode_base64(text),
+        }
+    }
+}
+
+/// Lowercase hex, two digits per byte
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn decode_hex(text: &str) -> Result<Vec<u8>, EncodingError> {
+    let digits = without_whitespace(text)
+        .map(|(position, character)| {
+            character
+                .to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or(EncodingError::InvalidCharacter(character, position))
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(EncodingError::InvalidLength(digits.len()));
+    }
+
+    Ok(digits
+        .chunks_exact(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect())
+}
+
+/// Standard base64 with `=` padding
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+
+        // n bytes give n + 1 digits, padded out to 4
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0x3f;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+pub fn decode_base64(text: &str) -> Result<Vec<u8>, EncodingError> {
+    let characters: Vec<(usize, char)> = without_whitespace(text).collect();
+    let padding = characters
+        .iter()
+        .rev()
+        .take_while(|(_, character)| *character == '=')
+        .count();
+    let digits = &characters[..characters.len() - padding];
+
+    // padding is optional, but if it's there it must finish the last group
+    let valid_padding = padding == 0 || (padding <= 2 && characters.len().is_multiple_of(4));
+    if !valid_padding {
+        return Err(EncodingError::InvalidPadding);
+    }
+    if digits.len() % 4 == 1 {
+        return Err(EncodingError::InvalidLength(digits.len()));
+    }
+
+    let values = digits
+        .iter()
+        .map(|&(position, character)| {
+            BASE64_ALPHABET
+                .iter()
+                .position(|&digit| digit as char == character)
+                .map(|value| value as u32)
+                .ok_or(EncodingError::InvalidCharacter(character, position))
+        })
+        .collect::<Result<Vec<u32>, _>>()?;
+
+    let mut result = Vec::with_capacity(values.len() * 3 / 4);
+    for group in values.chunks(4) {
+        let bits = group
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, value)| bits | (value << (18 - 6 * i)));
+        let bytes = &bits.to_be_bytes()[1..group.len()];
+
+        // the unused low bits of a partial group must be zero
+        let unused_bits = 24 - 8 * bytes.len();
+        if bits & ((1 << unused_bits) - 1) != 0 {
+            return Err(EncodingError::InvalidPadding);
+        }
+
+        result.extend(bytes);
+    }
+
+    Ok(result)
+}
+
+/// Decode a value that is either hex or base64
+///
+/// A `hex:` or `base64:` prefix picks the encoding explicitly. Without one, the
+/// value is decoded as hex if it can be, and as base64 otherwise.
+pub fn decode_any(text: &str) -> Result<Vec<u8>, EncodingError> {
+    if let Some(hex) = text.strip_prefix("hex:") {
+        decode_hex(hex)
+    } else if let Some(base64) = text.strip_prefix("base64:") {
+        decode_base64(base64)
+    } else {
+        decode_hex(text).or_else(|_| decode_base64(text))
+    }
+}
+
+/// The characters of `text` that aren't whitespace, numbered from zero
+fn without_whitespace(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    text.chars()
+        .filter(|character| !character.is_ascii_whitespace())
+        .enumerate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_test() {
+        assert_eq!(encode_hex(&[0x00, 0x0f, 0xa5, 0xff]), "000fa5ff");
+        assert_eq!(encode_hex(&[]), "");
+
+        assert_eq!(decode_hex("000FA5ff"), Ok(vec![0x00, 0x0f, 0xa5, 0xff]));
+        assert_eq!(
+            decode_hex(" 00 0f\na5ff\n"),
+            Ok(vec![0x00, 0x0f, 0xa5, 0xff])
+        );
+        assert_eq!(decode_hex(""), Ok(vec![]));
+        assert_eq!(decode_hex("abc"), Err(EncodingError::InvalidLength(3)));
+        assert_eq!(
+            decode_hex("00 +1"),
+            Err(EncodingError::InvalidCharacter('+', 2))
+        );
+        assert_eq!(
+            decode_hex("é0"),
+            Err(EncodingError::InvalidCharacter('é', 0))
+        );
+    }
+
+    #[test]
+    fn base64_test() {
+        // the examples from RFC 4648 section 10
+        let examples = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (decoded, encoded) in examples {
+            assert_eq!(encode_base64(decoded.as_bytes()), encoded);
+            assert_eq!(decode_base64(encoded).unwrap(), decoded.as_bytes());
+
+            // padding is optional when decoding
+            let unpadded = encoded.trim_end_matches('=');
+            assert_eq!(decode_base64(unpadded).unwrap(), decoded.as_bytes());
+        }
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = encode_base64(&bytes);
+        assert!(encoded.contains('+') && encoded.contains('/'));
+        assert_eq!(decode_base64(&encoded).unwrap(), bytes);
+
+        assert_eq!(decode_base64("Zm9v\r\nYmFy"), Ok(b"foobar".to_vec()));
+        assert_eq!(decode_base64("Zm9vY"), Err(EncodingError::InvalidLength(5)));
+        assert_eq!(
+            decode_base64("Zm-v"),
+            Err(EncodingError::InvalidCharacter('-', 2))
+        );
+        assert_eq!(decode_base64("Zg="), Err(EncodingError::InvalidPadding));
+        assert_eq!(decode_base64("Zg==="), Err(EncodingError::InvalidPadding));
+        assert_eq!(
+            decode_base64("Zm=v"),
+            Err(EncodingError::InvalidCharacter('=', 2))
+        );
+        assert_eq!(decode_base64("Zh=="), Err(EncodingError::InvalidPadding));
+    }
+
+    #[test]
+    fn decode_any_test() {
+        assert_eq!(decode_any("00ff"), Ok(vec![0x00, 0xff]));
+        assert_eq!(decode_any("hex:00ff"), Ok(vec![0x00, 0xff]));
+        assert_eq!(decode_any("base64:00ff"), Ok(vec![0xd3, 0x47, 0xdf]));
+        assert_eq!(decode_any("Zm9v"), Ok(b"foo".to_vec()));
+        assert!(decode_any("hex:Zm9v").is_err());
+    }
+}
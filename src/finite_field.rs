@@ -1,4 +1,5 @@
-//! Calculations done with finite fields, which are represented as bytes
+//! Calculations done with finite fields, which are represented as bytes (or as
+//! 16-byte blocks for GF(2^128))
 
 #[derive(Clone, Copy)]
 pub struct FiniteField(u8);
@@ -31,9 +32,9 @@ impl From<&u8> for FiniteField {
     }
 }
 
-impl Into<u8> for FiniteField {
-	fn into(self) -> u8 {
-		self.0
+impl From<FiniteField> for u8 {
+	fn from(value: FiniteField) -> Self {
+		value.0
 	}
 }
 
@@ -74,6 +75,62 @@ fn ff_multiply(x: u8, y: u8) -> u8 {
 	}
 }
 
+/// Multiply a 128-bit block by the primitive element `alpha` of GF(2^128), using
+/// the little-endian convention of IEEE 1619 (XTS), where the first byte holds
+/// the lowest-order coefficients
+pub fn mul_alpha(block: &[u8; 16]) -> [u8; 16] {
+	let mut result = [0; 16];
+	let mut carry = 0;
+
+	for (out, byte) in result.iter_mut().zip(block) {
+		*out = (byte << 1) | carry;
+		carry = byte >> 7;
+	}
+
+	if carry == 1 {
+		result[0] ^= 0x87;
+	}
+
+	result
+}
+
+/// Double a 128-bit block in GF(2^128), using the big-endian convention of
+/// CMAC and SIV, where the first byte holds the highest-order coefficients
+pub fn dbl(block: &[u8; 16]) -> [u8; 16] {
+	let mut result = [0; 16];
+	let mut carry = 0;
+
+	for (out, byte) in result.iter_mut().zip(block).rev() {
+		*out = (byte << 1) | carry;
+		carry = byte >> 7;
+	}
+
+	if carry == 1 {
+		result[15] ^= 0x87;
+	}
+
+	result
+}
+
+/// Halve a 128-bit block in GF(2^128), the inverse of [`dbl`], using the
+/// same big-endian convention
+pub fn halve(block: &[u8; 16]) -> [u8; 16] {
+	let mut result = [0; 16];
+	let mut carry = 0;
+
+	for (out, byte) in result.iter_mut().zip(block) {
+		*out = (byte >> 1) | carry;
+		carry = byte << 7;
+	}
+
+	if carry == 0x80 {
+		result[0] ^= 0x80;
+		result[15] ^= 0x43;
+	}
+
+	result
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -95,4 +152,68 @@ mod tests {
 	fn ff_multiply_test() {
 		assert_eq!(ff_multiply(0x57, 0x13), 0xfe);
 	}
+
+	#[test]
+	fn mul_alpha_test() {
+		let mut block = [0; 16];
+		block[0] = 0x01;
+		let mut expected = [0; 16];
+		expected[0] = 0x02;
+		assert_eq!(mul_alpha(&block), expected);
+
+		let mut block = [0; 16];
+		block[7] = 0x80;
+		let mut expected = [0; 16];
+		expected[8] = 0x01;
+		assert_eq!(mul_alpha(&block), expected);
+
+		let mut block = [0; 16];
+		block[15] = 0x80;
+		let mut expected = [0; 16];
+		expected[0] = 0x87;
+		assert_eq!(mul_alpha(&block), expected);
+	}
+
+	#[test]
+	fn dbl_test() {
+		// subkey generation example from RFC 4493 section 4
+		let l = [
+			0x7d, 0xf7, 0x6b, 0x0c, 0x1a, 0xb8, 0x99, 0xb3,
+			0x3e, 0x42, 0xf0, 0x47, 0xb9, 0x1b, 0x54, 0x6f,
+		];
+		let k_1 = [
+			0xfb, 0xee, 0xd6, 0x18, 0x35, 0x71, 0x33, 0x66,
+			0x7c, 0x85, 0xe0, 0x8f, 0x72, 0x36, 0xa8, 0xde,
+		];
+		let k_2 = [
+			0xf7, 0xdd, 0xac, 0x30, 0x6a, 0xe2, 0x66, 0xcc,
+			0xf9, 0x0b, 0xc1, 0x1e, 0xe4, 0x6d, 0x51, 0x3b,
+		];
+
+		assert_eq!(dbl(&l), k_1);
+		assert_eq!(dbl(&k_1), k_2);
+	}
+
+	#[test]
+	fn halve_test() {
+		let mut block = [0; 16];
+		block[0] = 0x02;
+		let mut expected = [0; 16];
+		expected[0] = 0x01;
+		assert_eq!(halve(&block), expected);
+
+		let mut block = [0; 16];
+		block[15] = 0x01;
+		let mut expected = [0; 16];
+		expected[0] = 0x80;
+		expected[15] = 0x43;
+		assert_eq!(halve(&block), expected);
+
+		let block = [
+			0x7d, 0xf7, 0x6b, 0x0c, 0x1a, 0xb8, 0x99, 0xb3,
+			0x3e, 0x42, 0xf0, 0x47, 0xb9, 0x1b, 0x54, 0x6f,
+		];
+		assert_eq!(halve(&dbl(&block)), block);
+		assert_eq!(dbl(&halve(&block)), block);
+	}
 }
\ No newline at end of file
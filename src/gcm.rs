@@ -0,0 +1,355 @@
+//! Galois/Counter Mode (GCM) authenticated encryption (NIST SP 800-38D)
+//!
+//! The plaintext is encrypted in CTR mode, with only the last 32 bits of the
+//! counter block incremented, and the tag is GHASH of the associated data and
+//! ciphertext, encrypted with the first counter block. 12-byte nonces are used
+//! directly as the start of the counter block; nonces of any other length are
+//! hashed with GHASH first. A nonce must never be repeated under the same key.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::util::{constant_time_eq, pad_to_block, xor_in_place};
+
+/// The recommended nonce length, in bytes
+pub const NONCE_LEN: usize = 12;
+
+/// The longest plaintext allowed, in bytes (2^39 - 256 bits)
+pub const MAX_PLAINTEXT_LEN: u64 = (1 << 36) - 32;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GcmError {
+    /// Nonces can't be empty
+    EmptyNonce,
+    /// Tags must be 4, 8, or 12 to 16 bytes
+    InvalidTagLength(usize),
+    /// The plaintext is longer than [`MAX_PLAINTEXT_LEN`]
+    InputTooLong,
+    /// The ciphertext is shorter than the tag
+    TooShort,
+    /// The tag didn't match the associated data and ciphertext
+    AuthenticationFailed,
+}
+
+impl Display for GcmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyNonce => write!(f, "the nonce is empty"),
+            Self::InvalidTagLength(len) => write!(f, "invalid tag length {len}"),
+            Self::InputTooLong => write!(f, "input is longer than 2^36 - 32 bytes"),
+            Self::TooShort => write!(f, "ciphertext is shorter than the tag"),
+            Self::AuthenticationFailed => write!(f, "tag verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for GcmError {}
+
+/// Encrypt and authenticate `plaintext`, and authenticate `associated_data`,
+/// returning the ciphertext followed by a `tag_len`-byte tag
+pub fn gcm_encrypt(
+    associated_data: &[u8],
+    plaintext: &[u8],
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, GcmError> {
+    check_params(nonce, tag_len)?;
+    if plaintext.len() as u64 > MAX_PLAINTEXT_LEN {
+        return Err(GcmError::InputTooLong);
+    }
+
+    let h = cipher_block(&[0; 16], expanded_key, key_type);
+    let j_0 = initial_counter(&h, nonce);
+
+    let mut result = gctr(plaintext, &increment32(&j_0), expanded_key, key_type);
+    let tag = compute_tag(&h, &j_0, associated_data, &result, expanded_key, key_type);
+    result.extend(&tag[..tag_len]);
+
+    Ok(result)
+}
+
+/// Check the tag at the end of `ciphertext` and decrypt the rest
+pub fn gcm_decrypt(
+    associated_data: &[u8],
+    ciphertext: &[u8],
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, GcmError> {
+    check_params(nonce, tag_len)?;
+    if ciphertext.len() < tag_len {
+        return Err(GcmError::TooShort);
+    }
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - tag_len);
+    if ciphertext.len() as u64 > MAX_PLAINTEXT_LEN {
+        return Err(GcmError::InputTooLong);
+    }
+
+    let h = cipher_block(&[0; 16], expanded_key, key_type);
+    let j_0 = initial_counter(&h, nonce);
+
+    let expected = compute_tag(
+        &h,
+        &j_0,
+        associated_data,
+        ciphertext,
+        expanded_key,
+        key_type,
+    );
+    if !constant_time_eq(&expected[..tag_len], tag) {
+        return Err(GcmError::AuthenticationFailed);
+    }
+
+    Ok(gctr(ciphertext, &increment32(&j_0), expanded_key, key_type))
+}
+
+/// The GHASH function over `data`, which must be a whole number of blocks
+pub fn ghash(h: &[u8; 16], data: &[u8]) -> [u8; 16] {
+    let h = u128::from_be_bytes(*h);
+
+    let y = data.chunks_exact(16).fold(0, |y, block| {
+        mul(y ^ u128::from_be_bytes(block.try_into().unwrap()), h)
+    });
+
+    y.to_be_bytes()
+}
+
+fn check_params(nonce: &[u8], tag_len: usize) -> Result<(), GcmError> {
+    if nonce.is_empty() {
+        return Err(GcmError::EmptyNonce);
+    }
+    if !matches!(tag_len, 4 | 8 | 12..=16) {
+        return Err(GcmError::InvalidTagLength(tag_len));
+    }
+    Ok(())
+}
+
+/// The pre-counter block `J_0`
+fn initial_counter(h: &[u8; 16], nonce: &[u8]) -> [u8; 16] {
+    if nonce.len() == NONCE_LEN {
+        let mut j_0 = [0; 16];
+        j_0[..NONCE_LEN].copy_from_slice(nonce);
+        j_0[15] = 1;
+        return j_0;
+    }
+
+    let mut input = nonce.to_vec();
+    pad_to_block(&mut input);
+    input.extend([0; 8]);
+    input.extend((nonce.len() as u64 * 8).to_be_bytes());
+    ghash(h, &input)
+}
+
+fn compute_tag(
+    h: &[u8; 16],
+    j_0: &[u8; 16],
+    associated_data: &[u8],
+    ciphertext: &[u8],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut input = associated_data.to_vec();
+    pad_to_block(&mut input);
+    input.extend(ciphertext);
+    pad_to_block(&mut input);
+    input.extend((associated_data.len() as u64 * 8).to_be_bytes());
+    input.extend((ciphertext.len() as u64 * 8).to_be_bytes());
+
+    let mut tag = ghash(h, &input);
+    xor_in_place(&mut tag, &cipher_block(j_0, expanded_key, key_type));
+    tag
+}
+
+/// CTR mode where only the last 32 bits of the counter block are incremented
+fn gctr(input: &[u8], counter: &[u8; 16], expanded_key: &[u32], key_type: KeyType) -> Vec<u8> {
+    let mut counter = *counter;
+    let mut result = input.to_vec();
+
+    for chunk in result.chunks_mut(16) {
+        xor_in_place(chunk, &cipher_block(&counter, expanded_key, key_type));
+        counter = increment32(&counter);
+    }
+
+    result
+}
+
+fn increment32(counter: &[u8; 16]) -> [u8; 16] {
+    let mut result = *counter;
+    let low = u32::from_be_bytes(counter[12..].try_into().unwrap());
+    result[12..].copy_from_slice(&low.wrapping_add(1).to_be_bytes());
+    result
+}
+
+/// Multiply two elements of GHASH's field, where the most significant bit
+/// holds the coefficient of `x^0` (SP 800-38D Algorithm 1)
+fn mul(x: u128, y: u128) -> u128 {
+    // 1 + x + x^2 + x^7, the low terms of the field polynomial, reflected
+    const R: u128 = 0xe1 << 120;
+
+    (0..128)
+        .fold((0, y), |(z, v), i| {
+            let z = if (x >> (127 - i)) & 1 == 1 { z ^ v } else { z };
+
+            // multiply by x, reducing if the x^127 coefficient falls off
+            let v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+            (z, v)
+        })
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::decode_hex;
+    use crate::key_expansion::key_expansion;
+
+    fn hex(text: &str) -> Vec<u8> {
+        decode_hex(text).unwrap()
+    }
+
+    fn check_vector(key: &str, nonce: &str, aad: &str, plaintext: &str, expected: &str) {
+        let key = hex(key);
+        let key_type = KeyType::from_key_len(key.len()).unwrap();
+        let expanded_key = key_expansion(&key, key_type);
+        let (nonce, aad, plaintext, expected) =
+            (hex(nonce), hex(aad), hex(plaintext), hex(expected));
+
+        let result = gcm_encrypt(&aad, &plaintext, &nonce, 16, &expanded_key, key_type);
+        assert_eq!(result.unwrap(), expected);
+
+        let result = gcm_decrypt(&aad, &expected, &nonce, 16, &expanded_key, key_type);
+        assert_eq!(result.unwrap(), plaintext);
+    }
+
+    // Test cases from "The Galois/Counter Mode of Operation (GCM)" by McGrew
+    // and Viega, appendix B
+
+    const KEY: &str = "feffe9928665731c6d6a8f9467308308";
+    const PLAINTEXT: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39";
+    const AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+
+    #[test]
+    fn empty_test() {
+        // test cases 1 and 2
+        let key = "00000000000000000000000000000000";
+        let nonce = "000000000000000000000000";
+        check_vector(key, nonce, "", "", "58e2fccefa7e3061367f1d57a4e7455a");
+        check_vector(
+            key,
+            nonce,
+            "",
+            "00000000000000000000000000000000",
+            "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf",
+        );
+    }
+
+    #[test]
+    fn aes_128_test() {
+        // test case 4
+        check_vector(
+            KEY,
+            "cafebabefacedbaddecaf888",
+            AAD,
+            PLAINTEXT,
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+             21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091\
+             5bc94fbc3221a5db94fae95ae7121a47",
+        );
+    }
+
+    #[test]
+    fn long_nonce_test() {
+        // test cases 5 and 6, with 8- and 60-byte nonces
+        check_vector(
+            KEY,
+            "cafebabefacedbad",
+            AAD,
+            PLAINTEXT,
+            "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+             73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598\
+             3612d2e79e3b0785561be14aaca2fccb",
+        );
+        check_vector(
+            KEY,
+            "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+             c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+            AAD,
+            PLAINTEXT,
+            "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+             01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5\
+             619cc5aefffe0bfa462af43c1699d050",
+        );
+    }
+
+    #[test]
+    fn aes_192_and_256_test() {
+        // test cases 10 and 16
+        check_vector(
+            "feffe9928665731c6d6a8f9467308308feffe9928665731c",
+            "cafebabefacedbaddecaf888",
+            AAD,
+            PLAINTEXT,
+            "3980ca0b3c00e841eb06fac4872a2757859e1ceaa6efd984628593b40ca1e19c\
+             7d773d00c144c525ac619d18c84a3f4718e2448b2fe324d9ccda2710\
+             2519498e80f1478f37ba55bd6d27618c",
+        );
+        check_vector(
+            "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+            "cafebabefacedbaddecaf888",
+            AAD,
+            PLAINTEXT,
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+             8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662\
+             76fc6ece0f4e1768cddf8853bb2d551b",
+        );
+    }
+
+    #[test]
+    fn tag_test() {
+        let expanded_key = key_expansion(&hex(KEY), KeyType::Aes128);
+        let nonce = [0x11; NONCE_LEN];
+
+        let full = gcm_encrypt(b"aad", b"hello", &nonce, 16, &expanded_key, KeyType::Aes128);
+        let full = full.unwrap();
+        let short = gcm_encrypt(b"aad", b"hello", &nonce, 12, &expanded_key, KeyType::Aes128);
+        assert_eq!(short.unwrap(), full[..5 + 12]);
+
+        for tag_len in [0, 5, 11, 17] {
+            assert_eq!(
+                gcm_encrypt(b"", b"", &nonce, tag_len, &expanded_key, KeyType::Aes128),
+                Err(GcmError::InvalidTagLength(tag_len))
+            );
+        }
+        assert_eq!(
+            gcm_encrypt(b"", b"", &[], 16, &expanded_key, KeyType::Aes128),
+            Err(GcmError::EmptyNonce)
+        );
+
+        let mut forged = full.clone();
+        forged[0] ^= 1;
+        assert_eq!(
+            gcm_decrypt(b"aad", &forged, &nonce, 16, &expanded_key, KeyType::Aes128),
+            Err(GcmError::AuthenticationFailed)
+        );
+        assert_eq!(
+            gcm_decrypt(b"aae", &full, &nonce, 16, &expanded_key, KeyType::Aes128),
+            Err(GcmError::AuthenticationFailed)
+        );
+        assert_eq!(
+            gcm_decrypt(
+                b"aad",
+                &full[..15],
+                &nonce,
+                16,
+                &expanded_key,
+                KeyType::Aes128
+            ),
+            Err(GcmError::TooShort)
+        );
+    }
+}
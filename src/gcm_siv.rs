@@ -0,0 +1,379 @@
+//! AES-GCM-SIV nonce-misuse-resistant authenticated encryption (RFC 8452)
+//!
+//! Every nonce derives a fresh authentication key and encryption key from the
+//! key-generating key. The tag is computed with POLYVAL over the associated
+//! data and plaintext, and then used as the initial counter block, so repeating
+//! a nonce only reveals whether the same message was encrypted twice.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::key_expansion::key_expansion;
+use crate::util::{constant_time_eq, pad_to_block, xor_in_place};
+
+/// Length of the nonce, in bytes
+pub const NONCE_LEN: usize = 12;
+
+/// The longest plaintext or associated data allowed, in bytes (2^36)
+pub const MAX_INPUT_LEN: u64 = 1 << 36;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GcmSivError {
+    /// AES-GCM-SIV is only defined for 128- and 256-bit keys
+    UnsupportedKeyType,
+    /// The plaintext or associated data is longer than [`MAX_INPUT_LEN`]
+    InputTooLong,
+    /// The ciphertext is shorter than the tag
+    TooShort,
+    /// The tag didn't match the associated data and decrypted plaintext
+    AuthenticationFailed,
+}
+
+impl Display for GcmSivError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedKeyType => write!(f, "AES-GCM-SIV requires a 128- or 256-bit key"),
+            Self::InputTooLong => write!(f, "input is longer than 2^36 bytes"),
+            Self::TooShort => write!(f, "ciphertext is shorter than the tag"),
+            Self::AuthenticationFailed => write!(f, "tag verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for GcmSivError {}
+
+/// Encrypt `plaintext`, returning the ciphertext followed by the 16-byte tag
+///
+/// `expanded_key` is the expanded key-generating key.
+pub fn gcm_siv_encrypt(
+    associated_data: &[u8],
+    plaintext: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, GcmSivError> {
+    check_inputs(associated_data, plaintext, key_type)?;
+
+    let (auth_key, encryption_key) = derive_keys(nonce, expanded_key, key_type);
+    let tag = compute_tag(
+        associated_data,
+        plaintext,
+        nonce,
+        &auth_key,
+        &encryption_key,
+        key_type,
+    );
+
+    let mut result = ctr32(plaintext, &tag, &encryption_key, key_type);
+    result.extend(tag);
+    Ok(result)
+}
+
+/// Decrypt a ciphertext produced by [`gcm_siv_encrypt`], verifying its tag
+pub fn gcm_siv_decrypt(
+    associated_data: &[u8],
+    ciphertext: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, GcmSivError> {
+    let ciphertext_len = ciphertext
+        .len()
+        .checked_sub(16)
+        .ok_or(GcmSivError::TooShort)?;
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext_len);
+    check_inputs(associated_data, ciphertext, key_type)?;
+
+    let (auth_key, encryption_key) = derive_keys(nonce, expanded_key, key_type);
+    let tag: [u8; 16] = tag.try_into().unwrap();
+    let plaintext = ctr32(ciphertext, &tag, &encryption_key, key_type);
+
+    let expected_tag = compute_tag(
+        associated_data,
+        &plaintext,
+        nonce,
+        &auth_key,
+        &encryption_key,
+        key_type,
+    );
+    if !constant_time_eq(&expected_tag, &tag) {
+        return Err(GcmSivError::AuthenticationFailed);
+    }
+
+    Ok(plaintext)
+}
+
+/// The POLYVAL universal hash of `data`, which is zero-padded to a whole
+/// number of blocks
+pub fn polyval(h: &[u8; 16], data: &[u8]) -> [u8; 16] {
+    let h = u128::from_le_bytes(*h);
+
+    let s = data.chunks(16).fold(0, |s, chunk| {
+        let mut block = [0; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        dot(s ^ u128::from_le_bytes(block), h)
+    });
+
+    s.to_le_bytes()
+}
+
+/// Multiply two elements of POLYVAL's field and then by `x^-128`, where the
+/// least significant bit holds the coefficient of `x^0`
+fn dot(a: u128, b: u128) -> u128 {
+    // x^127 + x^126 + x^121 + 1, the low terms of the field polynomial
+    const POLY: u128 = (1 << 127) | (1 << 126) | (1 << 121) | 1;
+
+    (0..128).fold(0, |result, i| {
+        let result = if (b >> i) & 1 == 1 {
+            result ^ a
+        } else {
+            result
+        };
+
+        // divide by x, first adding the polynomial if needed to clear x^0
+        if result & 1 == 1 {
+            ((result ^ POLY) >> 1) | (1 << 127)
+        } else {
+            result >> 1
+        }
+    })
+}
+
+fn check_inputs(associated_data: &[u8], text: &[u8], key_type: KeyType) -> Result<(), GcmSivError> {
+    if matches!(key_type, KeyType::Aes192) {
+        return Err(GcmSivError::UnsupportedKeyType);
+    }
+    if associated_data.len() as u64 > MAX_INPUT_LEN || text.len() as u64 > MAX_INPUT_LEN {
+        return Err(GcmSivError::InputTooLong);
+    }
+    Ok(())
+}
+
+/// Derive the per-nonce message authentication key and the expanded message
+/// encryption key from the key-generating key
+fn derive_keys(
+    nonce: &[u8; NONCE_LEN],
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> ([u8; 16], Vec<u32>) {
+    let num_blocks = 2 + key_type.n_k() / 2;
+
+    let derived: Vec<u8> = (0..num_blocks as u32)
+        .flat_map(|i| {
+            let mut block = [0; 16];
+            block[..4].copy_from_slice(&i.to_le_bytes());
+            block[4..].copy_from_slice(nonce);
+            cipher_block(&block, expanded_key, key_type)[..8].to_vec()
+        })
+        .collect();
+
+    let auth_key = derived[..16].try_into().unwrap();
+    let encryption_key = key_expansion(&derived[16..], key_type);
+    (auth_key, encryption_key)
+}
+
+fn compute_tag(
+    associated_data: &[u8],
+    plaintext: &[u8],
+    nonce: &[u8; NONCE_LEN],
+    auth_key: &[u8; 16],
+    encryption_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut input = associated_data.to_vec();
+    pad_to_block(&mut input);
+    input.extend(plaintext);
+    pad_to_block(&mut input);
+    input.extend((associated_data.len() as u64 * 8).to_le_bytes());
+    input.extend((plaintext.len() as u64 * 8).to_le_bytes());
+
+    let mut s = polyval(auth_key, &input);
+    xor_in_place(&mut s, nonce);
+    s[15] &= 0x7f;
+
+    cipher_block(&s, encryption_key, key_type)
+}
+
+/// CTR mode where only the first 32 bits of the counter block are incremented,
+/// as a little-endian integer that wraps without carrying into the rest
+fn ctr32(input: &[u8], tag: &[u8; 16], encryption_key: &[u32], key_type: KeyType) -> Vec<u8> {
+    let mut counter = *tag;
+    counter[15] |= 0x80;
+
+    let mut result = input.to_vec();
+    for chunk in result.chunks_mut(16) {
+        xor_in_place(chunk, &cipher_block(&counter, encryption_key, key_type));
+        counter = increment32(&counter);
+    }
+
+    result
+}
+
+fn increment32(counter: &[u8; 16]) -> [u8; 16] {
+    let mut result = *counter;
+    let low = u32::from_le_bytes(counter[..4].try_into().unwrap());
+    result[..4].copy_from_slice(&low.wrapping_add(1).to_le_bytes());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from RFC 8452 Appendix A and C
+
+    const NONCE: [u8; NONCE_LEN] = [0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    fn check_vector(key: &[u8], key_type: KeyType, aad: &[u8], plaintext: &[u8], expected: &[u8]) {
+        let expanded_key = key_expansion(key, key_type);
+
+        let result = gcm_siv_encrypt(aad, plaintext, &NONCE, &expanded_key, key_type).unwrap();
+        assert_eq!(result, expected);
+
+        let result = gcm_siv_decrypt(aad, expected, &NONCE, &expanded_key, key_type).unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    /// Build an RFC 8452 style input out of little-endian counters
+    fn blocks(values: &[u8], len: usize) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| {
+                let mut block = vec![0; len];
+                block[0] = *value;
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn polyval_test() {
+        let h = [
+            0x25, 0x62, 0x93, 0x47, 0x58, 0x92, 0x42, 0x76, 0x1d, 0x31, 0xf8, 0x26, 0xba, 0x4b,
+            0x75, 0x7b,
+        ];
+        let x = [
+            0x4f, 0x4f, 0x95, 0x66, 0x8c, 0x83, 0xdf, 0xb6, 0x40, 0x17, 0x62, 0xbb, 0x2d, 0x01,
+            0xa2, 0x62, 0xd1, 0xa2, 0x4d, 0xdd, 0x27, 0x21, 0xd0, 0x06, 0xbb, 0xe4, 0x5f, 0x20,
+            0xd3, 0xc9, 0xf3, 0x62,
+        ];
+        let expected = [
+            0xf7, 0xa3, 0xb4, 0x7b, 0x84, 0x61, 0x19, 0xfa, 0xe5, 0xb7, 0x86, 0x6c, 0xf5, 0xe5,
+            0xb7, 0x7e,
+        ];
+
+        assert_eq!(polyval(&h, &x), expected);
+    }
+
+    #[test]
+    fn aes_128_test() {
+        let mut key = [0; 16];
+        key[0] = 0x01;
+
+        let expected = [
+            0xdc, 0x20, 0xe2, 0xd8, 0x3f, 0x25, 0x70, 0x5b, 0xb4, 0x9e, 0x43, 0x9e, 0xca, 0x56,
+            0xde, 0x25,
+        ];
+        check_vector(&key, KeyType::Aes128, &[], &[], &expected);
+
+        let expected = [
+            0xb5, 0xd8, 0x39, 0x33, 0x0a, 0xc7, 0xb7, 0x86, 0x57, 0x87, 0x82, 0xff, 0xf6, 0x01,
+            0x3b, 0x81, 0x5b, 0x28, 0x7c, 0x22, 0x49, 0x3a, 0x36, 0x4c,
+        ];
+        check_vector(&key, KeyType::Aes128, &[], &blocks(&[1], 8), &expected);
+
+        let expected = [
+            0x84, 0xe0, 0x7e, 0x62, 0xba, 0x83, 0xa6, 0x58, 0x54, 0x17, 0x24, 0x5d, 0x7e, 0xc4,
+            0x13, 0xa9, 0xfe, 0x42, 0x7d, 0x63, 0x15, 0xc0, 0x9b, 0x57, 0xce, 0x45, 0xf2, 0xe3,
+            0x93, 0x6a, 0x94, 0x45, 0x1a, 0x8e, 0x45, 0xdc, 0xd4, 0x57, 0x8c, 0x66, 0x7c, 0xd8,
+            0x68, 0x47, 0xbf, 0x61, 0x55, 0xff,
+        ];
+        check_vector(&key, KeyType::Aes128, &[], &blocks(&[1, 2], 16), &expected);
+
+        let expected = [
+            0x1e, 0x6d, 0xab, 0xa3, 0x56, 0x69, 0xf4, 0x27, 0x3b, 0x0a, 0x1a, 0x25, 0x60, 0x96,
+            0x9c, 0xdf, 0x79, 0x0d, 0x99, 0x75, 0x9a, 0xbd, 0x15, 0x08,
+        ];
+        check_vector(&key, KeyType::Aes128, &[0x01], &blocks(&[2], 8), &expected);
+
+        let expected = [
+            0x50, 0xc8, 0x30, 0x3e, 0xa9, 0x39, 0x25, 0xd6, 0x40, 0x90, 0xd0, 0x7b, 0xd1, 0x09,
+            0xdf, 0xd9, 0x51, 0x5a, 0x5a, 0x33, 0x43, 0x10, 0x19, 0xc1, 0x7d, 0x93, 0x46, 0x59,
+            0x99, 0xa8, 0xb0, 0x05, 0x32, 0x01, 0xd7, 0x23, 0x12, 0x0a, 0x85, 0x62, 0xb8, 0x38,
+            0xcd, 0xff, 0x25, 0xbf, 0x9d, 0x1e, 0x6a, 0x8c, 0xc3, 0x86, 0x5f, 0x76, 0x89, 0x7c,
+            0x2e, 0x4b, 0x24, 0x5c, 0xf3, 0x1c, 0x51, 0xf2,
+        ];
+        check_vector(
+            &key,
+            KeyType::Aes128,
+            &[0x01],
+            &blocks(&[2, 3, 4], 16),
+            &expected,
+        );
+    }
+
+    #[test]
+    fn aes_256_test() {
+        let mut key = [0; 32];
+        key[0] = 0x01;
+
+        let expected = [
+            0x07, 0xf5, 0xf4, 0x16, 0x9b, 0xbf, 0x55, 0xa8, 0x40, 0x0c, 0xd4, 0x7e, 0xa6, 0xfd,
+            0x40, 0x0f,
+        ];
+        check_vector(&key, KeyType::Aes256, &[], &[], &expected);
+
+        let expected = [
+            0xc2, 0xef, 0x32, 0x8e, 0x5c, 0x71, 0xc8, 0x3b, 0x84, 0x31, 0x22, 0x13, 0x0f, 0x73,
+            0x64, 0xb7, 0x61, 0xe0, 0xb9, 0x74, 0x27, 0xe3, 0xdf, 0x28,
+        ];
+        check_vector(&key, KeyType::Aes256, &[], &blocks(&[1], 8), &expected);
+
+        let expected = [
+            0x07, 0xda, 0xd3, 0x64, 0xbf, 0xc2, 0xb9, 0xda, 0x89, 0x11, 0x6d, 0x7b, 0xef, 0x6d,
+            0xaa, 0xaf, 0x6f, 0x25, 0x55, 0x10, 0xaa, 0x65, 0x4f, 0x92, 0x0a, 0xc8, 0x1b, 0x94,
+            0xe8, 0xba, 0xd3, 0x65, 0xae, 0xa1, 0xba, 0xd1, 0x27, 0x02, 0xe1, 0x96, 0x56, 0x04,
+            0x37, 0x4a, 0xab, 0x96, 0xdb, 0xbc,
+        ];
+        check_vector(
+            &key,
+            KeyType::Aes256,
+            &[0x01],
+            &blocks(&[2, 3], 16),
+            &expected,
+        );
+    }
+
+    #[test]
+    fn increment32_test() {
+        let mut counter = [0xff; 16];
+        counter[3] = 0xfe;
+        let mut expected = [0xff; 16];
+        expected[..4].copy_from_slice(&[0x00, 0x00, 0x00, 0xff]);
+        assert_eq!(increment32(&counter), expected);
+
+        // the counter wraps without carrying into the rest of the block
+        let mut expected = [0xff; 16];
+        expected[..4].copy_from_slice(&[0x00; 4]);
+        assert_eq!(increment32(&[0xff; 16]), expected);
+    }
+
+    #[test]
+    fn errors_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+        let mut ciphertext =
+            gcm_siv_encrypt(b"aad", b"message", &NONCE, &expanded_key, KeyType::Aes128).unwrap();
+        ciphertext[0] ^= 1;
+
+        let result = gcm_siv_decrypt(b"aad", &ciphertext, &NONCE, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(GcmSivError::AuthenticationFailed));
+
+        let result = gcm_siv_decrypt(b"aad", &[0; 15], &NONCE, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(GcmSivError::TooShort));
+
+        let expanded_key = key_expansion(&[0; 24], KeyType::Aes192);
+        let result = gcm_siv_encrypt(b"", b"", &NONCE, &expanded_key, KeyType::Aes192);
+        assert_eq!(result, Err(GcmSivError::UnsupportedKeyType));
+    }
+}
@@ -0,0 +1,104 @@
+//! HMAC message authentication with SHA-256 (FIPS 198-1 / RFC 2104)
+//!
+//! Messages can be authenticated in one call with [`hmac_sha256`], or
+//! incrementally with [`HmacSha256`] when they arrive in pieces.
+
+use crate::sha256::{sha256, Sha256, BLOCK_LEN, DIGEST_LEN};
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+/// An in-progress HMAC-SHA-256 computation
+#[derive(Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    /// Start a computation under `key`, which may be any length (keys longer
+    /// than a block are hashed first)
+    pub fn new(key: &[u8]) -> Self {
+        let mut block_key = [0; BLOCK_LEN];
+        if key.len() > BLOCK_LEN {
+            block_key[..DIGEST_LEN].copy_from_slice(&sha256(key));
+        } else {
+            block_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha256::new();
+        inner.update(&block_key.map(|byte| byte ^ IPAD));
+        let mut outer = Sha256::new();
+        outer.update(&block_key.map(|byte| byte ^ OPAD));
+
+        Self { inner, outer }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub fn finalize(self) -> [u8; DIGEST_LEN] {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+}
+
+/// Authenticate `message` under `key` in one call
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut mac = HmacSha256::new(key);
+    mac.update(message);
+    mac.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{decode_hex, encode_hex};
+
+    #[test]
+    fn hmac_sha256_test() {
+        // test cases 1, 2, 3, 6 and 7 from RFC 4231
+        let examples: [(Vec<u8>, Vec<u8>, &str); 5] = [
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than \
+                  block-size data. The key needs to be hashed before being used by the \
+                  HMAC algorithm."
+                    .to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+
+        for (key, message, tag) in examples {
+            assert_eq!(encode_hex(&hmac_sha256(&key, &message)), tag);
+
+            let mut mac = HmacSha256::new(&key);
+            for chunk in message.chunks(7) {
+                mac.update(chunk);
+            }
+            assert_eq!(mac.finalize().to_vec(), decode_hex(tag).unwrap());
+        }
+    }
+}
@@ -93,7 +93,7 @@ pub fn inverse_cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: K
 
     let state = input
         .as_ref()
-        .into_iter()
+        .iter()
         .enumerate()
         .fold(vec![], |mut result, (i, value)| {
             if i / n_b == 0 {
@@ -111,7 +111,7 @@ pub fn inverse_cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: K
 
     let state = add_round_key(state, key_sched);
 
-    let state = (1..n_r).into_iter().fold(state, |state, round| {
+    let state = (1..n_r).fold(state, |state, round| {
         debug!(print_state(round, Step::IStart, &state));
         
         let state = shift_rows(state);
@@ -126,9 +126,7 @@ pub fn inverse_cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: K
         let state = add_round_key(state, key_sched);
         debug!(print_state(round, Step::IAddRoundKey, &state));
 
-        let state = mix_columns(state);
-
-        state
+        mix_columns(state)
     });
 
     let round = n_r;
@@ -148,8 +146,8 @@ pub fn inverse_cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: K
 
     let mut result = Vec::with_capacity(state.len() * state[0].len());
     for i in 0..state[0].len() {
-        for j in 0..state.len() {
-            result.push(state[j][i]);
+        for row in &state {
+            result.push(row[i]);
         }
     }
 
@@ -159,6 +157,13 @@ pub fn inverse_cipher(input: impl AsRef<[u8]>, expanded_key: &[u32], key_type: K
     result
 }
 
+/// Decrypt a single block, returning it as an array instead of a `Vec`
+pub fn inverse_cipher_block(input: &[u8; 16], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    inverse_cipher(input, expanded_key, key_type)
+        .try_into()
+        .expect("the inverse cipher always outputs a single block")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
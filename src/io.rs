@@ -0,0 +1,393 @@
+//! [`Read`] and [`Write`] adapters over the streaming modes
+//!
+//! [`EncryptingWriter`] encrypts everything written to it before passing it
+//! on, and [`DecryptingReader`] decrypts everything read through it, so
+//! existing I/O code can work with files or sockets without buffering whole
+//! messages itself.
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::stream::{Decryptor, Encryptor, StreamError};
+
+/// How much is read from the inner reader at a time
+const CHUNK_SIZE: usize = 8192;
+
+fn to_io_error(error: StreamError) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// Encrypts everything written to it with `E` and writes the ciphertext to `W`
+///
+/// [`EncryptingWriter::finish`] must be called once everything has been
+/// written, to add the final block and any padding or tag. Dropping the
+/// writer without finishing it leaves the ciphertext incomplete.
+pub struct EncryptingWriter<W: Write, E: Encryptor> {
+    inner: W,
+    encryptor: E,
+    /// Ciphertext that hasn't been written to the inner writer yet
+    pending: Vec<u8>,
+    position: usize,
+    /// Why writing `pending` failed after its input was taken, to be
+    /// returned from the next call
+    error: Option<io::Error>,
+}
+
+impl<W: Write, E: Encryptor> EncryptingWriter<W, E> {
+    pub fn new(inner: W, encryptor: E) -> Self {
+        Self {
+            inner,
+            encryptor,
+            pending: Vec::new(),
+            position: 0,
+            error: None,
+        }
+    }
+
+    /// Write the rest of the ciphertext, flush it and return the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.take_error()?;
+        self.write_pending()?;
+        let last = self.encryptor.finalize().map_err(to_io_error)?;
+        self.inner.write_all(&last)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Return the failure kept from an earlier write, if there was one
+    fn take_error(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Write out `pending`, keeping track of how much got through so a
+    /// failed write can be retried without losing or repeating ciphertext
+    fn write_pending(&mut self) -> io::Result<()> {
+        while self.position < self.pending.len() {
+            match self.inner.write(&self.pending[self.position..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => self.position += len,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write, E: Encryptor> Write for EncryptingWriter<W, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // ciphertext left over from an earlier call goes first, and no new
+        // input is taken until it's out
+        self.take_error()?;
+        self.write_pending()?;
+
+        self.pending = self.encryptor.update(buf);
+        self.position = 0;
+
+        // the encryptor has taken all of `buf`, so a failure now is kept for
+        // the next call to report, with the ciphertext still pending
+        if let Err(error) = self.write_pending() {
+            self.error = Some(error);
+        }
+        Ok(buf.len())
+    }
+
+    /// Write any pending ciphertext and flush the inner writer
+    ///
+    /// A partial block can't be encrypted until more input arrives or the
+    /// writer is finished, so it stays buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        self.take_error()?;
+        self.write_pending()?;
+        self.inner.flush()
+    }
+}
+
+/// Reads ciphertext from `R` and decrypts it with `D`
+///
+/// Padding or a tag is checked when the inner reader reaches the end, and a
+/// failure is returned as an error of kind [`ErrorKind::InvalidData`] wrapping
+/// a [`StreamError`], and again on every later read. With an authenticated
+/// mode, nothing read should be acted on until the reader has returned
+/// `Ok(0)`.
+pub struct DecryptingReader<R: Read, D: Decryptor> {
+    inner: R,
+    /// `None` once the inner reader has ended and the decryptor is finalized
+    decryptor: Option<D>,
+    /// Why finalizing failed, if it did
+    error: Option<StreamError>,
+    /// Plaintext that hasn't been read yet
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read, D: Decryptor> DecryptingReader<R, D> {
+    pub fn new(inner: R, decryptor: D) -> Self {
+        Self {
+            inner,
+            decryptor: Some(decryptor),
+            error: None,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Refill `pending`, returning `false` at the end of the plaintext
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; CHUNK_SIZE];
+
+        // a read that only completes part of a block gives no output, so keep
+        // reading until there's some plaintext or the input ends
+        while self.position == self.pending.len() {
+            let Some(decryptor) = &mut self.decryptor else {
+                // a failed check mustn't look like the end of the plaintext
+                // to whoever reads next
+                return match &self.error {
+                    Some(error) => Err(to_io_error(*error)),
+                    None => Ok(false),
+                };
+            };
+
+            let len = self.inner.read(&mut chunk)?;
+            self.pending = if len == 0 {
+                let decryptor = self.decryptor.take().unwrap();
+                decryptor.finalize().map_err(|error| {
+                    self.error = Some(error);
+                    to_io_error(error)
+                })?
+            } else {
+                decryptor.update(&chunk[..len])
+            };
+            self.position = 0;
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read, D: Decryptor> Read for DecryptingReader<R, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || !self.fill()? {
+            return Ok(0);
+        }
+
+        let available = &self.pending[self.position..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbc::{cbc_encrypt, CbcDecryptor, CbcEncryptor};
+    use crate::constant::KeyType;
+    use crate::key_expansion::key_expansion;
+    use crate::ocb::{OcbDecryptor, OcbEncryptor};
+    use crate::padding::Pkcs7;
+
+    /// Returns at most `max` bytes from each read, and is interrupted before
+    /// every other read
+    struct ShortReader<'a> {
+        data: &'a [u8],
+        max: usize,
+        interrupt: bool,
+    }
+
+    impl Read for ShortReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(ErrorKind::Interrupted.into());
+            }
+
+            let len = self.data.len().min(buf.len()).min(self.max);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    /// Accepts at most `max` bytes from each write, and fails every other
+    /// write until it has failed `failures` times
+    struct FlakyWriter {
+        data: Vec<u8>,
+        max: usize,
+        failures: usize,
+        fail: bool,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.fail = !self.fail;
+            if self.fail && self.failures > 0 {
+                self.failures -= 1;
+                return Err(io::Error::other("write failed"));
+            }
+
+            let len = buf.len().min(self.max);
+            self.data.extend(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn cbc_test() {
+        let expanded_key = key_expansion(&[0x61; 16], KeyType::Aes128);
+        let iv = [0x07; 16];
+        let plaintext: Vec<u8> = (0..=255).cycle().take(20000).collect();
+
+        let encryptor = CbcEncryptor::new(&iv, &Pkcs7, &expanded_key, KeyType::Aes128);
+        let mut writer = EncryptingWriter::new(Vec::new(), encryptor);
+        for chunk in plaintext.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+            writer.flush().unwrap();
+        }
+        let ciphertext = writer.finish().unwrap();
+        assert_eq!(
+            ciphertext,
+            cbc_encrypt(&plaintext, &iv, &Pkcs7, &expanded_key, KeyType::Aes128)
+        );
+
+        let reader = ShortReader {
+            data: &ciphertext,
+            max: 5,
+            interrupt: false,
+        };
+        let decryptor = CbcDecryptor::new(&iv, &Pkcs7, &expanded_key, KeyType::Aes128);
+        let mut result = Vec::new();
+        DecryptingReader::new(reader, decryptor)
+            .read_to_end(&mut result)
+            .unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn failing_writer_test() {
+        let expanded_key = key_expansion(&[0x63; 16], KeyType::Aes128);
+        let iv = [0x08; 16];
+        let plaintext: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        let inner = FlakyWriter {
+            data: Vec::new(),
+            max: 7,
+            failures: 50,
+            fail: false,
+        };
+        let encryptor = CbcEncryptor::new(&iv, &Pkcs7, &expanded_key, KeyType::Aes128);
+        let mut writer = EncryptingWriter::new(inner, encryptor);
+
+        // retry whatever fails, as a caller would after a transient error
+        let mut errors = 0;
+        for chunk in plaintext.chunks(100) {
+            let mut written = 0;
+            while written < chunk.len() {
+                match writer.write(&chunk[written..]) {
+                    Ok(len) => written += len,
+                    Err(_) => errors += 1,
+                }
+            }
+        }
+        while writer.flush().is_err() {
+            errors += 1;
+        }
+        assert!(errors > 0);
+
+        let ciphertext = writer.finish().unwrap().data;
+        assert_eq!(
+            ciphertext,
+            cbc_encrypt(&plaintext, &iv, &Pkcs7, &expanded_key, KeyType::Aes128)
+        );
+    }
+
+    #[test]
+    fn single_failure_test() {
+        let expanded_key = key_expansion(&[0x64; 16], KeyType::Aes128);
+        let iv = [0x09; 16];
+        let plaintext: Vec<u8> = (0..=255).cycle().take(100).collect();
+
+        let inner = FlakyWriter {
+            data: Vec::new(),
+            max: usize::MAX,
+            failures: 1,
+            fail: false,
+        };
+        let encryptor = CbcEncryptor::new(&iv, &Pkcs7, &expanded_key, KeyType::Aes128);
+        let mut writer = EncryptingWriter::new(inner, encryptor);
+
+        // the input is taken even though its ciphertext couldn't be written
+        assert_eq!(writer.write(&plaintext[..50]).unwrap(), 50);
+        assert!(writer.get_ref().data.is_empty());
+
+        // the inner writer has recovered, but the failure is still reported
+        let error = writer.write(&plaintext[50..]).unwrap_err();
+        assert_eq!(error.to_string(), "write failed");
+        assert!(writer.get_ref().data.is_empty());
+
+        // and only once
+        writer.write_all(&plaintext[50..]).unwrap();
+        let ciphertext = writer.finish().unwrap().data;
+        assert_eq!(
+            ciphertext,
+            cbc_encrypt(&plaintext, &iv, &Pkcs7, &expanded_key, KeyType::Aes128)
+        );
+    }
+
+    #[test]
+    fn authentication_test() {
+        let expanded_key = key_expansion(&[0x62; 32], KeyType::Aes256);
+        let nonce = [0x01; 12];
+        let plaintext = b"a message that is longer than a single block";
+
+        let encryptor = OcbEncryptor::new(b"", &nonce, 16, &expanded_key, KeyType::Aes256).unwrap();
+        let mut writer = EncryptingWriter::new(Vec::new(), encryptor);
+        writer.write_all(plaintext).unwrap();
+        let mut ciphertext = writer.finish().unwrap();
+
+        let decryptor = OcbDecryptor::new(b"", &nonce, 16, &expanded_key, KeyType::Aes256).unwrap();
+        let mut result = Vec::new();
+        DecryptingReader::new(&ciphertext[..], decryptor)
+            .read_to_end(&mut result)
+            .unwrap();
+        assert_eq!(result, plaintext);
+
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 0x01;
+        let decryptor = OcbDecryptor::new(b"", &nonce, 16, &expanded_key, KeyType::Aes256).unwrap();
+        let mut reader = DecryptingReader::new(&ciphertext[..], decryptor);
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().unwrap().downcast_ref::<StreamError>(),
+            Some(&StreamError::AuthenticationFailed)
+        );
+
+        // reading again reports the same failure rather than a clean end
+        let error = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            error.get_ref().unwrap().downcast_ref::<StreamError>(),
+            Some(&StreamError::AuthenticationFailed)
+        );
+    }
+}
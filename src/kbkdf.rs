@@ -0,0 +1,484 @@
+//! Key-based key derivation with CMAC as the PRF (NIST SP 800-108)
+//!
+//! Each mode calls the PRF once per block of output. The PRF input is the
+//! fixed input data (usually a label, a separator byte, a context and the
+//! output length, see [`fixed_input_data`]) along with a block counter and,
+//! in the feedback and double-pipeline modes, a chaining value:
+//!
+//! - Counter mode: `K(i) = PRF(KI, [i] || fixed)`
+//! - Feedback mode: `K(i) = PRF(KI, K(i-1) || [i] || fixed)`, with `K(0) = IV`
+//! - Double-pipeline mode: `A(i) = PRF(KI, A(i-1))` with `A(0) = fixed`, and
+//!   `K(i) = PRF(KI, A(i) || [i] || fixed)`
+//!
+//! The counter's width and position are configurable with [`Counter`], and it
+//! may be left out entirely in the feedback and double-pipeline modes.
+
+use std::fmt::Display;
+
+use crate::cmac::cmac;
+use crate::constant::KeyType;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KdfError {
+    /// Counters must be between 1 and 4 bytes
+    InvalidCounterLength(usize),
+    /// A middle counter position was past the end of the fixed input data
+    InvalidCounterPosition(usize),
+    /// More output was requested than the counter can number blocks for, or
+    /// than a 32-bit length in bits can describe
+    OutputTooLong(usize),
+}
+
+impl Display for KdfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCounterLength(len) => write!(f, "invalid counter length {len}"),
+            Self::InvalidCounterPosition(position) => {
+                write!(
+                    f,
+                    "counter position {position} is outside the fixed input data"
+                )
+            }
+            Self::OutputTooLong(len) => write!(f, "output of {len} bytes is too long"),
+        }
+    }
+}
+
+impl std::error::Error for KdfError {}
+
+/// Where the counter goes in the PRF input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterLocation {
+    /// Before the chaining value (the same as [`Self::BeforeFixed`] in
+    /// counter mode, which has no chaining value)
+    BeforeIteration,
+    /// Between the chaining value and the fixed input data
+    BeforeFixed,
+    /// After this many bytes of the fixed input data
+    MiddleFixed(usize),
+    /// After the fixed input data
+    AfterFixed,
+}
+
+/// A big-endian block counter, starting at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counter {
+    /// The width of the counter in bytes, from 1 to 4
+    pub len: usize,
+    pub location: CounterLocation,
+}
+
+impl Counter {
+    /// The 32-bit counter before the fixed input data that SP 800-108
+    /// describes by default
+    pub const DEFAULT: Self = Self {
+        len: 4,
+        location: CounterLocation::BeforeFixed,
+    };
+}
+
+/// Build the fixed input data `label || 0x00 || context || [L]_32`, where `L`
+/// is the output length in bits. Fails if `L` doesn't fit in 32 bits.
+pub fn fixed_input_data(
+    label: &[u8],
+    context: &[u8],
+    output_len: usize,
+) -> Result<Vec<u8>, KdfError> {
+    let bits = u32::try_from(output_len)
+        .ok()
+        .and_then(|len| len.checked_mul(8))
+        .ok_or(KdfError::OutputTooLong(output_len))?;
+
+    let mut fixed = Vec::with_capacity(label.len() + context.len() + 5);
+    fixed.extend(label);
+    fixed.push(0x00);
+    fixed.extend(context);
+    fixed.extend(bits.to_be_bytes());
+    Ok(fixed)
+}
+
+/// Derive `output_len` bytes in counter mode
+pub fn kdf_counter(
+    counter: Counter,
+    fixed_input: &[u8],
+    output_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KdfError> {
+    derive(
+        Some(counter),
+        fixed_input,
+        output_len,
+        expanded_key,
+        key_type,
+        |_| Vec::new(),
+    )
+}
+
+/// Derive `output_len` bytes in feedback mode, chaining from `iv` (which may
+/// be empty)
+pub fn kdf_feedback(
+    iv: &[u8],
+    counter: Option<Counter>,
+    fixed_input: &[u8],
+    output_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KdfError> {
+    let mut previous = iv.to_vec();
+
+    derive(
+        counter,
+        fixed_input,
+        output_len,
+        expanded_key,
+        key_type,
+        |last_block| {
+            if let Some(block) = last_block {
+                previous = block.to_vec();
+            }
+            previous.clone()
+        },
+    )
+}
+
+/// Derive `output_len` bytes in double-pipeline mode
+pub fn kdf_double_pipeline(
+    counter: Option<Counter>,
+    fixed_input: &[u8],
+    output_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KdfError> {
+    let mut a = fixed_input.to_vec();
+
+    derive(
+        counter,
+        fixed_input,
+        output_len,
+        expanded_key,
+        key_type,
+        |_| {
+            a = cmac(&a, expanded_key, key_type).to_vec();
+            a.clone()
+        },
+    )
+}
+
+/// The loop shared by every mode. `chaining_value` is given the previous
+/// output block (if any) and returns the value that precedes the counter and
+/// fixed input data in the PRF input.
+fn derive(
+    counter: Option<Counter>,
+    fixed_input: &[u8],
+    output_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+    mut chaining_value: impl FnMut(Option<&[u8; 16]>) -> Vec<u8>,
+) -> Result<Vec<u8>, KdfError> {
+    let block_count = output_len.div_ceil(16);
+
+    let max_blocks = match counter {
+        Some(Counter { len, .. }) if !(1..=4).contains(&len) => {
+            return Err(KdfError::InvalidCounterLength(len));
+        }
+        Some(Counter { len, .. }) => (1u64 << (len * 8)) - 1,
+        None => u32::MAX as u64,
+    };
+    if block_count as u64 > max_blocks {
+        return Err(KdfError::OutputTooLong(output_len));
+    }
+    if let Some(Counter {
+        location: CounterLocation::MiddleFixed(position),
+        ..
+    }) = counter
+    {
+        if position > fixed_input.len() {
+            return Err(KdfError::InvalidCounterPosition(position));
+        }
+    }
+
+    let mut result = Vec::with_capacity(block_count * 16);
+    let mut last_block = None;
+
+    for i in 1..=block_count as u32 {
+        let mut input = chaining_value(last_block.as_ref());
+
+        match counter {
+            None => input.extend(fixed_input),
+            Some(Counter { len, location }) => {
+                let counter = &i.to_be_bytes()[4 - len..];
+
+                match location {
+                    CounterLocation::BeforeIteration => {
+                        input.splice(0..0, counter.iter().copied());
+                        input.extend(fixed_input);
+                    }
+                    CounterLocation::BeforeFixed => {
+                        input.extend(counter);
+                        input.extend(fixed_input);
+                    }
+                    CounterLocation::MiddleFixed(position) => {
+                        input.extend(&fixed_input[..position]);
+                        input.extend(counter);
+                        input.extend(&fixed_input[position..]);
+                    }
+                    CounterLocation::AfterFixed => {
+                        input.extend(fixed_input);
+                        input.extend(counter);
+                    }
+                }
+            }
+        }
+
+        let block = cmac(&input, expanded_key, key_type);
+        result.extend(block);
+        last_block = Some(block);
+    }
+
+    result.truncate(output_len);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    #[derive(Clone, Copy)]
+    enum Mode {
+        Counter,
+        Feedback,
+        DoublePipeline,
+    }
+
+    fn parse_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Run every CMAC-AES case in a KBKDFVS response file, returning how many
+    /// there were
+    fn run_vectors(mode: Mode, vectors: &str) -> usize {
+        let mut key_type = None;
+        let mut location = None;
+        let mut counter_len = 0;
+
+        let mut output_len = 0;
+        let mut key = Vec::new();
+        let mut iv = Vec::new();
+        let mut fixed_input = Vec::new();
+        let mut cases_run = 0;
+
+        for line in vectors.lines().map(str::trim) {
+            if let Some(section) = line.strip_prefix('[') {
+                let Some((name, value)) = section.trim_end_matches(']').split_once('=') else {
+                    continue;
+                };
+                match name {
+                    "PRF" => {
+                        // each PRF starts a new set of sections
+                        key_type = match value {
+                            "CMAC_AES128" => Some(KeyType::Aes128),
+                            "CMAC_AES192" => Some(KeyType::Aes192),
+                            "CMAC_AES256" => Some(KeyType::Aes256),
+                            _ => None,
+                        };
+                        location = None;
+                    }
+                    "CTRLOCATION" => {
+                        location = Some(match value {
+                            "BEFORE_ITER" => CounterLocation::BeforeIteration,
+                            "BEFORE_FIXED" | "AFTER_ITER" => CounterLocation::BeforeFixed,
+                            "MIDDLE_FIXED" => CounterLocation::MiddleFixed(0),
+                            "AFTER_FIXED" => CounterLocation::AfterFixed,
+                            other => panic!("unknown counter location {other}"),
+                        })
+                    }
+                    "RLEN" => {
+                        counter_len = value.trim_end_matches("_BITS").parse::<usize>().unwrap() / 8
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            let Some(key_type) = key_type else {
+                continue;
+            };
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match name.trim() {
+                "COUNT" => {
+                    iv.clear();
+                    fixed_input.clear();
+                }
+                "L" => output_len = value.parse::<usize>().unwrap() / 8,
+                "KI" => key = parse_hex(value),
+                "IV" => iv = parse_hex(value),
+                "FixedInputData" | "DataAfterCtrData" => fixed_input.extend(parse_hex(value)),
+                "DataBeforeCtrData" => {
+                    fixed_input = parse_hex(value);
+                    location = Some(CounterLocation::MiddleFixed(fixed_input.len()));
+                }
+                "KO" => {
+                    let expanded_key = key_expansion(&key, key_type);
+                    let counter = location.map(|location| Counter {
+                        len: counter_len,
+                        location,
+                    });
+
+                    let result = match mode {
+                        Mode::Counter => kdf_counter(
+                            counter.unwrap(),
+                            &fixed_input,
+                            output_len,
+                            &expanded_key,
+                            key_type,
+                        ),
+                        Mode::Feedback => kdf_feedback(
+                            &iv,
+                            counter,
+                            &fixed_input,
+                            output_len,
+                            &expanded_key,
+                            key_type,
+                        ),
+                        Mode::DoublePipeline => kdf_double_pipeline(
+                            counter,
+                            &fixed_input,
+                            output_len,
+                            &expanded_key,
+                            key_type,
+                        ),
+                    };
+                    assert_eq!(result.unwrap(), parse_hex(value));
+                    cases_run += 1;
+                }
+                _ => {}
+            }
+        }
+
+        cases_run
+    }
+
+    #[test]
+    fn cavp_counter_test() {
+        // a CMAC_AES128 case from NIST's counter-mode KBKDFVS response file
+        let vectors = "[PRF=CMAC_AES128]\n\
+                       [CTRLOCATION=BEFORE_FIXED]\n\
+                       [RLEN=8_BITS]\n\
+                       COUNT=0\n\
+                       L = 128\n\
+                       KI = dff1e50ac0b69dc40f1051d46c2b069c\n\
+                       FixedInputDataByteLen = 60\n\
+                       FixedInputData = c16e6e02c5a3dcc8d78b9ac1306877761310455b4e41469951d9e6c2245a\
+                       064b33fd8c3b01203a7824485bf0a64060c4648b707d2607935699316ea5\n\
+                       KO = 8be8f0869b3c0ba97b71863d1b9f7813";
+        assert_eq!(run_vectors(Mode::Counter, vectors), 1);
+    }
+
+    #[test]
+    fn counter_test() {
+        let vectors = include_str!("../test_vectors/kbkdf_counter_computed.txt");
+        assert_eq!(run_vectors(Mode::Counter, vectors), 72);
+    }
+
+    #[test]
+    fn feedback_test() {
+        let vectors = include_str!("../test_vectors/kbkdf_feedback_computed.txt");
+        assert_eq!(run_vectors(Mode::Feedback, vectors), 42);
+    }
+
+    #[test]
+    fn double_pipeline_test() {
+        let vectors = include_str!("../test_vectors/kbkdf_double_pipeline_computed.txt");
+        assert_eq!(run_vectors(Mode::DoublePipeline, vectors), 42);
+    }
+
+    #[test]
+    fn other_prfs_test() {
+        // official response files also carry HMAC and TDES sections
+        let vectors = "[PRF=HMAC_SHA1]\n\
+                       [CTRLOCATION=BEFORE_FIXED]\n\
+                       [RLEN=8_BITS]\n\
+                       COUNT=0\n\
+                       L = 128\n\
+                       KI = 00112233445566778899aabbccddeeff00112233\n\
+                       KO = 00\n\
+                       [PRF=CMAC_AES128]\n\
+                       [CTRLOCATION=BEFORE_FIXED]\n\
+                       [RLEN=8_BITS]\n\
+                       COUNT=0\n\
+                       L = 128\n\
+                       KI = f52eb2fa4ce726e3941d270d7d03ea5e\n\
+                       FixedInputDataByteLen = 60\n\
+                       FixedInputData = 6481fa549afc088a54b8ddf3e600552b6c80b6256c587b65554a2c9e72b3\
+                       7216c5d68c51df77103a976753276044d71a19c619eac3933333f1b5cac5\n\
+                       KO = 3965aeb076353484c30c59384683b0ce";
+        assert_eq!(run_vectors(Mode::Counter, vectors), 1);
+    }
+
+    #[test]
+    fn fixed_input_data_test() {
+        let expanded_key = key_expansion(&[0x0f; 16], KeyType::Aes128);
+        let fixed = fixed_input_data(b"session", b"client-1", 32).unwrap();
+        assert_eq!(fixed, b"session\0client-1\x00\x00\x01\x00");
+
+        let key =
+            kdf_counter(Counter::DEFAULT, &fixed, 32, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(key.len(), 32);
+
+        // a different context gives an unrelated key
+        let fixed = fixed_input_data(b"session", b"client-2", 32).unwrap();
+        let other =
+            kdf_counter(Counter::DEFAULT, &fixed, 32, &expanded_key, KeyType::Aes128).unwrap();
+        assert_ne!(key, other);
+
+        // L is a 32-bit count of bits, so it stops just short of 2^29 bytes
+        let max_len = (u32::MAX / 8) as usize;
+        let fixed = fixed_input_data(b"", b"", max_len).unwrap();
+        assert_eq!(fixed, [0x00, 0xff, 0xff, 0xff, 0xf8]);
+        assert_eq!(
+            fixed_input_data(b"", b"", max_len + 1),
+            Err(KdfError::OutputTooLong(max_len + 1))
+        );
+        assert_eq!(
+            fixed_input_data(b"", b"", usize::MAX),
+            Err(KdfError::OutputTooLong(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn errors_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+
+        let counter = Counter {
+            len: 5,
+            location: CounterLocation::BeforeFixed,
+        };
+        let result = kdf_counter(counter, b"", 16, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(KdfError::InvalidCounterLength(5)));
+
+        // an 8-bit counter can number 255 blocks
+        let counter = Counter {
+            len: 1,
+            location: CounterLocation::AfterFixed,
+        };
+        assert!(kdf_counter(counter, b"", 255 * 16, &expanded_key, KeyType::Aes128).is_ok());
+        let result = kdf_counter(counter, b"", 255 * 16 + 1, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(KdfError::OutputTooLong(255 * 16 + 1)));
+
+        let counter = Counter {
+            len: 4,
+            location: CounterLocation::MiddleFixed(4),
+        };
+        let result = kdf_double_pipeline(Some(counter), b"abc", 16, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(KdfError::InvalidCounterPosition(4)));
+    }
+}
@@ -0,0 +1,333 @@
+//! AES Key Wrap (RFC 3394) and Key Wrap with Padding (RFC 5649)
+//!
+//! Key wrap encrypts key material under a key-encryption key (KEK), with an
+//! integrity check value (ICV) in place of a tag. Plain key wrap needs at least
+//! two 64-bit blocks of key data, while the padded variant takes any non-empty
+//! length and records it in the alternative initial value.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::util::constant_time_eq;
+
+/// The default initial value from RFC 3394 section 2.2.3.1
+const DEFAULT_IV: [u8; 8] = [0xa6; 8];
+
+/// The constant half of the alternative initial value from RFC 5649 section 3
+const ALTERNATIVE_IV_PREFIX: [u8; 4] = [0xa6, 0x59, 0x59, 0xa6];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyWrapError {
+    /// The key data or wrapped key isn't a length the algorithm accepts
+    InvalidLength(usize),
+    /// The unwrapped integrity check value didn't match, so the wrapped key was
+    /// corrupted or the wrong KEK was used
+    IntegrityCheckFailed,
+}
+
+impl Display for KeyWrapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(f, "invalid input length {len}"),
+            Self::IntegrityCheckFailed => write!(f, "integrity check value mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for KeyWrapError {}
+
+/// Wrap `key_data`, which must be a multiple of 8 bytes and at least 16 bytes
+pub fn key_wrap(
+    key_data: &[u8],
+    expanded_kek: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KeyWrapError> {
+    if key_data.len() < 16 || !key_data.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidLength(key_data.len()));
+    }
+
+    Ok(wrap(&DEFAULT_IV, key_data, expanded_kek, key_type))
+}
+
+/// Unwrap a key wrapped by [`key_wrap`], checking its integrity check value
+pub fn key_unwrap(
+    wrapped: &[u8],
+    expanded_kek: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KeyWrapError> {
+    if wrapped.len() < 24 || !wrapped.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidLength(wrapped.len()));
+    }
+
+    let (iv, key_data) = unwrap(wrapped, expanded_kek, key_type);
+    if !constant_time_eq(&iv, &DEFAULT_IV) {
+        return Err(KeyWrapError::IntegrityCheckFailed);
+    }
+
+    Ok(key_data)
+}
+
+/// Wrap `key_data` of any non-empty length, padding it with zeros
+pub fn key_wrap_padded(
+    key_data: &[u8],
+    expanded_kek: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KeyWrapError> {
+    let len =
+        u32::try_from(key_data.len()).map_err(|_| KeyWrapError::InvalidLength(key_data.len()))?;
+    if len == 0 {
+        return Err(KeyWrapError::InvalidLength(0));
+    }
+
+    let mut iv = [0; 8];
+    iv[..4].copy_from_slice(&ALTERNATIVE_IV_PREFIX);
+    iv[4..].copy_from_slice(&len.to_be_bytes());
+
+    let mut padded = key_data.to_vec();
+    padded.resize(key_data.len().div_ceil(8) * 8, 0);
+
+    if padded.len() == 8 {
+        // a single block is encrypted directly, along with the initial value
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&iv);
+        block[8..].copy_from_slice(&padded);
+        return Ok(cipher_block(&block, expanded_kek, key_type).to_vec());
+    }
+
+    Ok(wrap(&iv, &padded, expanded_kek, key_type))
+}
+
+/// Unwrap a key wrapped by [`key_wrap_padded`], checking its integrity check
+/// value, length and padding
+pub fn key_unwrap_padded(
+    wrapped: &[u8],
+    expanded_kek: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, KeyWrapError> {
+    if wrapped.len() < 16 || !wrapped.len().is_multiple_of(8) {
+        return Err(KeyWrapError::InvalidLength(wrapped.len()));
+    }
+
+    let (iv, mut padded) = if wrapped.len() == 16 {
+        let block = inverse_cipher_block(wrapped.try_into().unwrap(), expanded_kek, key_type);
+        (block[..8].try_into().unwrap(), block[8..].to_vec())
+    } else {
+        unwrap(wrapped, expanded_kek, key_type)
+    };
+
+    if !constant_time_eq(&iv[..4], &ALTERNATIVE_IV_PREFIX) {
+        return Err(KeyWrapError::IntegrityCheckFailed);
+    }
+
+    let len = u32::from_be_bytes(iv[4..].try_into().unwrap()) as usize;
+    if len > padded.len() || len + 8 <= padded.len() {
+        return Err(KeyWrapError::IntegrityCheckFailed);
+    }
+    if !constant_time_eq(&padded[len..], &[0; 8][..padded.len() - len]) {
+        return Err(KeyWrapError::IntegrityCheckFailed);
+    }
+
+    padded.truncate(len);
+    Ok(padded)
+}
+
+/// The wrapping process `W` from RFC 3394 section 2.2.1, using the index-based
+/// formulation
+fn wrap(iv: &[u8; 8], key_data: &[u8], expanded_kek: &[u32], key_type: KeyType) -> Vec<u8> {
+    let mut a = *iv;
+    let mut r: Vec<[u8; 8]> = key_data
+        .chunks_exact(8)
+        .map(|block| block.try_into().unwrap())
+        .collect();
+    let n = r.len();
+
+    for j in 0..6 {
+        for (i, r_i) in r.iter_mut().enumerate() {
+            let mut block = [0; 16];
+            block[..8].copy_from_slice(&a);
+            block[8..].copy_from_slice(r_i);
+            let b = cipher_block(&block, expanded_kek, key_type);
+
+            let t = (n * j + i + 1) as u64;
+            a = (u64::from_be_bytes(b[..8].try_into().unwrap()) ^ t).to_be_bytes();
+            r_i.copy_from_slice(&b[8..]);
+        }
+    }
+
+    let mut result = a.to_vec();
+    result.extend(r.concat());
+    result
+}
+
+/// The unwrapping process `W^-1` from RFC 3394 section 2.2.2, which returns
+/// the recovered initial value for the caller to check
+fn unwrap(wrapped: &[u8], expanded_kek: &[u32], key_type: KeyType) -> ([u8; 8], Vec<u8>) {
+    let mut a: [u8; 8] = wrapped[..8].try_into().unwrap();
+    let mut r: Vec<[u8; 8]> = wrapped[8..]
+        .chunks_exact(8)
+        .map(|block| block.try_into().unwrap())
+        .collect();
+    let n = r.len();
+
+    for j in (0..6).rev() {
+        for (i, r_i) in r.iter_mut().enumerate().rev() {
+            let t = (n * j + i + 1) as u64;
+
+            let mut block = [0; 16];
+            block[..8].copy_from_slice(&(u64::from_be_bytes(a) ^ t).to_be_bytes());
+            block[8..].copy_from_slice(r_i);
+            let b = inverse_cipher_block(&block, expanded_kek, key_type);
+
+            a.copy_from_slice(&b[..8]);
+            r_i.copy_from_slice(&b[8..]);
+        }
+    }
+
+    (a, r.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    const KEY_DATA: [u8; 32] = [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        0xff, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+        0x0e, 0x0f,
+    ];
+
+    fn check_vector(key_type: KeyType, key_data: &[u8], expected: &[u8]) {
+        let kek: Vec<u8> = (0..key_type.n_k() as u8 * 4).collect();
+        let expanded_kek = key_expansion(&kek, key_type);
+
+        let result = key_wrap(key_data, &expanded_kek, key_type).unwrap();
+        assert_eq!(result, expected);
+
+        let result = key_unwrap(expected, &expanded_kek, key_type).unwrap();
+        assert_eq!(result, key_data);
+    }
+
+    #[test]
+    fn rfc_3394_test() {
+        // 4.1 to 4.6 from RFC 3394
+        let expected = [
+            0x1f, 0xa6, 0x8b, 0x0a, 0x81, 0x12, 0xb4, 0x47, 0xae, 0xf3, 0x4b, 0xd8, 0xfb, 0x5a,
+            0x7b, 0x82, 0x9d, 0x3e, 0x86, 0x23, 0x71, 0xd2, 0xcf, 0xe5,
+        ];
+        check_vector(KeyType::Aes128, &KEY_DATA[..16], &expected);
+
+        let expected = [
+            0x96, 0x77, 0x8b, 0x25, 0xae, 0x6c, 0xa4, 0x35, 0xf9, 0x2b, 0x5b, 0x97, 0xc0, 0x50,
+            0xae, 0xd2, 0x46, 0x8a, 0xb8, 0xa1, 0x7a, 0xd8, 0x4e, 0x5d,
+        ];
+        check_vector(KeyType::Aes192, &KEY_DATA[..16], &expected);
+
+        let expected = [
+            0x64, 0xe8, 0xc3, 0xf9, 0xce, 0x0f, 0x5b, 0xa2, 0x63, 0xe9, 0x77, 0x79, 0x05, 0x81,
+            0x8a, 0x2a, 0x93, 0xc8, 0x19, 0x1e, 0x7d, 0x6e, 0x8a, 0xe7,
+        ];
+        check_vector(KeyType::Aes256, &KEY_DATA[..16], &expected);
+
+        let expected = [
+            0x03, 0x1d, 0x33, 0x26, 0x4e, 0x15, 0xd3, 0x32, 0x68, 0xf2, 0x4e, 0xc2, 0x60, 0x74,
+            0x3e, 0xdc, 0xe1, 0xc6, 0xc7, 0xdd, 0xee, 0x72, 0x5a, 0x93, 0x6b, 0xa8, 0x14, 0x91,
+            0x5c, 0x67, 0x62, 0xd2,
+        ];
+        check_vector(KeyType::Aes192, &KEY_DATA[..24], &expected);
+
+        let expected = [
+            0xa8, 0xf9, 0xbc, 0x16, 0x12, 0xc6, 0x8b, 0x3f, 0xf6, 0xe6, 0xf4, 0xfb, 0xe3, 0x0e,
+            0x71, 0xe4, 0x76, 0x9c, 0x8b, 0x80, 0xa3, 0x2c, 0xb8, 0x95, 0x8c, 0xd5, 0xd1, 0x7d,
+            0x6b, 0x25, 0x4d, 0xa1,
+        ];
+        check_vector(KeyType::Aes256, &KEY_DATA[..24], &expected);
+
+        let expected = [
+            0x28, 0xc9, 0xf4, 0x04, 0xc4, 0xb8, 0x10, 0xf4, 0xcb, 0xcc, 0xb3, 0x5c, 0xfb, 0x87,
+            0xf8, 0x26, 0x3f, 0x57, 0x86, 0xe2, 0xd8, 0x0e, 0xd3, 0x26, 0xcb, 0xc7, 0xf0, 0xe7,
+            0x1a, 0x99, 0xf4, 0x3b, 0xfb, 0x98, 0x8b, 0x9b, 0x7a, 0x02, 0xdd, 0x21,
+        ];
+        check_vector(KeyType::Aes256, &KEY_DATA, &expected);
+    }
+
+    #[test]
+    fn rfc_5649_test() {
+        // section 6 of RFC 5649
+        let kek = [
+            0x58, 0x40, 0xdf, 0x6e, 0x29, 0xb0, 0x2a, 0xf1, 0xab, 0x49, 0x3b, 0x70, 0x5b, 0xf1,
+            0x6e, 0xa1, 0xae, 0x83, 0x38, 0xf4, 0xdc, 0xc1, 0x76, 0xa8,
+        ];
+        let expanded_kek = key_expansion(&kek, KeyType::Aes192);
+
+        let key_data = [
+            0xc3, 0x7b, 0x7e, 0x64, 0x92, 0x58, 0x43, 0x40, 0xbe, 0xd1, 0x22, 0x07, 0x80, 0x89,
+            0x41, 0x15, 0x50, 0x68, 0xf7, 0x38,
+        ];
+        let expected = [
+            0x13, 0x8b, 0xde, 0xaa, 0x9b, 0x8f, 0xa7, 0xfc, 0x61, 0xf9, 0x77, 0x42, 0xe7, 0x22,
+            0x48, 0xee, 0x5a, 0xe6, 0xae, 0x53, 0x60, 0xd1, 0xae, 0x6a, 0x5f, 0x54, 0xf3, 0x73,
+            0xfa, 0x54, 0x3b, 0x6a,
+        ];
+        let result = key_wrap_padded(&key_data, &expanded_kek, KeyType::Aes192).unwrap();
+        assert_eq!(result, expected);
+        let result = key_unwrap_padded(&expected, &expanded_kek, KeyType::Aes192).unwrap();
+        assert_eq!(result, key_data);
+
+        let key_data = [0x46, 0x6f, 0x72, 0x50, 0x61, 0x73, 0x69];
+        let expected = [
+            0xaf, 0xbe, 0xb0, 0xf0, 0x7d, 0xfb, 0xf5, 0x41, 0x92, 0x00, 0xf2, 0xcc, 0xb5, 0x0b,
+            0xb2, 0x4f,
+        ];
+        let result = key_wrap_padded(&key_data, &expanded_kek, KeyType::Aes192).unwrap();
+        assert_eq!(result, expected);
+        let result = key_unwrap_padded(&expected, &expanded_kek, KeyType::Aes192).unwrap();
+        assert_eq!(result, key_data);
+    }
+
+    #[test]
+    fn integrity_check_test() {
+        let kek: Vec<u8> = (0..16).collect();
+        let expanded_kek = key_expansion(&kek, KeyType::Aes128);
+        let other_kek = key_expansion(&[0; 16], KeyType::Aes128);
+
+        let mut wrapped = key_wrap(&KEY_DATA, &expanded_kek, KeyType::Aes128).unwrap();
+        let result = key_unwrap(&wrapped, &other_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::IntegrityCheckFailed));
+
+        wrapped[10] ^= 1;
+        let result = key_unwrap(&wrapped, &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::IntegrityCheckFailed));
+
+        // a wrapped key can't be unwrapped with the other variant
+        let wrapped = key_wrap(&KEY_DATA, &expanded_kek, KeyType::Aes128).unwrap();
+        let result = key_unwrap_padded(&wrapped, &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::IntegrityCheckFailed));
+
+        let wrapped = key_wrap_padded(&KEY_DATA[..5], &expanded_kek, KeyType::Aes128).unwrap();
+        let result = key_unwrap_padded(&wrapped, &other_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::IntegrityCheckFailed));
+    }
+
+    #[test]
+    fn invalid_length_test() {
+        let expanded_kek = key_expansion(&[0; 16], KeyType::Aes128);
+
+        let result = key_wrap(&KEY_DATA[..8], &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::InvalidLength(8)));
+
+        let result = key_wrap(&KEY_DATA[..20], &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::InvalidLength(20)));
+
+        let result = key_unwrap(&KEY_DATA[..16], &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::InvalidLength(16)));
+
+        let result = key_wrap_padded(&[], &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::InvalidLength(0)));
+
+        let result = key_unwrap_padded(&KEY_DATA[..20], &expanded_kek, KeyType::Aes128);
+        assert_eq!(result, Err(KeyWrapError::InvalidLength(20)));
+    }
+}
@@ -0,0 +1,36 @@
+pub mod block;
+pub mod cbc;
+pub mod cbc_cs;
+pub mod cbc_mac;
+pub mod ccm_star;
+pub mod cipher;
+pub mod cmac;
+pub mod constant;
+pub mod container;
+pub mod ctr;
+pub mod ctr_drbg;
+pub mod debug;
+pub mod eax;
+pub mod ecb;
+pub mod encoding;
+pub mod finite_field;
+pub mod gcm;
+pub mod gcm_siv;
+pub mod hmac;
+pub mod inverse_cipher;
+pub mod io;
+pub mod kbkdf;
+pub mod key_expansion;
+pub mod key_wrap;
+pub mod md5;
+pub mod ocb;
+pub mod openssl;
+pub mod padding;
+pub mod pbkdf2;
+pub mod pmac;
+pub mod sha256;
+pub mod siv;
+pub mod stream;
+pub mod xcbc;
+pub mod xts;
+mod util;
@@ -0,0 +1,705 @@
+//! OCB3 authenticated encryption (RFC 7253)
+//!
+//! OCB encrypts and authenticates in a single pass over the plaintext. Each
+//! block is masked with an offset that changes by one of the precomputed
+//! `L_i = 2^i * L_$` values, chosen by the number of trailing zeros of the
+//! block index.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::finite_field::dbl;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+use crate::util::{constant_time_eq, xor_in_place};
+
+/// The longest nonce allowed, in bytes (120 bits)
+pub const MAX_NONCE_LEN: usize = 15;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OcbError {
+    /// Nonces can be at most [`MAX_NONCE_LEN`] bytes
+    InvalidNonceLength(usize),
+    /// Tags must be between 1 and 16 bytes
+    InvalidTagLength(usize),
+    /// The ciphertext is shorter than the tag
+    TooShort,
+    /// The tag didn't match the associated data and decrypted plaintext
+    AuthenticationFailed,
+}
+
+impl Display for OcbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidNonceLength(len) => write!(f, "invalid nonce length {len}"),
+            Self::InvalidTagLength(len) => write!(f, "invalid tag length {len}"),
+            Self::TooShort => write!(f, "ciphertext is shorter than the tag"),
+            Self::AuthenticationFailed => write!(f, "tag verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for OcbError {}
+
+/// The key-dependent values `L_*`, `L_$` and `L_0, L_1, ...`
+struct Offsets {
+    l_star: [u8; 16],
+    l_dollar: [u8; 16],
+    l: Vec<[u8; 16]>,
+}
+
+impl Offsets {
+    /// Precompute enough of the table for inputs of up to `max_blocks` blocks
+    fn new(max_blocks: usize, expanded_key: &[u32], key_type: KeyType) -> Self {
+        let l_star = cipher_block(&[0; 16], expanded_key, key_type);
+        let l_dollar = dbl(&l_star);
+
+        let table_len = (usize::BITS - max_blocks.leading_zeros()).max(1) as usize;
+        let l = std::iter::successors(Some(dbl(&l_dollar)), |l_i| Some(dbl(l_i)))
+            .take(table_len)
+            .collect();
+
+        Self {
+            l_star,
+            l_dollar,
+            l,
+        }
+    }
+
+    /// The value `L_{ntz(i)}` that is added to the offset for block `i`
+    fn for_block(&self, i: usize) -> &[u8; 16] {
+        &self.l[i.trailing_zeros() as usize]
+    }
+}
+
+/// Encrypt `plaintext`, returning the ciphertext followed by a `tag_len`-byte tag
+///
+/// RFC 7253 defines tags of 16, 12 and 8 bytes, but any length from 1 to 16
+/// bytes is accepted.
+pub fn ocb_encrypt(
+    associated_data: &[u8],
+    plaintext: &[u8],
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, OcbError> {
+    check_params(nonce, tag_len)?;
+
+    let max_blocks = associated_data.len().max(plaintext.len()) / 16;
+    let offsets = Offsets::new(max_blocks, expanded_key, key_type);
+
+    let mut offset = initial_offset(nonce, tag_len, expanded_key, key_type);
+    let mut checksum = [0; 16];
+    let mut result = Vec::with_capacity(plaintext.len() + tag_len);
+
+    let mut blocks = plaintext.chunks_exact(16);
+    for (i, block) in (1..).zip(&mut blocks) {
+        xor_in_place(&mut offset, offsets.for_block(i));
+        xor_in_place(&mut checksum, block);
+
+        let mut input: [u8; 16] = block.try_into().unwrap();
+        xor_in_place(&mut input, &offset);
+        let mut output = cipher_block(&input, expanded_key, key_type);
+        xor_in_place(&mut output, &offset);
+
+        result.extend(output);
+    }
+
+    let last = blocks.remainder();
+    if !last.is_empty() {
+        xor_in_place(&mut offset, &offsets.l_star);
+        let pad = cipher_block(&offset, expanded_key, key_type);
+
+        let mut output = last.to_vec();
+        xor_in_place(&mut output, &pad);
+        result.extend(output);
+
+        xor_in_place(&mut checksum, &pad_last(last));
+    }
+
+    let tag = compute_tag(
+        &checksum,
+        &offset,
+        associated_data,
+        &offsets,
+        expanded_key,
+        key_type,
+    );
+    result.extend(&tag[..tag_len]);
+
+    Ok(result)
+}
+
+/// Decrypt a ciphertext produced by [`ocb_encrypt`], verifying its tag
+pub fn ocb_decrypt(
+    associated_data: &[u8],
+    ciphertext: &[u8],
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, OcbError> {
+    check_params(nonce, tag_len)?;
+
+    let ciphertext_len = ciphertext
+        .len()
+        .checked_sub(tag_len)
+        .ok_or(OcbError::TooShort)?;
+    let (ciphertext, tag) = ciphertext.split_at(ciphertext_len);
+
+    let max_blocks = associated_data.len().max(ciphertext.len()) / 16;
+    let offsets = Offsets::new(max_blocks, expanded_key, key_type);
+
+    let mut offset = initial_offset(nonce, tag_len, expanded_key, key_type);
+    let mut checksum = [0; 16];
+    let mut result = Vec::with_capacity(ciphertext.len());
+
+    let mut blocks = ciphertext.chunks_exact(16);
+    for (i, block) in (1..).zip(&mut blocks) {
+        xor_in_place(&mut offset, offsets.for_block(i));
+
+        let mut input: [u8; 16] = block.try_into().unwrap();
+        xor_in_place(&mut input, &offset);
+        let mut output = inverse_cipher_block(&input, expanded_key, key_type);
+        xor_in_place(&mut output, &offset);
+
+        xor_in_place(&mut checksum, &output);
+        result.extend(output);
+    }
+
+    let last = blocks.remainder();
+    if !last.is_empty() {
+        xor_in_place(&mut offset, &offsets.l_star);
+        let pad = cipher_block(&offset, expanded_key, key_type);
+
+        let mut output = last.to_vec();
+        xor_in_place(&mut output, &pad);
+
+        xor_in_place(&mut checksum, &pad_last(&output));
+        result.extend(output);
+    }
+
+    let expected_tag = compute_tag(
+        &checksum,
+        &offset,
+        associated_data,
+        &offsets,
+        expanded_key,
+        key_type,
+    );
+    if !constant_time_eq(&expected_tag[..tag_len], tag) {
+        return Err(OcbError::AuthenticationFailed);
+    }
+
+    Ok(result)
+}
+
+/// The running state shared by [`OcbEncryptor`] and [`OcbDecryptor`]
+struct State<'a> {
+    associated_data: &'a [u8],
+    tag_len: usize,
+    expanded_key: &'a [u32],
+    key_type: KeyType,
+    offsets: Offsets,
+    offset: [u8; 16],
+    checksum: [u8; 16],
+    /// The number of whole blocks processed so far
+    blocks: usize,
+    buffer: Buffer,
+}
+
+impl<'a> State<'a> {
+    fn new(
+        associated_data: &'a [u8],
+        nonce: &[u8],
+        tag_len: usize,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, OcbError> {
+        check_params(nonce, tag_len)?;
+
+        // the length of the message isn't known, so precompute the whole table
+        Ok(Self {
+            associated_data,
+            tag_len,
+            expanded_key,
+            key_type,
+            offsets: Offsets::new(usize::MAX, expanded_key, key_type),
+            offset: initial_offset(nonce, tag_len, expanded_key, key_type),
+            checksum: [0; 16],
+            blocks: 0,
+            buffer: Buffer::default(),
+        })
+    }
+
+    /// Encrypt or decrypt whole blocks in place, updating the checksum with
+    /// the plaintext
+    fn process_blocks(&mut self, blocks: &mut [u8], decrypt: bool) {
+        for block in blocks.chunks_exact_mut(16) {
+            self.blocks += 1;
+            xor_in_place(&mut self.offset, self.offsets.for_block(self.blocks));
+            if !decrypt {
+                xor_in_place(&mut self.checksum, block);
+            }
+
+            let mut input: [u8; 16] = block.try_into().unwrap();
+            xor_in_place(&mut input, &self.offset);
+            let mut output = if decrypt {
+                inverse_cipher_block(&input, self.expanded_key, self.key_type)
+            } else {
+                cipher_block(&input, self.expanded_key, self.key_type)
+            };
+            xor_in_place(&mut output, &self.offset);
+
+            if decrypt {
+                xor_in_place(&mut self.checksum, &output);
+            }
+            block.copy_from_slice(&output);
+        }
+    }
+
+    /// Encrypt or decrypt the final partial block in place and return the
+    /// full tag
+    fn finish(&mut self, last: &mut [u8], decrypt: bool) -> [u8; 16] {
+        if !last.is_empty() {
+            xor_in_place(&mut self.offset, &self.offsets.l_star);
+            let pad = cipher_block(&self.offset, self.expanded_key, self.key_type);
+
+            if !decrypt {
+                xor_in_place(&mut self.checksum, &pad_last(last));
+            }
+            xor_in_place(last, &pad);
+            if decrypt {
+                xor_in_place(&mut self.checksum, &pad_last(last));
+            }
+        }
+
+        compute_tag(
+            &self.checksum,
+            &self.offset,
+            self.associated_data,
+            &self.offsets,
+            self.expanded_key,
+            self.key_type,
+        )
+    }
+}
+
+/// Incremental OCB encryption, which appends the tag in
+/// [`Encryptor::finalize`]
+pub struct OcbEncryptor<'a> {
+    state: State<'a>,
+}
+
+impl<'a> OcbEncryptor<'a> {
+    pub fn new(
+        associated_data: &'a [u8],
+        nonce: &[u8],
+        tag_len: usize,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, OcbError> {
+        Ok(Self {
+            state: State::new(associated_data, nonce, tag_len, expanded_key, key_type)?,
+        })
+    }
+}
+
+impl Encryptor for OcbEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let mut blocks = self.state.buffer.take(plaintext, 16, 0);
+        self.state.process_blocks(&mut blocks, false);
+        blocks
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let mut result = std::mem::take(&mut self.state.buffer).remaining();
+        let tag = self.state.finish(&mut result, false);
+        result.extend(&tag[..self.state.tag_len]);
+        Ok(result)
+    }
+}
+
+/// Incremental OCB decryption
+///
+/// The last `tag_len` bytes are held back as the tag, which is checked by
+/// [`Decryptor::finalize`]. Nothing returned by [`Decryptor::update`] is
+/// authentic until then.
+pub struct OcbDecryptor<'a> {
+    state: State<'a>,
+}
+
+impl<'a> OcbDecryptor<'a> {
+    pub fn new(
+        associated_data: &'a [u8],
+        nonce: &[u8],
+        tag_len: usize,
+        expanded_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, OcbError> {
+        Ok(Self {
+            state: State::new(associated_data, nonce, tag_len, expanded_key, key_type)?,
+        })
+    }
+}
+
+impl Decryptor for OcbDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let tag_len = self.state.tag_len;
+        let mut blocks = self.state.buffer.take(ciphertext, 16, tag_len);
+        self.state.process_blocks(&mut blocks, true);
+        blocks
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let mut result = std::mem::take(&mut self.state.buffer).remaining();
+        let last_len = result
+            .len()
+            .checked_sub(self.state.tag_len)
+            .ok_or(StreamError::TooShort(result.len()))?;
+        let tag = result.split_off(last_len);
+
+        let expected_tag = self.state.finish(&mut result, true);
+        if !constant_time_eq(&expected_tag[..self.state.tag_len], &tag) {
+            return Err(StreamError::AuthenticationFailed);
+        }
+
+        Ok(result)
+    }
+}
+
+fn check_params(nonce: &[u8], tag_len: usize) -> Result<(), OcbError> {
+    if nonce.len() > MAX_NONCE_LEN {
+        return Err(OcbError::InvalidNonceLength(nonce.len()));
+    }
+    if tag_len == 0 || tag_len > 16 {
+        return Err(OcbError::InvalidTagLength(tag_len));
+    }
+    Ok(())
+}
+
+/// Compute `Offset_0` from the nonce, via `Ktop` and `Stretch`
+fn initial_offset(
+    nonce: &[u8],
+    tag_len: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut formatted = [0; 16];
+    formatted[0] = (((tag_len * 8) % 128) as u8) << 1;
+    formatted[16 - nonce.len()..].copy_from_slice(nonce);
+    formatted[15 - nonce.len()] |= 0x01;
+
+    let bottom = (formatted[15] & 0x3f) as usize;
+    formatted[15] &= 0xc0;
+
+    let k_top = cipher_block(&formatted, expanded_key, key_type);
+    let mut stretch = [0; 24];
+    stretch[..16].copy_from_slice(&k_top);
+    for i in 0..8 {
+        stretch[16 + i] = k_top[i] ^ k_top[i + 1];
+    }
+
+    // take bits `bottom..bottom + 128` of the stretch
+    let byte_shift = bottom / 8;
+    let bit_shift = bottom % 8;
+    let mut offset = [0; 16];
+    for (i, byte) in offset.iter_mut().enumerate() {
+        *byte = stretch[i + byte_shift] << bit_shift;
+        if bit_shift != 0 {
+            *byte |= stretch[i + byte_shift + 1] >> (8 - bit_shift);
+        }
+    }
+
+    offset
+}
+
+/// Pad a partial block with a single one bit followed by zeros
+fn pad_last(last: &[u8]) -> [u8; 16] {
+    let mut padded = [0; 16];
+    padded[..last.len()].copy_from_slice(last);
+    padded[last.len()] = 0x80;
+    padded
+}
+
+/// The full 16-byte tag, before truncation
+fn compute_tag(
+    checksum: &[u8; 16],
+    offset: &[u8; 16],
+    associated_data: &[u8],
+    offsets: &Offsets,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut input = *checksum;
+    xor_in_place(&mut input, offset);
+    xor_in_place(&mut input, &offsets.l_dollar);
+
+    let mut tag = cipher_block(&input, expanded_key, key_type);
+    xor_in_place(
+        &mut tag,
+        &hash(associated_data, offsets, expanded_key, key_type),
+    );
+    tag
+}
+
+/// The HASH function over the associated data
+fn hash(
+    associated_data: &[u8],
+    offsets: &Offsets,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut sum = [0; 16];
+    let mut offset = [0; 16];
+
+    let mut blocks = associated_data.chunks_exact(16);
+    for (i, block) in (1..).zip(&mut blocks) {
+        xor_in_place(&mut offset, offsets.for_block(i));
+
+        let mut input: [u8; 16] = block.try_into().unwrap();
+        xor_in_place(&mut input, &offset);
+        xor_in_place(&mut sum, &cipher_block(&input, expanded_key, key_type));
+    }
+
+    let last = blocks.remainder();
+    if !last.is_empty() {
+        xor_in_place(&mut offset, &offsets.l_star);
+
+        let mut input = pad_last(last);
+        xor_in_place(&mut input, &offset);
+        xor_in_place(&mut sum, &cipher_block(&input, expanded_key, key_type));
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::stream::check_streaming;
+
+    // Sample results from RFC 7253 Appendix A
+
+    fn nonce(last: u8) -> [u8; 12] {
+        [
+            0xbb, 0xaa, 0x99, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, last,
+        ]
+    }
+
+    fn check_vector(nonce: &[u8], aad: &[u8], plaintext: &[u8], expected: &[u8]) {
+        let key: Vec<u8> = (0..16).collect();
+        let expanded_key = key_expansion(&key, KeyType::Aes128);
+
+        let result =
+            ocb_encrypt(aad, plaintext, nonce, 16, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(result, expected);
+
+        let result = ocb_decrypt(aad, expected, nonce, 16, &expanded_key, KeyType::Aes128).unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn sample_results_test() {
+        let data: Vec<u8> = (0..40).collect();
+
+        let expected = [
+            0x78, 0x54, 0x07, 0xbf, 0xff, 0xc8, 0xad, 0x9e, 0xdc, 0xc5, 0x52, 0x0a, 0xc9, 0x11,
+            0x1e, 0xe6,
+        ];
+        check_vector(&nonce(0x00), &[], &[], &expected);
+
+        let expected = [
+            0x68, 0x20, 0xb3, 0x65, 0x7b, 0x6f, 0x61, 0x5a, 0x57, 0x25, 0xbd, 0xa0, 0xd3, 0xb4,
+            0xeb, 0x3a, 0x25, 0x7c, 0x9a, 0xf1, 0xf8, 0xf0, 0x30, 0x09,
+        ];
+        check_vector(&nonce(0x01), &data[..8], &data[..8], &expected);
+
+        let expected = [
+            0x81, 0x01, 0x7f, 0x82, 0x03, 0xf0, 0x81, 0x27, 0x71, 0x52, 0xfa, 0xde, 0x69, 0x4a,
+            0x0a, 0x00,
+        ];
+        check_vector(&nonce(0x02), &data[..8], &[], &expected);
+
+        let expected = [
+            0x45, 0xdd, 0x69, 0xf8, 0xf5, 0xaa, 0xe7, 0x24, 0x14, 0x05, 0x4c, 0xd1, 0xf3, 0x5d,
+            0x82, 0x76, 0x0b, 0x2c, 0xd0, 0x0d, 0x2f, 0x99, 0xbf, 0xa9,
+        ];
+        check_vector(&nonce(0x03), &[], &data[..8], &expected);
+    }
+
+    #[test]
+    fn tag_96_test() {
+        let key: Vec<u8> = (0..16).rev().collect();
+        let expanded_key = key_expansion(&key, KeyType::Aes128);
+        let data: Vec<u8> = (0..40).collect();
+        let expected = [
+            0x17, 0x92, 0xa4, 0xe3, 0x1e, 0x07, 0x55, 0xfb, 0x03, 0xe3, 0x1b, 0x22, 0x11, 0x6e,
+            0x6c, 0x2d, 0xdf, 0x9e, 0xfd, 0x6e, 0x33, 0xd5, 0x36, 0xf1, 0xa0, 0x12, 0x4b, 0x0a,
+            0x55, 0xba, 0xe8, 0x84, 0xed, 0x93, 0x48, 0x15, 0x29, 0xc7, 0x6b, 0x6a, 0xd0, 0xc5,
+            0x15, 0xf4, 0xd1, 0xcd, 0xd4, 0xfd, 0xac, 0x4f, 0x02, 0xaa,
+        ];
+
+        let result = ocb_encrypt(
+            &data,
+            &data,
+            &nonce(0x0d),
+            12,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+
+        let result = ocb_decrypt(
+            &data,
+            &expected,
+            &nonce(0x0d),
+            12,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, data);
+    }
+
+    /// The iterated test from RFC 7253 Appendix A, which covers every input
+    /// length up to 127 bytes
+    fn iterated(key_type: KeyType, tag_len: usize) -> Vec<u8> {
+        let mut key = vec![0; key_type.n_k() * 4];
+        *key.last_mut().unwrap() = (tag_len * 8) as u8;
+        let expanded_key = key_expansion(&key, key_type);
+
+        let encrypt = |n: u32, aad: &[u8], plaintext: &[u8]| {
+            let mut nonce = [0; 12];
+            nonce[8..].copy_from_slice(&n.to_be_bytes());
+            ocb_encrypt(aad, plaintext, &nonce, tag_len, &expanded_key, key_type).unwrap()
+        };
+
+        let mut c = vec![];
+        for i in 0..128 {
+            let s = vec![0; i as usize];
+            c.extend(encrypt(3 * i + 1, &s, &s));
+            c.extend(encrypt(3 * i + 2, &[], &s));
+            c.extend(encrypt(3 * i + 3, &s, &[]));
+        }
+
+        encrypt(385, &c, &[])
+    }
+
+    #[test]
+    fn iterated_test() {
+        let expected = [
+            0x67, 0xe9, 0x44, 0xd2, 0x32, 0x56, 0xc5, 0xe0, 0xb6, 0xc6, 0x1f, 0xa2, 0x2f, 0xdf,
+            0x1e, 0xa2,
+        ];
+        assert_eq!(iterated(KeyType::Aes128, 16), expected);
+
+        let expected = [
+            0x77, 0xa3, 0xd8, 0xe7, 0x35, 0x89, 0x15, 0x8d, 0x25, 0xd0, 0x12, 0x09,
+        ];
+        assert_eq!(iterated(KeyType::Aes128, 12), expected);
+
+        let expected = [0x19, 0x2c, 0x9b, 0x7b, 0xd9, 0x0b, 0xa0, 0x6a];
+        assert_eq!(iterated(KeyType::Aes128, 8), expected);
+
+        let expected = [
+            0xd9, 0x0e, 0xb8, 0xe9, 0xc9, 0x77, 0xc8, 0x8b, 0x79, 0xdd, 0x79, 0x3d, 0x7f, 0xfa,
+            0x16, 0x1c,
+        ];
+        assert_eq!(iterated(KeyType::Aes256, 16), expected);
+    }
+
+    #[test]
+    fn errors_test() {
+        let expanded_key = key_expansion(&[0; 16], KeyType::Aes128);
+        let nonce = nonce(0x00);
+
+        let mut ciphertext = ocb_encrypt(
+            b"aad",
+            b"message",
+            &nonce,
+            8,
+            &expanded_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        ciphertext[0] ^= 1;
+        let result = ocb_decrypt(
+            b"aad",
+            &ciphertext,
+            &nonce,
+            8,
+            &expanded_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(OcbError::AuthenticationFailed));
+
+        let result = ocb_decrypt(b"", &[0; 7], &nonce, 8, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(OcbError::TooShort));
+
+        let result = ocb_encrypt(b"", b"", &[0; 16], 16, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(OcbError::InvalidNonceLength(16)));
+
+        let result = ocb_encrypt(b"", b"", &nonce, 17, &expanded_key, KeyType::Aes128);
+        assert_eq!(result, Err(OcbError::InvalidTagLength(17)));
+    }
+
+    #[test]
+    fn streaming_test() {
+        let expanded_key = key_expansion(&[0x5c; 24], KeyType::Aes192);
+        let associated_data = [0xaa; 20];
+        let nonce = nonce(0x42);
+        let plaintext: Vec<u8> = (0..100).collect();
+
+        for len in [0, 15, 16, 17, 100] {
+            let expected = ocb_encrypt(
+                &associated_data,
+                &plaintext[..len],
+                &nonce,
+                12,
+                &expanded_key,
+                KeyType::Aes192,
+            )
+            .unwrap();
+
+            check_streaming(
+                &plaintext[..len],
+                &expected,
+                || {
+                    OcbEncryptor::new(&associated_data, &nonce, 12, &expanded_key, KeyType::Aes192)
+                        .unwrap()
+                },
+                || {
+                    OcbDecryptor::new(&associated_data, &nonce, 12, &expanded_key, KeyType::Aes192)
+                        .unwrap()
+                },
+            );
+        }
+
+        let mut ciphertext = ocb_encrypt(
+            &associated_data,
+            &plaintext,
+            &nonce,
+            12,
+            &expanded_key,
+            KeyType::Aes192,
+        )
+        .unwrap();
+        ciphertext[50] ^= 0x01;
+        let mut decryptor =
+            OcbDecryptor::new(&associated_data, &nonce, 12, &expanded_key, KeyType::Aes192)
+                .unwrap();
+        decryptor.update(&ciphertext);
+        assert_eq!(decryptor.finalize(), Err(StreamError::AuthenticationFailed));
+
+        let mut decryptor =
+            OcbDecryptor::new(&associated_data, &nonce, 12, &expanded_key, KeyType::Aes192)
+                .unwrap();
+        assert_eq!(decryptor.update(&[0; 11]), []);
+        assert_eq!(decryptor.finalize(), Err(StreamError::TooShort(11)));
+    }
+}
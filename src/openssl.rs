@@ -0,0 +1,309 @@
+//! The salted file format of OpenSSL's `enc` command
+//!
+//! `openssl enc -aes-256-cbc -pass ...` writes `Salted__`, an 8-byte random
+//! salt, then the ciphertext. The key and IV are both derived from the
+//! password and salt, in one of two ways:
+//!
+//! - EVP_BytesToKey, OpenSSL's original scheme, which hashes the password and
+//!   salt once per block of output. The digest is MD5 before OpenSSL 1.1.0 and
+//!   SHA-256 since, and can be chosen with `-md`.
+//! - PBKDF2-HMAC-SHA-256 with `-pbkdf2`, which runs 10000 iterations unless
+//!   `-iter` says otherwise.
+//!
+//! The iteration count and the choice of scheme aren't recorded in the file,
+//! so they have to be known when decrypting.
+
+use std::fmt::Display;
+
+use crate::cbc::{cbc_decrypt, cbc_encrypt};
+use crate::constant::KeyType;
+use crate::key_expansion::key_expansion;
+use crate::md5::md5;
+use crate::padding::{PaddingError, Pkcs7};
+use crate::pbkdf2::{pbkdf2_hmac_sha256, Pbkdf2Error};
+use crate::sha256::sha256;
+
+pub const MAGIC: [u8; 8] = *b"Salted__";
+pub const SALT_LEN: usize = 8;
+pub const HEADER_LEN: usize = MAGIC.len() + SALT_LEN;
+
+/// The iteration count `openssl enc -pbkdf2` uses without `-iter`
+pub const DEFAULT_ITERATIONS: u32 = 10_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpenSslError {
+    /// The data didn't start with `Salted__` and a salt
+    MissingHeader,
+    Pbkdf2(Pbkdf2Error),
+    /// The padding was invalid, which usually means the password was wrong
+    Padding(PaddingError),
+}
+
+impl Display for OpenSslError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingHeader => write!(f, "missing Salted__ header"),
+            Self::Pbkdf2(error) => write!(f, "{error}"),
+            Self::Padding(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for OpenSslError {}
+
+impl From<Pbkdf2Error> for OpenSslError {
+    fn from(error: Pbkdf2Error) -> Self {
+        Self::Pbkdf2(error)
+    }
+}
+
+impl From<PaddingError> for OpenSslError {
+    fn from(error: PaddingError) -> Self {
+        Self::Padding(error)
+    }
+}
+
+/// The digest for EVP_BytesToKey, set with `openssl enc -md`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Digest {
+    Md5,
+    Sha256,
+}
+
+impl Digest {
+    fn hash(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Md5 => md5(data).to_vec(),
+            Self::Sha256 => sha256(data).to_vec(),
+        }
+    }
+}
+
+/// How the key and IV are derived from the password
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDerivation {
+    /// EVP_BytesToKey with a single iteration, as `openssl enc` uses it
+    BytesToKey(Digest),
+    /// PBKDF2-HMAC-SHA-256, as `openssl enc -pbkdf2 -iter <iterations>` uses it
+    Pbkdf2 { iterations: u32 },
+}
+
+/// OpenSSL's EVP_BytesToKey with an iteration count of 1
+///
+/// Each block of output is `D_i = H(D_(i-1) || password || salt)`, starting
+/// from an empty `D_0`.
+pub fn evp_bytes_to_key(digest: Digest, password: &[u8], salt: &[u8], len: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(len);
+    let mut previous = Vec::new();
+
+    while output.len() < len {
+        let mut input = previous;
+        input.extend(password);
+        input.extend(salt);
+        previous = digest.hash(&input);
+        output.extend(&previous);
+    }
+
+    output.truncate(len);
+    output
+}
+
+/// Derive the key and IV for `key_type` from `password` and `salt`
+///
+/// Modes without an IV, such as ECB, use the same key and ignore the IV.
+pub fn derive_key_and_iv(
+    password: &[u8],
+    salt: &[u8; SALT_LEN],
+    key_derivation: KeyDerivation,
+    key_type: KeyType,
+) -> Result<(Vec<u8>, [u8; 16]), OpenSslError> {
+    let key_len = key_type.n_k() * 4;
+    let mut output = match key_derivation {
+        KeyDerivation::BytesToKey(digest) => evp_bytes_to_key(digest, password, salt, key_len + 16),
+        KeyDerivation::Pbkdf2 { iterations } => {
+            pbkdf2_hmac_sha256(password, salt, iterations, key_len + 16)?
+        }
+    };
+
+    let iv = output.split_off(key_len).try_into().unwrap();
+    Ok((output, iv))
+}
+
+/// The `Salted__` header for `salt`
+pub fn header(salt: &[u8; SALT_LEN]) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..MAGIC.len()].copy_from_slice(&MAGIC);
+    header[MAGIC.len()..].copy_from_slice(salt);
+    header
+}
+
+/// Split `data` into its salt and the ciphertext after the header
+pub fn parse_header(data: &[u8]) -> Result<([u8; SALT_LEN], &[u8]), OpenSslError> {
+    match data.strip_prefix(&MAGIC) {
+        Some(rest) if rest.len() >= SALT_LEN => {
+            let (salt, ciphertext) = rest.split_at(SALT_LEN);
+            Ok((salt.try_into().unwrap(), ciphertext))
+        }
+        _ => Err(OpenSslError::MissingHeader),
+    }
+}
+
+/// Encrypt `plaintext` as `openssl enc` does with CBC mode, using `salt`
+/// (which should be random) for the key derivation
+pub fn openssl_encrypt(
+    plaintext: &[u8],
+    password: &[u8],
+    salt: &[u8; SALT_LEN],
+    key_derivation: KeyDerivation,
+    key_type: KeyType,
+) -> Result<Vec<u8>, OpenSslError> {
+    let (key, iv) = derive_key_and_iv(password, salt, key_derivation, key_type)?;
+    let expanded_key = key_expansion(&key, key_type);
+
+    let mut result = header(salt).to_vec();
+    result.extend(cbc_encrypt(plaintext, &iv, &Pkcs7, &expanded_key, key_type));
+    Ok(result)
+}
+
+/// Decrypt a file written by `openssl enc` with CBC mode
+pub fn openssl_decrypt(
+    data: &[u8],
+    password: &[u8],
+    key_derivation: KeyDerivation,
+    key_type: KeyType,
+) -> Result<Vec<u8>, OpenSslError> {
+    let (salt, ciphertext) = parse_header(data)?;
+    let (key, iv) = derive_key_and_iv(password, &salt, key_derivation, key_type)?;
+    let expanded_key = key_expansion(&key, key_type);
+
+    Ok(cbc_decrypt(
+        ciphertext,
+        &iv,
+        &Pkcs7,
+        &expanded_key,
+        key_type,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ctr::ctr_decrypt;
+    use crate::encoding::decode_base64;
+
+    const PASSWORD: &[u8] = b"fixture-password";
+    const PLAINTEXT: &[u8] = include_bytes!("../test_vectors/openssl/plaintext.txt");
+
+    /// A file's name and contents, how its key was derived, and its plaintext
+    type Fixture = (&'static str, Vec<u8>, KeyDerivation, KeyType, &'static [u8]);
+
+    /// The fixtures written by `openssl enc` in CBC mode
+    fn cbc_fixtures() -> Vec<Fixture> {
+        let pbkdf2 = KeyDerivation::Pbkdf2 {
+            iterations: DEFAULT_ITERATIONS,
+        };
+        let base64 = include_str!("../test_vectors/openssl/aes-192-cbc-pbkdf2.b64");
+
+        vec![
+            (
+                "aes-256-cbc-md5",
+                include_bytes!("../test_vectors/openssl/aes-256-cbc-md5.enc").to_vec(),
+                KeyDerivation::BytesToKey(Digest::Md5),
+                KeyType::Aes256,
+                PLAINTEXT,
+            ),
+            (
+                "aes-256-cbc-sha256",
+                include_bytes!("../test_vectors/openssl/aes-256-cbc-sha256.enc").to_vec(),
+                KeyDerivation::BytesToKey(Digest::Sha256),
+                KeyType::Aes256,
+                PLAINTEXT,
+            ),
+            (
+                "aes-256-cbc-pbkdf2",
+                include_bytes!("../test_vectors/openssl/aes-256-cbc-pbkdf2.enc").to_vec(),
+                pbkdf2,
+                KeyType::Aes256,
+                PLAINTEXT,
+            ),
+            (
+                "aes-128-cbc-pbkdf2-1000",
+                include_bytes!("../test_vectors/openssl/aes-128-cbc-pbkdf2-1000.enc").to_vec(),
+                KeyDerivation::Pbkdf2 { iterations: 1000 },
+                KeyType::Aes128,
+                PLAINTEXT,
+            ),
+            (
+                "aes-192-cbc-pbkdf2",
+                decode_base64(base64).unwrap(),
+                pbkdf2,
+                KeyType::Aes192,
+                PLAINTEXT,
+            ),
+            (
+                "aes-256-cbc-pbkdf2-empty",
+                include_bytes!("../test_vectors/openssl/aes-256-cbc-pbkdf2-empty.enc").to_vec(),
+                pbkdf2,
+                KeyType::Aes256,
+                b"",
+            ),
+        ]
+    }
+
+    #[test]
+    fn decrypt_fixtures_test() {
+        for (name, data, key_derivation, key_type, plaintext) in cbc_fixtures() {
+            let decrypted = openssl_decrypt(&data, PASSWORD, key_derivation, key_type);
+            assert_eq!(decrypted.unwrap(), plaintext, "{name}");
+
+            assert!(
+                openssl_decrypt(&data, b"wrong password", key_derivation, key_type).is_err(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn encrypt_fixtures_test() {
+        // with the same salt, encrypting must reproduce OpenSSL's output exactly
+        for (name, data, key_derivation, key_type, plaintext) in cbc_fixtures() {
+            let (salt, _) = parse_header(&data).unwrap();
+            let encrypted = openssl_encrypt(plaintext, PASSWORD, &salt, key_derivation, key_type);
+            assert_eq!(encrypted.unwrap(), data, "{name}");
+        }
+    }
+
+    #[test]
+    fn ctr_fixture_test() {
+        let data = include_bytes!("../test_vectors/openssl/aes-256-ctr-pbkdf2.enc");
+        let (salt, ciphertext) = parse_header(data).unwrap();
+        let key_derivation = KeyDerivation::Pbkdf2 {
+            iterations: DEFAULT_ITERATIONS,
+        };
+        let (key, iv) =
+            derive_key_and_iv(PASSWORD, &salt, key_derivation, KeyType::Aes256).unwrap();
+
+        let expanded_key = key_expansion(&key, KeyType::Aes256);
+        assert_eq!(
+            ctr_decrypt(ciphertext, &iv, &expanded_key, KeyType::Aes256),
+            PLAINTEXT
+        );
+    }
+
+    #[test]
+    fn header_test() {
+        let salt = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut data = header(&salt).to_vec();
+        assert_eq!(data, b"Salted__\x01\x02\x03\x04\x05\x06\x07\x08");
+        assert_eq!(parse_header(&data), Ok((salt, &[][..])));
+
+        data.extend([0xff; 16]);
+        assert_eq!(parse_header(&data), Ok((salt, &[0xff; 16][..])));
+
+        assert_eq!(
+            parse_header(b"Salted__\x01\x02"),
+            Err(OpenSslError::MissingHeader)
+        );
+        assert_eq!(parse_header(&[0; 32]), Err(OpenSslError::MissingHeader));
+    }
+}
@@ -0,0 +1,280 @@
+//! Block padding schemes
+//!
+//! Padding extends a message to a whole number of 16-byte blocks before it is
+//! encrypted with a block-aligned mode such as [`ecb`](crate::ecb) or
+//! [`cbc`](crate::cbc), and is checked and removed again after decryption.
+//! Every scheme except [`ZeroPadding`] always adds at least one byte, so the
+//! padding can be removed unambiguously.
+
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hasher};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaddingError {
+    /// The padded data wasn't a non-zero multiple of the block size
+    InvalidLength(usize),
+    /// The padding bytes weren't in the form the scheme produces
+    InvalidPadding,
+}
+
+impl Display for PaddingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(f, "invalid padded length {len}"),
+            Self::InvalidPadding => write!(f, "invalid padding"),
+        }
+    }
+}
+
+impl std::error::Error for PaddingError {}
+
+/// A way of padding messages to a whole number of blocks
+pub trait Padding {
+    /// Append padding to bring `data` to a whole number of blocks
+    fn pad(&self, data: &mut Vec<u8>);
+
+    /// Check the padding at the end of `data` and return the message before it
+    fn unpad<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], PaddingError>;
+}
+
+/// PKCS#7 (RFC 5652 section 6.3): `n` bytes that each hold the value `n`
+pub struct Pkcs7;
+
+/// ANSI X9.23: `n - 1` zero bytes followed by a byte holding `n`
+pub struct AnsiX923;
+
+/// ISO 10126: `n - 1` arbitrary bytes followed by a byte holding `n`
+///
+/// The filler bytes are random, but not from a cryptographic generator, since
+/// the scheme only requires them to be arbitrary.
+pub struct Iso10126;
+
+/// ISO/IEC 7816-4: a single `0x80` byte followed by zero bytes
+pub struct Iso7816;
+
+/// Zero bytes up to the end of the block, and none if the message is already
+/// block-aligned
+///
+/// Removing the padding strips trailing zeros, so messages that end in zero
+/// bytes don't round-trip. Prefer another scheme unless a peer requires it.
+pub struct ZeroPadding;
+
+/// How many padding bytes bring `len` up to a whole number of blocks, from 1
+/// to 16
+fn padding_len(len: usize) -> usize {
+    16 - len % 16
+}
+
+/// Check that padded data is a non-zero number of whole blocks
+fn check_len(data: &[u8]) -> Result<(), PaddingError> {
+    if data.is_empty() || !data.len().is_multiple_of(16) {
+        return Err(PaddingError::InvalidLength(data.len()));
+    }
+    Ok(())
+}
+
+/// Read the final length byte used by PKCS#7, X9.23 and ISO 10126, returning
+/// the message and the padding bytes before the length byte
+fn split_length_byte(data: &[u8]) -> Result<(&[u8], &[u8]), PaddingError> {
+    check_len(data)?;
+
+    let n = data[data.len() - 1] as usize;
+    if !(1..=16).contains(&n) {
+        return Err(PaddingError::InvalidPadding);
+    }
+
+    let (message, padding) = data.split_at(data.len() - n);
+    Ok((message, &padding[..n - 1]))
+}
+
+impl Padding for Pkcs7 {
+    fn pad(&self, data: &mut Vec<u8>) {
+        let n = padding_len(data.len());
+        data.resize(data.len() + n, n as u8);
+    }
+
+    fn unpad<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], PaddingError> {
+        let (message, filler) = split_length_byte(data)?;
+        let n = filler.len() as u8 + 1;
+
+        if filler.iter().any(|&byte| byte != n) {
+            return Err(PaddingError::InvalidPadding);
+        }
+        Ok(message)
+    }
+}
+
+impl Padding for AnsiX923 {
+    fn pad(&self, data: &mut Vec<u8>) {
+        let n = padding_len(data.len());
+        data.resize(data.len() + n - 1, 0);
+        data.push(n as u8);
+    }
+
+    fn unpad<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], PaddingError> {
+        let (message, filler) = split_length_byte(data)?;
+
+        if filler.iter().any(|&byte| byte != 0) {
+            return Err(PaddingError::InvalidPadding);
+        }
+        Ok(message)
+    }
+}
+
+impl Padding for Iso10126 {
+    fn pad(&self, data: &mut Vec<u8>) {
+        let n = padding_len(data.len());
+
+        // a freshly keyed SipHash of nothing is a cheap source of arbitrary bytes
+        let mut filler = Vec::with_capacity(16);
+        while filler.len() < n - 1 {
+            let bytes = RandomState::new().build_hasher().finish().to_le_bytes();
+            filler.extend(bytes);
+        }
+
+        data.extend(&filler[..n - 1]);
+        data.push(n as u8);
+    }
+
+    fn unpad<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], PaddingError> {
+        let (message, _) = split_length_byte(data)?;
+        Ok(message)
+    }
+}
+
+impl Padding for Iso7816 {
+    fn pad(&self, data: &mut Vec<u8>) {
+        let n = padding_len(data.len());
+        data.push(0x80);
+        data.resize(data.len() + n - 1, 0);
+    }
+
+    fn unpad<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], PaddingError> {
+        check_len(data)?;
+
+        // the marker must be within the last block, after only zero bytes
+        let last_block = &data[data.len() - 16..];
+        let marker = last_block
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .ok_or(PaddingError::InvalidPadding)?;
+        if last_block[marker] != 0x80 {
+            return Err(PaddingError::InvalidPadding);
+        }
+
+        Ok(&data[..data.len() - 16 + marker])
+    }
+}
+
+impl Padding for ZeroPadding {
+    fn pad(&self, data: &mut Vec<u8>) {
+        if !data.len().is_multiple_of(16) {
+            data.resize(data.len() + padding_len(data.len()), 0);
+        }
+    }
+
+    fn unpad<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], PaddingError> {
+        if !data.len().is_multiple_of(16) {
+            return Err(PaddingError::InvalidLength(data.len()));
+        }
+
+        // at most 15 bytes were added, so a whole block of zeros is kept
+        let zeros = data.iter().rev().take(15).take_while(|&&byte| byte == 0);
+        Ok(&data[..data.len() - zeros.count()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn padded(padding: &dyn Padding, len: usize) -> Vec<u8> {
+        let mut data = vec![0xaa; len];
+        padding.pad(&mut data);
+        data
+    }
+
+    #[test]
+    fn pkcs7_test() {
+        assert_eq!(padded(&Pkcs7, 13)[13..], [0x03, 0x03, 0x03]);
+        assert_eq!(padded(&Pkcs7, 16)[16..], [0x10; 16]);
+
+        assert_eq!(Pkcs7.unpad(&padded(&Pkcs7, 13)), Ok(&[0xaa; 13][..]));
+        assert_eq!(Pkcs7.unpad(&padded(&Pkcs7, 0)), Ok(&[][..]));
+
+        let mut data = padded(&Pkcs7, 13);
+        data[13] = 0x02;
+        assert_eq!(Pkcs7.unpad(&data), Err(PaddingError::InvalidPadding));
+
+        let mut data = padded(&Pkcs7, 16);
+        data[31] = 0x00;
+        assert_eq!(Pkcs7.unpad(&data), Err(PaddingError::InvalidPadding));
+        data[31] = 0x11;
+        assert_eq!(Pkcs7.unpad(&data), Err(PaddingError::InvalidPadding));
+    }
+
+    #[test]
+    fn ansi_x923_test() {
+        assert_eq!(padded(&AnsiX923, 13)[13..], [0x00, 0x00, 0x03]);
+        assert_eq!(AnsiX923.unpad(&padded(&AnsiX923, 13)), Ok(&[0xaa; 13][..]));
+        assert_eq!(AnsiX923.unpad(&padded(&AnsiX923, 16)), Ok(&[0xaa; 16][..]));
+
+        let mut data = padded(&AnsiX923, 13);
+        data[14] = 0x01;
+        assert_eq!(AnsiX923.unpad(&data), Err(PaddingError::InvalidPadding));
+    }
+
+    #[test]
+    fn iso_10126_test() {
+        let data = padded(&Iso10126, 5);
+        assert_eq!(data.len(), 16);
+        assert_eq!(data[15], 11);
+        assert_eq!(Iso10126.unpad(&data), Ok(&[0xaa; 5][..]));
+        assert_eq!(Iso10126.unpad(&padded(&Iso10126, 32)), Ok(&[0xaa; 32][..]));
+
+        let mut data = padded(&Iso10126, 5);
+        data[15] = 17;
+        assert_eq!(Iso10126.unpad(&data), Err(PaddingError::InvalidPadding));
+    }
+
+    #[test]
+    fn iso_7816_test() {
+        assert_eq!(padded(&Iso7816, 13)[13..], [0x80, 0x00, 0x00]);
+        assert_eq!(padded(&Iso7816, 15)[15..], [0x80]);
+        assert_eq!(Iso7816.unpad(&padded(&Iso7816, 13)), Ok(&[0xaa; 13][..]));
+        assert_eq!(Iso7816.unpad(&padded(&Iso7816, 16)), Ok(&[0xaa; 16][..]));
+
+        let mut data = padded(&Iso7816, 13);
+        data[13] = 0x81;
+        assert_eq!(Iso7816.unpad(&data), Err(PaddingError::InvalidPadding));
+        assert_eq!(Iso7816.unpad(&[0; 16]), Err(PaddingError::InvalidPadding));
+    }
+
+    #[test]
+    fn zero_padding_test() {
+        assert_eq!(padded(&ZeroPadding, 13)[13..], [0x00; 3]);
+        assert_eq!(padded(&ZeroPadding, 16).len(), 16);
+        assert_eq!(
+            ZeroPadding.unpad(&padded(&ZeroPadding, 13)),
+            Ok(&[0xaa; 13][..])
+        );
+        assert_eq!(ZeroPadding.unpad(&[0; 16]), Ok(&[0][..]));
+        assert_eq!(ZeroPadding.unpad(&[]), Ok(&[][..]));
+    }
+
+    #[test]
+    fn invalid_length_test() {
+        let schemes: [&dyn Padding; 5] = [&Pkcs7, &AnsiX923, &Iso10126, &Iso7816, &ZeroPadding];
+
+        for padding in schemes {
+            assert_eq!(
+                padding.unpad(&[0x01; 17]),
+                Err(PaddingError::InvalidLength(17))
+            );
+        }
+        for padding in &schemes[..4] {
+            assert_eq!(padding.unpad(&[]), Err(PaddingError::InvalidLength(0)));
+        }
+    }
+}
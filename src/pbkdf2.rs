@@ -0,0 +1,160 @@
+//! Password-based key derivation with PBKDF2-HMAC-SHA-256 (RFC 8018 section
+//! 5.2, NIST SP 800-132)
+//!
+//! Each 32-byte block of output is `U_1 ^ U_2 ^ ... ^ U_c`, where
+//! `U_1 = PRF(P, S || [i]_32)` and `U_j = PRF(P, U_(j-1))`, so the iteration
+//! count `c` sets how expensive each password guess is.
+
+use std::fmt::Display;
+
+use crate::constant::KeyType;
+use crate::hmac::HmacSha256;
+use crate::sha256::DIGEST_LEN;
+use crate::util::xor_in_place;
+
+/// The salt length SP 800-132 asks for at the least
+pub const SALT_LEN: usize = 16;
+
+/// A reasonable iteration count for new passwords
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pbkdf2Error {
+    /// There must be at least one iteration
+    ZeroIterations,
+    /// More output was requested than a 32-bit block counter can number
+    OutputTooLong(usize),
+}
+
+impl Display for Pbkdf2Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroIterations => write!(f, "the iteration count must be at least 1"),
+            Self::OutputTooLong(len) => write!(f, "output of {len} bytes is too long"),
+        }
+    }
+}
+
+impl std::error::Error for Pbkdf2Error {}
+
+/// Derive `output_len` bytes from `password` and `salt`
+pub fn pbkdf2_hmac_sha256(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    output_len: usize,
+) -> Result<Vec<u8>, Pbkdf2Error> {
+    if iterations == 0 {
+        return Err(Pbkdf2Error::ZeroIterations);
+    }
+    if output_len.div_ceil(DIGEST_LEN) > u32::MAX as usize {
+        return Err(Pbkdf2Error::OutputTooLong(output_len));
+    }
+
+    // the keyed state is the same for every PRF call, so set it up once
+    let prf = HmacSha256::new(password);
+    let mut output = Vec::with_capacity(output_len);
+
+    for i in 1..=output_len.div_ceil(DIGEST_LEN) as u32 {
+        let mut mac = prf.clone();
+        mac.update(salt);
+        mac.update(&i.to_be_bytes());
+        let mut u = mac.finalize();
+        let mut block = u;
+
+        for _ in 1..iterations {
+            let mut mac = prf.clone();
+            mac.update(&u);
+            u = mac.finalize();
+            xor_in_place(&mut block, &u);
+        }
+
+        output.extend(block);
+    }
+
+    output.truncate(output_len);
+    Ok(output)
+}
+
+/// Derive an AES key of `key_type`'s length from `password`
+///
+/// The salt should be [`SALT_LEN`] random bytes, stored alongside the
+/// ciphertext with the iteration count so the key can be derived again.
+pub fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    key_type: KeyType,
+) -> Result<Vec<u8>, Pbkdf2Error> {
+    pbkdf2_hmac_sha256(password, salt, iterations, key_type.n_k() * 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::encode_hex;
+
+    #[test]
+    fn pbkdf2_test() {
+        // the PBKDF2-HMAC-SHA256 examples from RFC 7914 section 11, and the
+        // commonly used SHA-256 versions of the RFC 6070 examples
+        let examples: [(&[u8], &[u8], u32, &str); 5] = [
+            (
+                b"passwd",
+                b"salt",
+                1,
+                "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                 49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783",
+            ),
+            (
+                b"password",
+                b"salt",
+                1,
+                "120fb6cffcf8b32c43e7225256c4f837a86548c9",
+            ),
+            (
+                b"password",
+                b"salt",
+                4096,
+                "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a",
+            ),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "348c89dbcbd32b2f32d814b8116e84cf2b17347ebc1800181c4e2a1fb8dd53e1c635518c7dac47e9",
+            ),
+            (
+                b"pass\0word",
+                b"sa\0lt",
+                4096,
+                "89b69d0516f829893c696226650a8687",
+            ),
+        ];
+
+        for (password, salt, iterations, output) in examples {
+            let derived = pbkdf2_hmac_sha256(password, salt, iterations, output.len() / 2);
+            assert_eq!(encode_hex(&derived.unwrap()), output);
+        }
+    }
+
+    #[test]
+    fn derive_key_test() {
+        let salt = [0x5a; SALT_LEN];
+        let key = derive_key(b"password", &salt, 10, KeyType::Aes192).unwrap();
+        assert_eq!(key.len(), 24);
+
+        // a longer key starts with the shorter one
+        let longer = derive_key(b"password", &salt, 10, KeyType::Aes256).unwrap();
+        assert_eq!(longer[..24], key);
+        assert_ne!(
+            derive_key(b"password", &[0; SALT_LEN], 10, KeyType::Aes256).unwrap(),
+            longer
+        );
+
+        assert_eq!(
+            derive_key(b"password", &salt, 0, KeyType::Aes128),
+            Err(Pbkdf2Error::ZeroIterations)
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! PMAC1 parallelizable message authentication (Rogaway)
+//!
+//! Every block but the last is masked with its own offset and encrypted
+//! independently, and the results are combined with XOR. The offset for block
+//! `i` is `gamma(i) * L`, where `gamma` is the Gray code, so consecutive
+//! offsets differ by one of the precomputed `L_j = 2^j * L` values and any
+//! offset can also be computed directly. That lets [`pmac_parallel`] hand each
+//! thread its own run of blocks and still produce the same tag as [`pmac`].
+
+use std::thread;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::finite_field::{dbl, halve};
+use crate::util::xor_in_place;
+
+/// The key-dependent values `L * x^-1` and `L_0, L_1, ...`
+struct Offsets {
+    l_inv: [u8; 16],
+    l: Vec<[u8; 16]>,
+}
+
+impl Offsets {
+    /// Precompute enough of the table for inputs of up to `max_blocks` blocks
+    fn new(max_blocks: usize, expanded_key: &[u32], key_type: KeyType) -> Self {
+        let l = cipher_block(&[0; 16], expanded_key, key_type);
+        let l_inv = halve(&l);
+
+        let table_len = (usize::BITS - max_blocks.leading_zeros()).max(1) as usize;
+        let l = std::iter::successors(Some(l), |l_i| Some(dbl(l_i)))
+            .take(table_len)
+            .collect();
+
+        Self { l_inv, l }
+    }
+
+    /// The value `L_{ntz(i)}` that is added to the offset for block `i`
+    fn for_block(&self, i: usize) -> &[u8; 16] {
+        &self.l[i.trailing_zeros() as usize]
+    }
+
+    /// The offset for block `i`, computed directly from the Gray code of `i`
+    fn at(&self, i: usize) -> [u8; 16] {
+        let gray = i ^ (i >> 1);
+        let mut offset = [0; 16];
+
+        for (j, l_j) in self.l.iter().enumerate() {
+            if gray & (1 << j) != 0 {
+                xor_in_place(&mut offset, l_j);
+            }
+        }
+
+        offset
+    }
+}
+
+/// Compute the 16-byte PMAC tag of `message`
+pub fn pmac(message: &[u8], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let (blocks, last) = split_last_block(message);
+    let offsets = Offsets::new(blocks.len() / 16, expanded_key, key_type);
+
+    let sum = sum_blocks(blocks, 1, &offsets, expanded_key, key_type);
+    finish(sum, last, &offsets, expanded_key, key_type)
+}
+
+/// Compute the same tag as [`pmac`], spreading the block cipher calls across
+/// up to `threads` threads
+///
+/// Spawning threads only pays off for large inputs, so short messages are
+/// better served by [`pmac`].
+pub fn pmac_parallel(
+    message: &[u8],
+    threads: usize,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let (blocks, last) = split_last_block(message);
+    let block_count = blocks.len() / 16;
+    let offsets = Offsets::new(block_count, expanded_key, key_type);
+
+    let threads = threads.clamp(1, block_count.max(1));
+    let blocks_per_thread = block_count.div_ceil(threads).max(1);
+
+    let sum = thread::scope(|scope| {
+        let handles: Vec<_> = blocks
+            .chunks(blocks_per_thread * 16)
+            .enumerate()
+            .map(|(n, run)| {
+                let offsets = &offsets;
+                let first_index = 1 + n * blocks_per_thread;
+                scope.spawn(move || sum_blocks(run, first_index, offsets, expanded_key, key_type))
+            })
+            .collect();
+
+        handles.into_iter().fold([0; 16], |mut sum, handle| {
+            xor_in_place(&mut sum, &handle.join().unwrap());
+            sum
+        })
+    });
+
+    finish(sum, last, &offsets, expanded_key, key_type)
+}
+
+/// Split `message` into the whole blocks that are masked with an offset and
+/// the final (possibly partial, possibly empty) block that isn't
+fn split_last_block(message: &[u8]) -> (&[u8], &[u8]) {
+    let last_len = match message.len() % 16 {
+        0 => message.len().min(16),
+        remainder => remainder,
+    };
+    message.split_at(message.len() - last_len)
+}
+
+/// XOR together the encrypted, offset-masked blocks of `blocks`, the first of
+/// which is block number `first_index` of the message
+fn sum_blocks(
+    blocks: &[u8],
+    first_index: usize,
+    offsets: &Offsets,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    let mut offset = offsets.at(first_index - 1);
+    let mut sum = [0; 16];
+
+    for (i, block) in (first_index..).zip(blocks.chunks_exact(16)) {
+        xor_in_place(&mut offset, offsets.for_block(i));
+
+        let mut input: [u8; 16] = block.try_into().unwrap();
+        xor_in_place(&mut input, &offset);
+        xor_in_place(&mut sum, &cipher_block(&input, expanded_key, key_type));
+    }
+
+    sum
+}
+
+fn finish(
+    mut sum: [u8; 16],
+    last: &[u8],
+    offsets: &Offsets,
+    expanded_key: &[u32],
+    key_type: KeyType,
+) -> [u8; 16] {
+    if last.len() == 16 {
+        xor_in_place(&mut sum, last);
+        xor_in_place(&mut sum, &offsets.l_inv);
+    } else {
+        let mut padded = [0; 16];
+        padded[..last.len()].copy_from_slice(last);
+        padded[last.len()] = 0x80;
+        xor_in_place(&mut sum, &padded);
+    }
+
+    cipher_block(&sum, expanded_key, key_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    const KEY: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+
+    fn counting_message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    // Test vectors from the Miscreant PMAC implementations, which follow
+    // Rogaway's PMAC1 reference code
+
+    #[test]
+    fn pmac_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+
+        let vectors = [
+            (
+                0,
+                [
+                    0x43, 0x99, 0x57, 0x2c, 0xd6, 0xea, 0x53, 0x41, 0xb8, 0xd3, 0x58, 0x76, 0xa7,
+                    0x09, 0x8a, 0xf7,
+                ],
+            ),
+            (
+                3,
+                [
+                    0x25, 0x6b, 0xa5, 0x19, 0x3c, 0x1b, 0x99, 0x1b, 0x4d, 0xf0, 0xc5, 0x1f, 0x38,
+                    0x8a, 0x9e, 0x27,
+                ],
+            ),
+            (
+                16,
+                [
+                    0xeb, 0xbd, 0x82, 0x2f, 0xa4, 0x58, 0xda, 0xf6, 0xdf, 0xda, 0xd7, 0xc2, 0x7d,
+                    0xa7, 0x63, 0x38,
+                ],
+            ),
+            (
+                20,
+                [
+                    0x04, 0x12, 0xca, 0x15, 0x0b, 0xbf, 0x79, 0x05, 0x8d, 0x8c, 0x75, 0xa5, 0x8c,
+                    0x99, 0x3f, 0x55,
+                ],
+            ),
+            (
+                32,
+                [
+                    0xe9, 0x7a, 0xc0, 0x4e, 0x9e, 0x5e, 0x33, 0x99, 0xce, 0x53, 0x55, 0xcd, 0x74,
+                    0x07, 0xbc, 0x75,
+                ],
+            ),
+            (
+                34,
+                [
+                    0x5c, 0xba, 0x7d, 0x5e, 0xb2, 0x4f, 0x7c, 0x86, 0xcc, 0xc5, 0x46, 0x04, 0xe5,
+                    0x3d, 0x55, 0x12,
+                ],
+            ),
+        ];
+
+        for (len, expected) in vectors {
+            let result = pmac(&counting_message(len), &expanded_key, KeyType::Aes128);
+            assert_eq!(result, expected, "message of {len} bytes");
+        }
+    }
+
+    #[test]
+    fn long_message_test() {
+        let expanded_key = key_expansion(&KEY, KeyType::Aes128);
+        let expected = [
+            0xc2, 0xc9, 0xfa, 0x1d, 0x99, 0x85, 0xf6, 0xf0, 0xd2, 0xaf, 0xf9, 0x15, 0xa0, 0xe8,
+            0xd9, 0x10,
+        ];
+
+        assert_eq!(pmac(&[0; 1000], &expanded_key, KeyType::Aes128), expected);
+        assert_eq!(
+            pmac_parallel(&[0; 1000], 4, &expanded_key, KeyType::Aes128),
+            expected
+        );
+    }
+
+    #[test]
+    fn parallel_test() {
+        let expanded_key = key_expansion(&[0x5a; 32], KeyType::Aes256);
+
+        // uneven splits, more threads than blocks, and runs that start at
+        // block indices whose Gray code sets several bits
+        for len in [0, 15, 16, 17, 48, 1023, 4096, 4097] {
+            let message = counting_message(len);
+            let expected = pmac(&message, &expanded_key, KeyType::Aes256);
+
+            for threads in [0, 1, 2, 3, 7, 64, 1000] {
+                let result = pmac_parallel(&message, threads, &expanded_key, KeyType::Aes256);
+                assert_eq!(result, expected, "{len} bytes on {threads} threads");
+            }
+        }
+    }
+}
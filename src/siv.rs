@@ -0,0 +1,285 @@
+//! AES-SIV deterministic authenticated encryption (RFC 5297)
+//!
+//! The synthetic IV is a CMAC-based PRF (S2V) of the associated data and the
+//! plaintext, which doubles as the authentication tag. Because nothing but the
+//! inputs goes into the IV, encrypting the same inputs twice gives the same
+//! ciphertext, and reusing a nonce only reveals whether two messages are equal.
+
+use std::fmt::Display;
+
+use crate::cmac::cmac;
+use crate::constant::KeyType;
+use crate::ctr::ctr_encrypt;
+use crate::finite_field::dbl;
+use crate::util::{constant_time_eq, xor_in_place};
+
+/// The most associated data components S2V can take alongside the plaintext
+pub const MAX_ASSOCIATED_DATA: usize = 126;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SivError {
+    /// More than [`MAX_ASSOCIATED_DATA`] associated data components were given
+    TooManyComponents(usize),
+    /// The ciphertext is shorter than the synthetic IV
+    TooShort,
+    /// The synthetic IV didn't match the decrypted plaintext
+    AuthenticationFailed,
+}
+
+impl Display for SivError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyComponents(count) => {
+                write!(f, "{count} associated data components is too many")
+            }
+            Self::TooShort => write!(f, "ciphertext is shorter than the synthetic IV"),
+            Self::AuthenticationFailed => write!(f, "synthetic IV verification failed"),
+        }
+    }
+}
+
+impl std::error::Error for SivError {}
+
+/// The S2V pseudo-random function over a vector of strings
+pub fn s2v(components: &[&[u8]], expanded_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let Some((last, rest)) = components.split_last() else {
+        let mut one = [0; 16];
+        one[15] = 0x01;
+        return cmac(&one, expanded_key, key_type);
+    };
+
+    let mut d = cmac(&[0; 16], expanded_key, key_type);
+    for component in rest {
+        d = dbl(&d);
+        xor_in_place(&mut d, &cmac(component, expanded_key, key_type));
+    }
+
+    let t = if last.len() >= 16 {
+        // xor D into the last 16 bytes ("xorend")
+        let mut t = last.to_vec();
+        let start = t.len() - 16;
+        xor_in_place(&mut t[start..], &d);
+        t
+    } else {
+        let mut padded = [0; 16];
+        padded[..last.len()].copy_from_slice(last);
+        padded[last.len()] = 0x80;
+
+        let mut t = dbl(&d);
+        xor_in_place(&mut t, &padded);
+        t.to_vec()
+    };
+
+    cmac(&t, expanded_key, key_type)
+}
+
+/// Encrypt `plaintext`, returning the synthetic IV followed by the ciphertext
+///
+/// `mac_key` is the first half of the SIV key (K1) and `ctr_key` the second
+/// half (K2). For nonce-based use, the nonce is passed as the last associated
+/// data component.
+pub fn siv_encrypt(
+    associated_data: &[&[u8]],
+    plaintext: &[u8],
+    mac_key: &[u32],
+    ctr_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, SivError> {
+    if associated_data.len() > MAX_ASSOCIATED_DATA {
+        return Err(SivError::TooManyComponents(associated_data.len()));
+    }
+
+    let mut components = associated_data.to_vec();
+    components.push(plaintext);
+    let v = s2v(&components, mac_key, key_type);
+
+    let mut result = v.to_vec();
+    result.extend(ctr_encrypt(plaintext, &clear_bits(&v), ctr_key, key_type));
+    Ok(result)
+}
+
+/// Decrypt a ciphertext produced by [`siv_encrypt`], verifying its synthetic IV
+pub fn siv_decrypt(
+    associated_data: &[&[u8]],
+    ciphertext: &[u8],
+    mac_key: &[u32],
+    ctr_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, SivError> {
+    if associated_data.len() > MAX_ASSOCIATED_DATA {
+        return Err(SivError::TooManyComponents(associated_data.len()));
+    }
+    if ciphertext.len() < 16 {
+        return Err(SivError::TooShort);
+    }
+
+    let (v, ciphertext) = ciphertext.split_at(16);
+    let v: [u8; 16] = v.try_into().unwrap();
+    let plaintext = ctr_encrypt(ciphertext, &clear_bits(&v), ctr_key, key_type);
+
+    let mut components = associated_data.to_vec();
+    components.push(&plaintext);
+    let t = s2v(&components, mac_key, key_type);
+
+    if !constant_time_eq(&t, &v) {
+        return Err(SivError::AuthenticationFailed);
+    }
+
+    Ok(plaintext)
+}
+
+/// Clear the top bit of the last two 32-bit words, so implementations can
+/// increment the counter with 64-bit (or 32-bit) arithmetic
+fn clear_bits(v: &[u8; 16]) -> [u8; 16] {
+    let mut q = *v;
+    q[8] &= 0x7f;
+    q[12] &= 0x7f;
+    q
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+
+    #[test]
+    fn deterministic_test() {
+        // A.1 from RFC 5297
+        let mac_key = [
+            0xff, 0xfe, 0xfd, 0xfc, 0xfb, 0xfa, 0xf9, 0xf8, 0xf7, 0xf6, 0xf5, 0xf4, 0xf3, 0xf2,
+            0xf1, 0xf0,
+        ];
+        let ctr_key = [
+            0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd,
+            0xfe, 0xff,
+        ];
+        let associated_data = [
+            0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+            0x1e, 0x1f, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27,
+        ];
+        let plaintext = [
+            0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        ];
+        let expected = [
+            0x85, 0x63, 0x2d, 0x07, 0xc6, 0xe8, 0xf3, 0x7f, 0x95, 0x0a, 0xcd, 0x32, 0x0a, 0x2e,
+            0xcc, 0x93, 0x40, 0xc0, 0x2b, 0x96, 0x90, 0xc4, 0xdc, 0x04, 0xda, 0xef, 0x7f, 0x6a,
+            0xfe, 0x5c,
+        ];
+
+        let mac_key = key_expansion(&mac_key, KeyType::Aes128);
+        let ctr_key = key_expansion(&ctr_key, KeyType::Aes128);
+
+        let result = siv_encrypt(
+            &[&associated_data],
+            &plaintext,
+            &mac_key,
+            &ctr_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+
+        let result = siv_decrypt(
+            &[&associated_data],
+            &expected,
+            &mac_key,
+            &ctr_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn nonce_based_test() {
+        // A.2 from RFC 5297
+        let mac_key = [
+            0x7f, 0x7e, 0x7d, 0x7c, 0x7b, 0x7a, 0x79, 0x78, 0x77, 0x76, 0x75, 0x74, 0x73, 0x72,
+            0x71, 0x70,
+        ];
+        let ctr_key = [
+            0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d,
+            0x4e, 0x4f,
+        ];
+        let associated_data_1 = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff, 0xde, 0xad, 0xda, 0xda, 0xde, 0xad, 0xda, 0xda, 0xff, 0xee, 0xdd, 0xcc,
+            0xbb, 0xaa, 0x99, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x00,
+        ];
+        let associated_data_2 = [0x10, 0x20, 0x30, 0x40, 0x50, 0x60, 0x70, 0x80, 0x90, 0xa0];
+        let nonce = [
+            0x09, 0xf9, 0x11, 0x02, 0x9d, 0x74, 0xe3, 0x5b, 0xd8, 0x41, 0x56, 0xc5, 0x63, 0x56,
+            0x88, 0xc0,
+        ];
+        let plaintext = b"this is some plaintext to encrypt using SIV-AES";
+        let expected = [
+            0x7b, 0xdb, 0x6e, 0x3b, 0x43, 0x26, 0x67, 0xeb, 0x06, 0xf4, 0xd1, 0x4b, 0xff, 0x2f,
+            0xbd, 0x0f, 0xcb, 0x90, 0x0f, 0x2f, 0xdd, 0xbe, 0x40, 0x43, 0x26, 0x60, 0x19, 0x65,
+            0xc8, 0x89, 0xbf, 0x17, 0xdb, 0xa7, 0x7c, 0xeb, 0x09, 0x4f, 0xa6, 0x63, 0xb7, 0xa3,
+            0xf7, 0x48, 0xba, 0x8a, 0xf8, 0x29, 0xea, 0x64, 0xad, 0x54, 0x4a, 0x27, 0x2e, 0x9c,
+            0x48, 0x5b, 0x62, 0xa3, 0xfd, 0x5c, 0x0d,
+        ];
+
+        let mac_key = key_expansion(&mac_key, KeyType::Aes128);
+        let ctr_key = key_expansion(&ctr_key, KeyType::Aes128);
+        let associated_data: [&[u8]; 3] = [&associated_data_1, &associated_data_2, &nonce];
+
+        let result = siv_encrypt(
+            &associated_data,
+            plaintext,
+            &mac_key,
+            &ctr_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, expected);
+
+        let result = siv_decrypt(
+            &associated_data,
+            &expected,
+            &mac_key,
+            &ctr_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn tampered_test() {
+        let mac_key = key_expansion(&[0x01; 16], KeyType::Aes128);
+        let ctr_key = key_expansion(&[0x02; 16], KeyType::Aes128);
+        let associated_data: [&[u8]; 2] = [b"header", b"index"];
+
+        let mut ciphertext = siv_encrypt(
+            &associated_data,
+            b"secret value",
+            &mac_key,
+            &ctr_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+
+        // the associated data is bound to the ciphertext, in order
+        let swapped: [&[u8]; 2] = [b"index", b"header"];
+        let result = siv_decrypt(&swapped, &ciphertext, &mac_key, &ctr_key, KeyType::Aes128);
+        assert_eq!(result, Err(SivError::AuthenticationFailed));
+
+        ciphertext[20] ^= 1;
+        let result = siv_decrypt(
+            &associated_data,
+            &ciphertext,
+            &mac_key,
+            &ctr_key,
+            KeyType::Aes128,
+        );
+        assert_eq!(result, Err(SivError::AuthenticationFailed));
+
+        let result = siv_decrypt(&[], &[0; 15], &mac_key, &ctr_key, KeyType::Aes128);
+        assert_eq!(result, Err(SivError::TooShort));
+
+        let too_many: Vec<&[u8]> = vec![b""; MAX_ASSOCIATED_DATA + 1];
+        let result = siv_encrypt(&too_many, b"", &mac_key, &ctr_key, KeyType::Aes128);
+        assert_eq!(result, Err(SivError::TooManyComponents(127)));
+    }
+}
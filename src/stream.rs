@@ -0,0 +1,139 @@
+//! Incremental encryption and decryption
+//!
+//! Each mode that can process its input in a single pass has an encryptor and
+//! a decryptor implementing the traits here. `update` can be called with input
+//! of any size and returns whatever output is ready, buffering partial blocks
+//! (and, when decrypting, whatever might turn out to be padding or a tag).
+//! `finalize` processes the rest of the buffer and adds or checks the padding
+//! or tag.
+//!
+//! The streaming AEAD decryptors necessarily return plaintext before the tag
+//! has been checked, so nothing they output should be acted on until
+//! `finalize` succeeds. SIV, AES-GCM-SIV, CCM* and key wrap need the whole
+//! message before producing any output, so they have no streaming form.
+
+use std::fmt::Display;
+
+use crate::padding::PaddingError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamError {
+    /// The decrypted padding was malformed, or the ciphertext wasn't a whole
+    /// number of blocks
+    Padding(PaddingError),
+    /// The input ended before the mode had enough to work with (such as less
+    /// than a block for ciphertext stealing, or less than the tag)
+    TooShort(usize),
+    /// The tag didn't match
+    AuthenticationFailed,
+    /// Every chunk of a chunked stream authenticated, but the input ended
+    /// before the last one
+    Truncated,
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Padding(error) => write!(f, "{error}"),
+            Self::TooShort(len) => write!(f, "input of {len} bytes is too short"),
+            Self::AuthenticationFailed => write!(f, "tag verification failed"),
+            Self::Truncated => write!(f, "the input was truncated"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+impl From<PaddingError> for StreamError {
+    fn from(error: PaddingError) -> Self {
+        Self::Padding(error)
+    }
+}
+
+/// Incremental encryption
+pub trait Encryptor {
+    /// Encrypt more of the plaintext, returning the ciphertext that is ready
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Encrypt whatever is still buffered, adding any padding or tag
+    fn finalize(self) -> Result<Vec<u8>, StreamError>;
+}
+
+/// Incremental decryption
+pub trait Decryptor {
+    /// Decrypt more of the ciphertext, returning the plaintext that is ready
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8>;
+
+    /// Decrypt whatever is still buffered, checking any padding or tag
+    fn finalize(self) -> Result<Vec<u8>, StreamError>;
+}
+
+/// Input waiting to be processed in whole units (usually blocks)
+#[derive(Default)]
+pub(crate) struct Buffer {
+    data: Vec<u8>,
+}
+
+impl Buffer {
+    /// Add `input`, then remove and return as many whole `unit`-sized pieces
+    /// as possible while leaving at least `hold_back` bytes buffered
+    pub(crate) fn take(&mut self, input: &[u8], unit: usize, hold_back: usize) -> Vec<u8> {
+        self.data.extend(input);
+
+        let available = self.data.len().saturating_sub(hold_back);
+        let len = available - available % unit;
+        self.data.drain(..len).collect()
+    }
+
+    /// Everything left in the buffer
+    pub(crate) fn remaining(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Stream `plaintext` through fresh encryptors in chunks of several sizes,
+/// checking each result against the one-shot `expected` ciphertext, and then
+/// stream that back through fresh decryptors the same way
+#[cfg(test)]
+pub(crate) fn check_streaming<E: Encryptor, D: Decryptor>(
+    plaintext: &[u8],
+    expected: &[u8],
+    new_encryptor: impl Fn() -> E,
+    new_decryptor: impl Fn() -> D,
+) {
+    for chunk_size in [1, 7, 16, 33] {
+        let mut encryptor = new_encryptor();
+        let mut ciphertext = Vec::new();
+        for chunk in plaintext.chunks(chunk_size) {
+            ciphertext.extend(encryptor.update(chunk));
+        }
+        ciphertext.extend(encryptor.finalize().unwrap());
+        assert_eq!(ciphertext, expected, "chunk size {chunk_size}");
+
+        let mut decryptor = new_decryptor();
+        let mut result = Vec::new();
+        for chunk in ciphertext.chunks(chunk_size) {
+            result.extend(decryptor.update(chunk));
+        }
+        result.extend(decryptor.finalize().unwrap());
+        assert_eq!(result, plaintext, "chunk size {chunk_size}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_test() {
+        let mut buffer = Buffer::default();
+
+        assert_eq!(buffer.take(&[1; 10], 16, 0), []);
+        assert_eq!(buffer.take(&[2; 10], 16, 0).len(), 16);
+
+        // a whole block is kept back until there's something after it
+        assert_eq!(buffer.take(&[3; 12], 16, 1), []);
+        assert_eq!(buffer.take(&[4; 1], 16, 1).len(), 16);
+        assert_eq!(buffer.remaining(), [4]);
+    }
+}
@@ -0,0 +1,50 @@
+//! Small byte-level helpers shared between the modes of operation
+
+use std::thread;
+
+/// XOR `other` into `target`, stopping at the end of the shorter slice
+pub fn xor_in_place(target: &mut [u8], other: &[u8]) {
+    for (t, o) in target.iter_mut().zip(other) {
+        *t ^= o;
+    }
+}
+
+/// Pad `bytes` with zeros up to a whole number of blocks
+pub fn pad_to_block(bytes: &mut Vec<u8>) {
+    let remainder = bytes.len() % 16;
+    if remainder != 0 {
+        bytes.resize(bytes.len() + 16 - remainder, 0);
+    }
+}
+
+/// Compare two byte strings without short-circuiting on the first difference,
+/// so that tag checks don't leak how many leading bytes were correct
+pub fn constant_time_eq(x: &[u8], y: &[u8]) -> bool {
+    if x.len() != y.len() {
+        return false;
+    }
+
+    let difference = x.iter().zip(y).fold(0, |acc, (a, b)| acc | (a ^ b));
+    std::hint::black_box(difference) == 0
+}
+
+/// Split `data` into runs of whole `unit`-sized pieces and process each run
+/// on its own scoped thread, using at most `threads` threads
+///
+/// `process` is given the index of the first unit in its run. Only the last
+/// run can end with a partial unit.
+pub fn process_parallel<F>(data: &mut [u8], unit: usize, threads: usize, process: F)
+where
+    F: Fn(usize, &mut [u8]) + Sync,
+{
+    let units = data.len().div_ceil(unit);
+    let threads = threads.clamp(1, units.max(1));
+    let units_per_thread = units.div_ceil(threads).max(1);
+
+    thread::scope(|scope| {
+        for (n, run) in data.chunks_mut(units_per_thread * unit).enumerate() {
+            let process = &process;
+            scope.spawn(move || process(n * units_per_thread, run));
+        }
+    });
+}
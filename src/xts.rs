@@ -0,0 +1,789 @@
+//! XTS-AES mode (IEEE 1619), for encrypting sectors of storage devices
+//!
+//! Each data unit (sector) is encrypted under two independent keys: the first
+//! encrypts the data, while the second encrypts the sector number to produce
+//! the tweak. Data units that aren't a multiple of the block size are handled
+//! with ciphertext stealing, so the ciphertext is always the same length as the
+//! plaintext.
+
+use std::fmt::Display;
+
+use crate::cipher::cipher_block;
+use crate::constant::KeyType;
+use crate::finite_field::mul_alpha;
+use crate::inverse_cipher::inverse_cipher_block;
+use crate::stream::{Buffer, Decryptor, Encryptor, StreamError};
+use crate::util::{process_parallel, xor_in_place};
+
+/// The largest data unit allowed by IEEE 1619, in blocks
+const MAX_BLOCKS: usize = 1 << 20;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum XtsError {
+    /// Data units must contain at least one full block
+    DataUnitTooShort(usize),
+    /// Data units can't be longer than 2^20 blocks
+    DataUnitTooLong(usize),
+}
+
+impl Display for XtsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DataUnitTooShort(len) => write!(f, "data unit of {len} bytes is too short"),
+            Self::DataUnitTooLong(len) => write!(f, "data unit of {len} bytes is too long"),
+        }
+    }
+}
+
+impl std::error::Error for XtsError {}
+
+/// Encrypt a single data unit
+///
+/// `data_key` encrypts the data and `tweak_key` encrypts the sector number.
+/// Both must be expanded from keys of the same `key_type`.
+pub fn xts_encrypt(
+    plaintext: &[u8],
+    sector: u128,
+    data_key: &[u32],
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, XtsError> {
+    check_len(plaintext.len())?;
+
+    let mut tweaks = tweaks(sector, plaintext.len(), tweak_key, key_type);
+    let mut result = Vec::with_capacity(plaintext.len());
+    let full_blocks = plaintext.len() / 16;
+    let remainder = plaintext.len() % 16;
+
+    // when stealing, the last full block is handled with the partial one
+    let normal_blocks = if remainder == 0 {
+        full_blocks
+    } else {
+        full_blocks - 1
+    };
+
+    for block in plaintext.chunks_exact(16).take(normal_blocks) {
+        let tweak = tweaks.next().unwrap();
+        result.extend(encrypt_block(block, &tweak, data_key, key_type));
+    }
+
+    if remainder != 0 {
+        let last_full = &plaintext[normal_blocks * 16..full_blocks * 16];
+        let partial = &plaintext[full_blocks * 16..];
+
+        let stolen = encrypt_block(last_full, &tweaks.next().unwrap(), data_key, key_type);
+
+        let mut last = partial.to_vec();
+        last.extend(&stolen[remainder..]);
+
+        result.extend(encrypt_block(
+            &last,
+            &tweaks.next().unwrap(),
+            data_key,
+            key_type,
+        ));
+        result.extend(&stolen[..remainder]);
+    }
+
+    Ok(result)
+}
+
+/// Decrypt a single data unit produced by [`xts_encrypt`]
+pub fn xts_decrypt(
+    ciphertext: &[u8],
+    sector: u128,
+    data_key: &[u32],
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, XtsError> {
+    check_len(ciphertext.len())?;
+
+    let mut tweaks = tweaks(sector, ciphertext.len(), tweak_key, key_type);
+    let mut result = Vec::with_capacity(ciphertext.len());
+    let full_blocks = ciphertext.len() / 16;
+    let remainder = ciphertext.len() % 16;
+
+    let normal_blocks = if remainder == 0 {
+        full_blocks
+    } else {
+        full_blocks - 1
+    };
+
+    for block in ciphertext.chunks_exact(16).take(normal_blocks) {
+        let tweak = tweaks.next().unwrap();
+        result.extend(decrypt_block(block, &tweak, data_key, key_type));
+    }
+
+    if remainder != 0 {
+        let last_full = &ciphertext[normal_blocks * 16..full_blocks * 16];
+        let partial = &ciphertext[full_blocks * 16..];
+
+        // the tweaks are used in the opposite order to encryption
+        let second_to_last_tweak = tweaks.next().unwrap();
+        let last_tweak = tweaks.next().unwrap();
+
+        let stolen = decrypt_block(last_full, &last_tweak, data_key, key_type);
+
+        let mut last = partial.to_vec();
+        last.extend(&stolen[remainder..]);
+
+        result.extend(decrypt_block(
+            &last,
+            &second_to_last_tweak,
+            data_key,
+            key_type,
+        ));
+        result.extend(&stolen[..remainder]);
+    }
+
+    Ok(result)
+}
+
+/// Encrypt consecutive data units of `sector_size` bytes, starting at
+/// `first_sector`. The final data unit may be shorter than `sector_size`.
+pub fn xts_encrypt_sectors(
+    plaintext: &[u8],
+    first_sector: u128,
+    sector_size: usize,
+    data_key: &[u32],
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, XtsError> {
+    check_sector_size(sector_size)?;
+    let mut result = Vec::with_capacity(plaintext.len());
+
+    for (sector, data_unit) in (first_sector..).zip(plaintext.chunks(sector_size)) {
+        result.extend(xts_encrypt(
+            data_unit, sector, data_key, tweak_key, key_type,
+        )?);
+    }
+
+    Ok(result)
+}
+
+/// Decrypt consecutive data units produced by [`xts_encrypt_sectors`]
+pub fn xts_decrypt_sectors(
+    ciphertext: &[u8],
+    first_sector: u128,
+    sector_size: usize,
+    data_key: &[u32],
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, XtsError> {
+    check_sector_size(sector_size)?;
+    let mut result = Vec::with_capacity(ciphertext.len());
+
+    for (sector, data_unit) in (first_sector..).zip(ciphertext.chunks(sector_size)) {
+        result.extend(xts_decrypt(
+            data_unit, sector, data_key, tweak_key, key_type,
+        )?);
+    }
+
+    Ok(result)
+}
+
+/// Encrypt consecutive data units like [`xts_encrypt_sectors`], splitting them
+/// between up to `threads` threads
+pub fn xts_encrypt_sectors_parallel(
+    plaintext: &[u8],
+    first_sector: u128,
+    sector_size: usize,
+    threads: usize,
+    data_key: &[u32],
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, XtsError> {
+    check_sectors(plaintext.len(), sector_size)?;
+
+    let mut result = plaintext.to_vec();
+    process_parallel(&mut result, sector_size, threads, |first, run| {
+        let output = xts_encrypt_sectors(
+            run,
+            first_sector + first as u128,
+            sector_size,
+            data_key,
+            tweak_key,
+            key_type,
+        )
+        .expect("data unit lengths were already checked");
+        run.copy_from_slice(&output);
+    });
+
+    Ok(result)
+}
+
+/// Decrypt consecutive data units like [`xts_decrypt_sectors`], splitting them
+/// between up to `threads` threads
+pub fn xts_decrypt_sectors_parallel(
+    ciphertext: &[u8],
+    first_sector: u128,
+    sector_size: usize,
+    threads: usize,
+    data_key: &[u32],
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> Result<Vec<u8>, XtsError> {
+    check_sectors(ciphertext.len(), sector_size)?;
+
+    let mut result = ciphertext.to_vec();
+    process_parallel(&mut result, sector_size, threads, |first, run| {
+        let output = xts_decrypt_sectors(
+            run,
+            first_sector + first as u128,
+            sector_size,
+            data_key,
+            tweak_key,
+            key_type,
+        )
+        .expect("data unit lengths were already checked");
+        run.copy_from_slice(&output);
+    });
+
+    Ok(result)
+}
+
+/// Incremental encryption of consecutive data units, like
+/// [`xts_encrypt_sectors`]
+///
+/// Each data unit is encrypted as soon as all of it has arrived. A final
+/// partial data unit is encrypted by [`Encryptor::finalize`].
+pub struct XtsEncryptor<'a> {
+    sector: u128,
+    sector_size: usize,
+    data_key: &'a [u32],
+    tweak_key: &'a [u32],
+    key_type: KeyType,
+    buffer: Buffer,
+}
+
+impl<'a> XtsEncryptor<'a> {
+    pub fn new(
+        first_sector: u128,
+        sector_size: usize,
+        data_key: &'a [u32],
+        tweak_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, XtsError> {
+        check_len(sector_size)?;
+
+        Ok(Self {
+            sector: first_sector,
+            sector_size,
+            data_key,
+            tweak_key,
+            key_type,
+            buffer: Buffer::default(),
+        })
+    }
+}
+
+impl Encryptor for XtsEncryptor<'_> {
+    fn update(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let data_units = self.buffer.take(plaintext, self.sector_size, 0);
+        let result = xts_encrypt_sectors(
+            &data_units,
+            self.sector,
+            self.sector_size,
+            self.data_key,
+            self.tweak_key,
+            self.key_type,
+        )
+        .expect("whole data units have a valid length");
+
+        self.sector += (data_units.len() / self.sector_size) as u128;
+        result
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let last = std::mem::take(&mut self.buffer).remaining();
+        if last.is_empty() {
+            return Ok(last);
+        }
+
+        xts_encrypt(
+            &last,
+            self.sector,
+            self.data_key,
+            self.tweak_key,
+            self.key_type,
+        )
+        .map_err(|_| StreamError::TooShort(last.len()))
+    }
+}
+
+/// Incremental decryption of consecutive data units, like
+/// [`xts_decrypt_sectors`]
+pub struct XtsDecryptor<'a> {
+    sector: u128,
+    sector_size: usize,
+    data_key: &'a [u32],
+    tweak_key: &'a [u32],
+    key_type: KeyType,
+    buffer: Buffer,
+}
+
+impl<'a> XtsDecryptor<'a> {
+    pub fn new(
+        first_sector: u128,
+        sector_size: usize,
+        data_key: &'a [u32],
+        tweak_key: &'a [u32],
+        key_type: KeyType,
+    ) -> Result<Self, XtsError> {
+        check_len(sector_size)?;
+
+        Ok(Self {
+            sector: first_sector,
+            sector_size,
+            data_key,
+            tweak_key,
+            key_type,
+            buffer: Buffer::default(),
+        })
+    }
+}
+
+impl Decryptor for XtsDecryptor<'_> {
+    fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        let data_units = self.buffer.take(ciphertext, self.sector_size, 0);
+        let result = xts_decrypt_sectors(
+            &data_units,
+            self.sector,
+            self.sector_size,
+            self.data_key,
+            self.tweak_key,
+            self.key_type,
+        )
+        .expect("whole data units have a valid length");
+
+        self.sector += (data_units.len() / self.sector_size) as u128;
+        result
+    }
+
+    fn finalize(mut self) -> Result<Vec<u8>, StreamError> {
+        let last = std::mem::take(&mut self.buffer).remaining();
+        if last.is_empty() {
+            return Ok(last);
+        }
+
+        xts_decrypt(
+            &last,
+            self.sector,
+            self.data_key,
+            self.tweak_key,
+            self.key_type,
+        )
+        .map_err(|_| StreamError::TooShort(last.len()))
+    }
+}
+
+/// Check every data unit of an input of `len` bytes split into `sector_size`
+/// pieces, failing on the first invalid one as the serial functions would
+fn check_sectors(len: usize, sector_size: usize) -> Result<(), XtsError> {
+    check_sector_size(sector_size)?;
+    if len >= sector_size {
+        check_len(sector_size)?;
+    }
+    match len % sector_size {
+        0 => Ok(()),
+        last_len => check_len(last_len),
+    }
+}
+
+/// A sector size under a block is rejected even when there's no data, so it
+/// never reaches `chunks`
+fn check_sector_size(sector_size: usize) -> Result<(), XtsError> {
+    if sector_size < 16 {
+        return Err(XtsError::DataUnitTooShort(sector_size));
+    }
+    Ok(())
+}
+
+fn check_len(len: usize) -> Result<(), XtsError> {
+    if len < 16 {
+        Err(XtsError::DataUnitTooShort(len))
+    } else if len > MAX_BLOCKS * 16 {
+        Err(XtsError::DataUnitTooLong(len))
+    } else {
+        Ok(())
+    }
+}
+
+/// The tweak for each block of a data unit: the encrypted sector number,
+/// multiplied by `alpha` once for every block that comes before it
+fn tweaks(
+    sector: u128,
+    len: usize,
+    tweak_key: &[u32],
+    key_type: KeyType,
+) -> impl Iterator<Item = [u8; 16]> {
+    let first = cipher_block(&sector.to_le_bytes(), tweak_key, key_type);
+    let num_blocks = len.div_ceil(16);
+
+    std::iter::successors(Some(first), |tweak| Some(mul_alpha(tweak))).take(num_blocks)
+}
+
+fn encrypt_block(block: &[u8], tweak: &[u8; 16], data_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let mut input: [u8; 16] = block.try_into().unwrap();
+    xor_in_place(&mut input, tweak);
+
+    let mut output = cipher_block(&input, data_key, key_type);
+    xor_in_place(&mut output, tweak);
+    output
+}
+
+fn decrypt_block(block: &[u8], tweak: &[u8; 16], data_key: &[u32], key_type: KeyType) -> [u8; 16] {
+    let mut input: [u8; 16] = block.try_into().unwrap();
+    xor_in_place(&mut input, tweak);
+
+    let mut output = inverse_cipher_block(&input, data_key, key_type);
+    xor_in_place(&mut output, tweak);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_expansion::key_expansion;
+    use crate::stream::check_streaming;
+
+    // Test vectors from IEEE 1619-2007 Annex B
+
+    fn check_vector(
+        key_1: &[u8],
+        key_2: &[u8],
+        key_type: KeyType,
+        sector: u128,
+        plaintext: &[u8],
+        ciphertext: &[u8],
+    ) {
+        let data_key = key_expansion(key_1, key_type);
+        let tweak_key = key_expansion(key_2, key_type);
+
+        let result = xts_encrypt(plaintext, sector, &data_key, &tweak_key, key_type).unwrap();
+        assert_eq!(result, ciphertext);
+
+        let result = xts_decrypt(ciphertext, sector, &data_key, &tweak_key, key_type).unwrap();
+        assert_eq!(result, plaintext);
+    }
+
+    #[test]
+    fn vector_1_test() {
+        let ciphertext = [
+            0x91, 0x7c, 0xf6, 0x9e, 0xbd, 0x68, 0xb2, 0xec, 0x9b, 0x9f, 0xe9, 0xa3, 0xea, 0xdd,
+            0xa6, 0x92, 0xcd, 0x43, 0xd2, 0xf5, 0x95, 0x98, 0xed, 0x85, 0x8c, 0x02, 0xc2, 0x65,
+            0x2f, 0xbf, 0x92, 0x2e,
+        ];
+
+        check_vector(
+            &[0; 16],
+            &[0; 16],
+            KeyType::Aes128,
+            0,
+            &[0; 32],
+            &ciphertext,
+        );
+    }
+
+    #[test]
+    fn vector_2_test() {
+        let ciphertext = [
+            0xc4, 0x54, 0x18, 0x5e, 0x6a, 0x16, 0x93, 0x6e, 0x39, 0x33, 0x40, 0x38, 0xac, 0xef,
+            0x83, 0x8b, 0xfb, 0x18, 0x6f, 0xff, 0x74, 0x80, 0xad, 0xc4, 0x28, 0x93, 0x82, 0xec,
+            0xd6, 0xd3, 0x94, 0xf0,
+        ];
+
+        check_vector(
+            &[0x11; 16],
+            &[0x22; 16],
+            KeyType::Aes128,
+            0x3333333333,
+            &[0x44; 32],
+            &ciphertext,
+        );
+    }
+
+    #[test]
+    fn vector_3_test() {
+        let key_1 = [
+            0xff, 0xfe, 0xfd, 0xfc, 0xfb, 0xfa, 0xf9, 0xf8, 0xf7, 0xf6, 0xf5, 0xf4, 0xf3, 0xf2,
+            0xf1, 0xf0,
+        ];
+        let ciphertext = [
+            0xaf, 0x85, 0x33, 0x6b, 0x59, 0x7a, 0xfc, 0x1a, 0x90, 0x0b, 0x2e, 0xb2, 0x1e, 0xc9,
+            0x49, 0xd2, 0x92, 0xdf, 0x4c, 0x04, 0x7e, 0x0b, 0x21, 0x53, 0x21, 0x86, 0xa5, 0x97,
+            0x1a, 0x22, 0x7a, 0x89,
+        ];
+
+        check_vector(
+            &key_1,
+            &[0x22; 16],
+            KeyType::Aes128,
+            0x3333333333,
+            &[0x44; 32],
+            &ciphertext,
+        );
+    }
+
+    #[test]
+    fn vector_10_test() {
+        let key_1 = [
+            0x27, 0x18, 0x28, 0x18, 0x28, 0x45, 0x90, 0x45, 0x23, 0x53, 0x60, 0x28, 0x74, 0x71,
+            0x35, 0x26, 0x62, 0x49, 0x77, 0x57, 0x24, 0x70, 0x93, 0x69, 0x99, 0x59, 0x57, 0x49,
+            0x66, 0x96, 0x76, 0x27,
+        ];
+        let key_2 = [
+            0x31, 0x41, 0x59, 0x26, 0x53, 0x58, 0x97, 0x93, 0x23, 0x84, 0x62, 0x64, 0x33, 0x83,
+            0x27, 0x95, 0x02, 0x88, 0x41, 0x97, 0x16, 0x93, 0x99, 0x37, 0x51, 0x05, 0x82, 0x09,
+            0x74, 0x94, 0x45, 0x92,
+        ];
+        let plaintext: Vec<u8> = (0..=255).chain(0..=255).collect();
+        let ciphertext = [
+            0x1c, 0x3b, 0x3a, 0x10, 0x2f, 0x77, 0x03, 0x86, 0xe4, 0x83, 0x6c, 0x99, 0xe3, 0x70,
+            0xcf, 0x9b, 0xea, 0x00, 0x80, 0x3f, 0x5e, 0x48, 0x23, 0x57, 0xa4, 0xae, 0x12, 0xd4,
+            0x14, 0xa3, 0xe6, 0x3b, 0x5d, 0x31, 0xe2, 0x76, 0xf8, 0xfe, 0x4a, 0x8d, 0x66, 0xb3,
+            0x17, 0xf9, 0xac, 0x68, 0x3f, 0x44, 0x68, 0x0a, 0x86, 0xac, 0x35, 0xad, 0xfc, 0x33,
+            0x45, 0xbe, 0xfe, 0xcb, 0x4b, 0xb1, 0x88, 0xfd, 0x57, 0x76, 0x92, 0x6c, 0x49, 0xa3,
+            0x09, 0x5e, 0xb1, 0x08, 0xfd, 0x10, 0x98, 0xba, 0xec, 0x70, 0xaa, 0xa6, 0x69, 0x99,
+            0xa7, 0x2a, 0x82, 0xf2, 0x7d, 0x84, 0x8b, 0x21, 0xd4, 0xa7, 0x41, 0xb0, 0xc5, 0xcd,
+            0x4d, 0x5f, 0xff, 0x9d, 0xac, 0x89, 0xae, 0xba, 0x12, 0x29, 0x61, 0xd0, 0x3a, 0x75,
+            0x71, 0x23, 0xe9, 0x87, 0x0f, 0x8a, 0xcf, 0x10, 0x00, 0x02, 0x08, 0x87, 0x89, 0x14,
+            0x29, 0xca, 0x2a, 0x3e, 0x7a, 0x7d, 0x7d, 0xf7, 0xb1, 0x03, 0x55, 0x16, 0x5c, 0x8b,
+            0x9a, 0x6d, 0x0a, 0x7d, 0xe8, 0xb0, 0x62, 0xc4, 0x50, 0x0d, 0xc4, 0xcd, 0x12, 0x0c,
+            0x0f, 0x74, 0x18, 0xda, 0xe3, 0xd0, 0xb5, 0x78, 0x1c, 0x34, 0x80, 0x3f, 0xa7, 0x54,
+            0x21, 0xc7, 0x90, 0xdf, 0xe1, 0xde, 0x18, 0x34, 0xf2, 0x80, 0xd7, 0x66, 0x7b, 0x32,
+            0x7f, 0x6c, 0x8c, 0xd7, 0x55, 0x7e, 0x12, 0xac, 0x3a, 0x0f, 0x93, 0xec, 0x05, 0xc5,
+            0x2e, 0x04, 0x93, 0xef, 0x31, 0xa1, 0x2d, 0x3d, 0x92, 0x60, 0xf7, 0x9a, 0x28, 0x9d,
+            0x6a, 0x37, 0x9b, 0xc7, 0x0c, 0x50, 0x84, 0x14, 0x73, 0xd1, 0xa8, 0xcc, 0x81, 0xec,
+            0x58, 0x3e, 0x96, 0x45, 0xe0, 0x7b, 0x8d, 0x96, 0x70, 0x65, 0x5b, 0xa5, 0xbb, 0xcf,
+            0xec, 0xc6, 0xdc, 0x39, 0x66, 0x38, 0x0a, 0xd8, 0xfe, 0xcb, 0x17, 0xb6, 0xba, 0x02,
+            0x46, 0x9a, 0x02, 0x0a, 0x84, 0xe1, 0x8e, 0x8f, 0x84, 0x25, 0x20, 0x70, 0xc1, 0x3e,
+            0x9f, 0x1f, 0x28, 0x9b, 0xe5, 0x4f, 0xbc, 0x48, 0x14, 0x57, 0x77, 0x8f, 0x61, 0x60,
+            0x15, 0xe1, 0x32, 0x7a, 0x02, 0xb1, 0x40, 0xf1, 0x50, 0x5e, 0xb3, 0x09, 0x32, 0x6d,
+            0x68, 0x37, 0x8f, 0x83, 0x74, 0x59, 0x5c, 0x84, 0x9d, 0x84, 0xf4, 0xc3, 0x33, 0xec,
+            0x44, 0x23, 0x88, 0x51, 0x43, 0xcb, 0x47, 0xbd, 0x71, 0xc5, 0xed, 0xae, 0x9b, 0xe6,
+            0x9a, 0x2f, 0xfe, 0xce, 0xb1, 0xbe, 0xc9, 0xde, 0x24, 0x4f, 0xbe, 0x15, 0x99, 0x2b,
+            0x11, 0xb7, 0x7c, 0x04, 0x0f, 0x12, 0xbd, 0x8f, 0x6a, 0x97, 0x5a, 0x44, 0xa0, 0xf9,
+            0x0c, 0x29, 0xa9, 0xab, 0xc3, 0xd4, 0xd8, 0x93, 0x92, 0x72, 0x84, 0xc5, 0x87, 0x54,
+            0xcc, 0xe2, 0x94, 0x52, 0x9f, 0x86, 0x14, 0xdc, 0xd2, 0xab, 0xa9, 0x91, 0x92, 0x5f,
+            0xed, 0xc4, 0xae, 0x74, 0xff, 0xac, 0x6e, 0x33, 0x3b, 0x93, 0xeb, 0x4a, 0xff, 0x04,
+            0x79, 0xda, 0x9a, 0x41, 0x0e, 0x44, 0x50, 0xe0, 0xdd, 0x7a, 0xe4, 0xc6, 0xe2, 0x91,
+            0x09, 0x00, 0x57, 0x5d, 0xa4, 0x01, 0xfc, 0x07, 0x05, 0x9f, 0x64, 0x5e, 0x8b, 0x7e,
+            0x9b, 0xfd, 0xef, 0x33, 0x94, 0x30, 0x54, 0xff, 0x84, 0x01, 0x14, 0x93, 0xc2, 0x7b,
+            0x34, 0x29, 0xea, 0xed, 0xb4, 0xed, 0x53, 0x76, 0x44, 0x1a, 0x77, 0xed, 0x43, 0x85,
+            0x1a, 0xd7, 0x7f, 0x16, 0xf5, 0x41, 0xdf, 0xd2, 0x69, 0xd5, 0x0d, 0x6a, 0x5f, 0x14,
+            0xfb, 0x0a, 0xab, 0x1c, 0xbb, 0x4c, 0x15, 0x50, 0xbe, 0x97, 0xf7, 0xab, 0x40, 0x66,
+            0x19, 0x3c, 0x4c, 0xaa, 0x77, 0x3d, 0xad, 0x38, 0x01, 0x4b, 0xd2, 0x09, 0x2f, 0xa7,
+            0x55, 0xc8, 0x24, 0xbb, 0x5e, 0x54, 0xc4, 0xf3, 0x6f, 0xfd, 0xa9, 0xfc, 0xea, 0x70,
+            0xb9, 0xc6, 0xe6, 0x93, 0xe1, 0x48, 0xc1, 0x51,
+        ];
+
+        check_vector(
+            &key_1,
+            &key_2,
+            KeyType::Aes256,
+            0xff,
+            &plaintext,
+            &ciphertext,
+        );
+    }
+
+    #[test]
+    fn ciphertext_stealing_test() {
+        // vectors 15 to 18, with lengths just over one block
+        let key_1 = [
+            0xff, 0xfe, 0xfd, 0xfc, 0xfb, 0xfa, 0xf9, 0xf8, 0xf7, 0xf6, 0xf5, 0xf4, 0xf3, 0xf2,
+            0xf1, 0xf0,
+        ];
+        let key_2 = [
+            0xbf, 0xbe, 0xbd, 0xbc, 0xbb, 0xba, 0xb9, 0xb8, 0xb7, 0xb6, 0xb5, 0xb4, 0xb3, 0xb2,
+            0xb1, 0xb0,
+        ];
+        let ciphertexts: [&[u8]; 4] = [
+            &[
+                0x6c, 0x16, 0x25, 0xdb, 0x46, 0x71, 0x52, 0x2d, 0x3d, 0x75, 0x99, 0x60, 0x1d, 0xe7,
+                0xca, 0x09, 0xed,
+            ],
+            &[
+                0xd0, 0x69, 0x44, 0x4b, 0x7a, 0x7e, 0x0c, 0xab, 0x09, 0xe2, 0x44, 0x47, 0xd2, 0x4d,
+                0xeb, 0x1f, 0xed, 0xbf,
+            ],
+            &[
+                0xe5, 0xdf, 0x13, 0x51, 0xc0, 0x54, 0x4b, 0xa1, 0x35, 0x0b, 0x33, 0x63, 0xcd, 0x8e,
+                0xf4, 0xbe, 0xed, 0xbf, 0x9d,
+            ],
+            &[
+                0x9d, 0x84, 0xc8, 0x13, 0xf7, 0x19, 0xaa, 0x2c, 0x7b, 0xe3, 0xf6, 0x61, 0x71, 0xc7,
+                0xc5, 0xc2, 0xed, 0xbf, 0x9d, 0xac,
+            ],
+        ];
+
+        for ciphertext in ciphertexts {
+            let plaintext: Vec<u8> = (0..ciphertext.len() as u8).collect();
+            check_vector(
+                &key_1,
+                &key_2,
+                KeyType::Aes128,
+                0x123456789a,
+                &plaintext,
+                ciphertext,
+            );
+        }
+    }
+
+    #[test]
+    fn sectors_test() {
+        let data_key = key_expansion(&[0x11; 16], KeyType::Aes128);
+        let tweak_key = key_expansion(&[0x22; 16], KeyType::Aes128);
+        let plaintext: Vec<u8> = (0..90).collect();
+
+        let ciphertext =
+            xts_encrypt_sectors(&plaintext, 7, 32, &data_key, &tweak_key, KeyType::Aes128).unwrap();
+
+        // each sector is encrypted independently with its own tweak, and the
+        // final short sector uses ciphertext stealing
+        let second_sector = xts_encrypt(
+            &plaintext[32..64],
+            8,
+            &data_key,
+            &tweak_key,
+            KeyType::Aes128,
+        )
+        .unwrap();
+        assert_eq!(ciphertext[32..64], second_sector);
+
+        let last_sector =
+            xts_encrypt(&plaintext[64..], 9, &data_key, &tweak_key, KeyType::Aes128).unwrap();
+        assert_eq!(ciphertext[64..], last_sector);
+
+        let decrypted =
+            xts_decrypt_sectors(&ciphertext, 7, 32, &data_key, &tweak_key, KeyType::Aes128)
+                .unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        let result = xts_encrypt_sectors(&[0; 40], 0, 32, &data_key, &tweak_key, KeyType::Aes128);
+        assert_eq!(result, Err(XtsError::DataUnitTooShort(8)));
+
+        for (len, sector_size) in [(0, 0), (40, 0), (0, 15)] {
+            let result = xts_encrypt_sectors(
+                &plaintext[..len],
+                0,
+                sector_size,
+                &data_key,
+                &tweak_key,
+                KeyType::Aes128,
+            );
+            assert_eq!(result, Err(XtsError::DataUnitTooShort(sector_size)));
+            let result = xts_decrypt_sectors(
+                &plaintext[..len],
+                0,
+                sector_size,
+                &data_key,
+                &tweak_key,
+                KeyType::Aes128,
+            );
+            assert_eq!(result, Err(XtsError::DataUnitTooShort(sector_size)));
+        }
+    }
+
+    #[test]
+    fn parallel_test() {
+        let data_key = key_expansion(&[0x11; 16], KeyType::Aes128);
+        let tweak_key = key_expansion(&[0x22; 16], KeyType::Aes128);
+        let plaintext: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        for len in [0, 16, 512, 600, 1000] {
+            let expected = xts_encrypt_sectors(
+                &plaintext[..len],
+                5,
+                64,
+                &data_key,
+                &tweak_key,
+                KeyType::Aes128,
+            )
+            .unwrap();
+
+            for threads in [0, 1, 3, 8, 100] {
+                let ciphertext = xts_encrypt_sectors_parallel(
+                    &plaintext[..len],
+                    5,
+                    64,
+                    threads,
+                    &data_key,
+                    &tweak_key,
+                    KeyType::Aes128,
+                )
+                .unwrap();
+                assert_eq!(ciphertext, expected);
+
+                let result = xts_decrypt_sectors_parallel(
+                    &ciphertext,
+                    5,
+                    64,
+                    threads,
+                    &data_key,
+                    &tweak_key,
+                    KeyType::Aes128,
+                )
+                .unwrap();
+                assert_eq!(result, &plaintext[..len]);
+            }
+        }
+
+        for (len, sector_size) in [(40, 32), (40, 8), (8, 32), (0, 0), (40, 0), (0, 8)] {
+            let serial = xts_encrypt_sectors(
+                &plaintext[..len],
+                0,
+                sector_size,
+                &data_key,
+                &tweak_key,
+                KeyType::Aes128,
+            );
+            let parallel = xts_encrypt_sectors_parallel(
+                &plaintext[..len],
+                0,
+                sector_size,
+                4,
+                &data_key,
+                &tweak_key,
+                KeyType::Aes128,
+            );
+            assert_eq!(parallel, serial);
+            assert!(parallel.is_err());
+        }
+    }
+
+    #[test]
+    fn streaming_test() {
+        let data_key = key_expansion(&[0x11; 32], KeyType::Aes256);
+        let tweak_key = key_expansion(&[0x22; 32], KeyType::Aes256);
+        let plaintext: Vec<u8> = (0..90).collect();
+
+        for len in [0, 16, 32, 50, 90] {
+            let expected = xts_encrypt_sectors(
+                &plaintext[..len],
+                3,
+                32,
+                &data_key,
+                &tweak_key,
+                KeyType::Aes256,
+            )
+            .unwrap();
+
+            check_streaming(
+                &plaintext[..len],
+                &expected,
+                || XtsEncryptor::new(3, 32, &data_key, &tweak_key, KeyType::Aes256).unwrap(),
+                || XtsDecryptor::new(3, 32, &data_key, &tweak_key, KeyType::Aes256).unwrap(),
+            );
+        }
+
+        let result = XtsEncryptor::new(0, 8, &data_key, &tweak_key, KeyType::Aes256);
+        assert!(matches!(result, Err(XtsError::DataUnitTooShort(8))));
+
+        let mut encryptor =
+            XtsEncryptor::new(0, 32, &data_key, &tweak_key, KeyType::Aes256).unwrap();
+        assert_eq!(encryptor.update(&[0; 40]).len(), 32);
+        assert_eq!(encryptor.finalize(), Err(StreamError::TooShort(8)));
+    }
+}